@@ -0,0 +1,213 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek},
+    ops::ControlFlow,
+    path::Path,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use flate2::read::MultiGzDecoder;
+use snafu::ResultExt;
+
+use crate::reader::{ArchiveSnafu, Error, IoSnafu};
+
+/// Number of bytes needed to detect any supported archive format (the size of a tar header).
+pub const DETECT_LEN: usize = 512;
+
+/// Archive formats DICOM objects can be read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    /// Either a gzipped tar archive or a single gzipped file.
+    Gzip,
+}
+
+impl ArchiveKind {
+    /// Detects the archive format from the first `DETECT_LEN` bytes of the input, if available.
+    pub fn detect(buf: &[u8]) -> Option<ArchiveKind> {
+        // the preamble can contain anything, don't mistake a DICOM file for an archive
+        if buf.get(128..132) == Some(b"DICM") {
+            return None;
+        }
+
+        if buf.starts_with(b"PK\x03\x04") || buf.starts_with(b"PK\x05\x06") {
+            return Some(ArchiveKind::Zip);
+        }
+
+        if buf.starts_with(&[0x1F, 0x8B]) {
+            return Some(ArchiveKind::Gzip);
+        }
+
+        // both POSIX "ustar\0" and GNU "ustar " magic
+        if buf.get(257..262) == Some(b"ustar") {
+            return Some(ArchiveKind::Tar);
+        }
+
+        None
+    }
+
+    /// Detects the archive format of a file. Errors are ignored, they're reported when the file is read as DICOM.
+    pub fn detect_file(path: &Path) -> Option<ArchiveKind> {
+        let mut buf = Vec::with_capacity(DETECT_LEN);
+
+        File::open(path)
+            .ok()?
+            .take(DETECT_LEN as u64)
+            .read_to_end(&mut buf)
+            .ok()?;
+
+        ArchiveKind::detect(&buf)
+    }
+}
+
+/// Items produced from the entries of an archive by `map_entries()`, as they're read.
+pub struct MappedEntries<T> {
+    receiver: Receiver<Result<T, Error>>,
+}
+
+impl<T> Iterator for MappedEntries<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver
+            .recv()
+            .ok()
+    }
+}
+
+/// Maps each regular file in the archive to zero or one item using `read`, see `for_each_entry()`.
+///
+/// The archive is read on a separate thread, one entry ahead of the returned iterator, so that large archives are
+/// not collected into memory. Reading stops once the iterator is dropped. An error reading the archive itself is
+/// the last item.
+pub fn map_entries<R, T, F>(
+    input: R,
+    kind: ArchiveKind,
+    mut read: F,
+) -> MappedEntries<T>
+where
+    R: Read + Seek + Send + 'static,
+    T: Send + 'static,
+    F: FnMut(&str, &mut dyn Read) -> Option<T> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::sync_channel(1);
+
+    thread::spawn(move || {
+        let result = for_each_entry(input, kind, |name, entry| {
+            let Some(item) = read(name, entry) else {
+                return ControlFlow::Continue(());
+            };
+
+            // the receiver is gone if the iterator has been dropped
+            match sender.send(Ok(item)) {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
+            }
+        });
+
+        if let Err(e) = result {
+            let _ = sender.send(Err(e));
+        }
+    });
+
+    MappedEntries { receiver }
+}
+
+/// Calls `read` with the name and content of each regular file in the archive, in archive order, until it breaks.
+/// Entries are read directly from the archive, nothing is extracted to disk.
+///
+/// A single gzipped file is reported as one entry named after the original file name stored in the gzip header, if any.
+pub fn for_each_entry<R: Read + Seek>(
+    input: R,
+    kind: ArchiveKind,
+    mut read: impl FnMut(&str, &mut dyn Read) -> ControlFlow<()>,
+) -> Result<(), Error> {
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(input).context(ArchiveSnafu)?;
+
+            for index in 0..archive.len() {
+                let mut entry = archive
+                    .by_index(index)
+                    .context(ArchiveSnafu)?;
+
+                if !entry.is_file() {
+                    continue;
+                }
+
+                let name = entry
+                    .name()
+                    .context(ArchiveSnafu)?
+                    .into_owned();
+
+                if read(&name, &mut entry).is_break() {
+                    break;
+                }
+            }
+
+            Ok(())
+        }
+        ArchiveKind::Tar => for_each_tar_entry(input, read),
+        ArchiveKind::Gzip => {
+            let mut decoder = MultiGzDecoder::new(input);
+
+            // peek into the decompressed data to tell a tarball from a single file
+            let mut buf = Vec::with_capacity(DETECT_LEN);
+            decoder
+                .by_ref()
+                .take(DETECT_LEN as u64)
+                .read_to_end(&mut buf)
+                .context(IoSnafu)?;
+
+            if ArchiveKind::detect(&buf) == Some(ArchiveKind::Tar) {
+                return for_each_tar_entry(Cursor::new(buf).chain(decoder), read);
+            }
+
+            let name = decoder
+                .header()
+                .and_then(|header| header.filename())
+                .map(|name| String::from_utf8_lossy(name).into_owned())
+                .unwrap_or_default();
+
+            let _ = read(&name, &mut Cursor::new(buf).chain(decoder));
+
+            Ok(())
+        }
+    }
+}
+
+fn for_each_tar_entry<R: Read>(
+    input: R,
+    mut read: impl FnMut(&str, &mut dyn Read) -> ControlFlow<()>,
+) -> Result<(), Error> {
+    let mut archive = tar::Archive::new(input);
+
+    for entry in archive
+        .entries()
+        .context(IoSnafu)?
+    {
+        let mut entry = entry.context(IoSnafu)?;
+
+        if !entry
+            .header()
+            .entry_type()
+            .is_file()
+        {
+            continue;
+        }
+
+        let name = entry
+            .path()
+            .context(IoSnafu)?
+            .to_string_lossy()
+            .into_owned();
+
+        if read(&name, &mut entry).is_break() {
+            break;
+        }
+    }
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use dicom::core::PrimitiveValue;
+use dicom::{
+    core::{PrimitiveValue, Tag},
+    object::InMemDicomObject,
+};
 use itertools::Itertools;
 use nu_protocol::{Span, Value};
 
@@ -8,6 +11,96 @@ pub fn trim_string(s: &String) -> &str {
     s.trim_matches(TRIM_CHARS)
 }
 
+/// Trimmed string value of the attribute, `None` if the attribute is missing or empty.
+pub fn get_string(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> Option<String> {
+    let value = obj
+        .get(tag)?
+        .to_str()
+        .ok()?
+        .into_owned();
+    let value = trim_string(&value);
+
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// First integer value of the attribute, `None` if the attribute is missing or the value doesn't fit `T`.
+pub fn get_int<T: TryFrom<i64>>(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> Option<T> {
+    let value: i64 = obj
+        .get(tag)?
+        .to_int()
+        .ok()?;
+
+    T::try_from(value).ok()
+}
+
+pub fn get_ints(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> Option<Vec<i64>> {
+    obj.get(tag)?
+        .to_multi_int()
+        .ok()
+}
+
+pub fn get_float(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> Option<f64> {
+    obj.get(tag)?
+        .to_float64()
+        .ok()
+}
+
+pub fn get_floats(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> Option<Vec<f64>> {
+    obj.get(tag)?
+        .to_multi_float64()
+        .ok()
+}
+
+/// Items of a sequence attribute, empty if the attribute is missing or not a sequence.
+pub fn items(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> &[InMemDicomObject] {
+    obj.get(tag)
+        .and_then(|e| e.items())
+        .unwrap_or_default()
+}
+
+/// Bytes of an OB or OW value, words being read as little endian.
+pub fn bytes(data: &PrimitiveValue) -> Vec<u8> {
+    match data {
+        PrimitiveValue::U16(words) => words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect(),
+        data => data
+            .to_bytes()
+            .into_owned(),
+    }
+}
+
+/// 16-bit words of an OB or OW value, bytes being read as little endian.
+pub fn words(data: &PrimitiveValue) -> Vec<u16> {
+    match data {
+        PrimitiveValue::U16(words) => words.to_vec(),
+        data => data
+            .to_bytes()
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect(),
+    }
+}
+
 fn is_nothing(value: &PrimitiveValue) -> bool {
     if let PrimitiveValue::Empty = value {
         return true;
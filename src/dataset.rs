@@ -0,0 +1,254 @@
+use std::{cell::Cell, io::Read, rc::Rc};
+
+use dicom::{
+    core::{
+        DataElementHeader, Length, Tag, VR,
+        value::{DataSetSequence, Value as DicomValue},
+    },
+    dictionary_std::tags,
+    encoding::{Codec, TransferSyntax},
+    object::{InMemDicomObject, mem::InMemElement},
+    parser::dataset::{DataSetReader, DataToken, read::Error as DataSetError},
+};
+use snafu::ResultExt;
+
+use crate::reader::{DatasetSnafu, Error, UnsupportedTransferSyntaxSnafu};
+
+/// Reads a data set without the file meta group, up to the pixel data.
+pub fn read_dataset<R: Read>(
+    reader: R,
+    ts: &TransferSyntax,
+) -> Result<InMemDicomObject, Error> {
+    let mut builder = ObjectBuilder::default();
+    builder.read(reader, ts)?;
+
+    Ok(builder.finish())
+}
+
+enum Frame {
+    Item(Vec<InMemElement>),
+    Sequence { tag: Tag, len: Length, items: Vec<InMemDicomObject> },
+    PixelSequence,
+    Fragment,
+}
+
+/// Builds an object from data set tokens, keeping everything read so far if the tokens end prematurely.
+#[derive(Default)]
+pub struct ObjectBuilder {
+    root: Vec<InMemElement>,
+    stack: Vec<Frame>,
+}
+
+impl ObjectBuilder {
+    /// Reads data set tokens from `reader`, adding them to the object. Stops before the pixel data.
+    pub fn read<R: Read>(
+        &mut self,
+        reader: R,
+        ts: &TransferSyntax,
+    ) -> Result<(), Error> {
+        let result = match ts.codec() {
+            Codec::Dataset(Some(adapter)) => self.read_tokens(adapter.adapt_reader(Box::new(reader)), ts),
+            Codec::Dataset(None) => return UnsupportedTransferSyntaxSnafu { uid: ts.uid() }.fail(),
+            Codec::None | Codec::EncapsulatedPixelData(..) => self.read_tokens(reader, ts),
+        };
+
+        result.context(DatasetSnafu { dataset_start: None })
+    }
+
+    fn read_tokens<R: Read>(
+        &mut self,
+        reader: R,
+        ts: &TransferSyntax,
+    ) -> Result<(), DataSetError> {
+        let dataset = DataSetReader::new_with_ts(reader, ts)?;
+        let mut header: Option<DataElementHeader> = None;
+
+        for token in dataset {
+            match token? {
+                DataToken::ElementHeader(h) => {
+                    // pixel data are never read
+                    if self
+                        .stack
+                        .is_empty()
+                        && h.tag >= tags::PIXEL_DATA
+                    {
+                        return Ok(());
+                    }
+                    header = Some(h);
+                }
+                DataToken::PrimitiveValue(value) => {
+                    if let Some(h) = header.take() {
+                        self.push_element(InMemElement::new_with_len(h.tag, h.vr, h.len, DicomValue::Primitive(value)));
+                    }
+                }
+                DataToken::SequenceStart { tag, len } => {
+                    self.stack
+                        .push(Frame::Sequence { tag, len, items: Vec::new() });
+                }
+                DataToken::PixelSequenceStart => {
+                    if self
+                        .stack
+                        .is_empty()
+                    {
+                        return Ok(());
+                    }
+                    self.stack
+                        .push(Frame::PixelSequence);
+                }
+                DataToken::ItemStart { .. } => match self
+                    .stack
+                    .last()
+                {
+                    Some(Frame::Sequence { .. }) => {
+                        self.stack
+                            .push(Frame::Item(Vec::new()));
+                    }
+                    // the offset table and fragments of encapsulated pixel data (e.g. of an icon image) are items too, their item
+                    // ends must not close the enclosing frames
+                    Some(Frame::PixelSequence) => {
+                        self.stack
+                            .push(Frame::Fragment);
+                    }
+                    _ => {}
+                },
+                DataToken::ItemEnd | DataToken::SequenceEnd => {
+                    self.close_frame();
+                }
+                DataToken::ItemValue(_) | DataToken::OffsetTable(_) => {
+                    // fragments of encapsulated pixel data are skipped
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// True if the builder is inside a sequence, i.e. the data set ended prematurely.
+    pub fn in_sequence(&self) -> bool {
+        !self
+            .stack
+            .is_empty()
+    }
+
+    fn push_element(
+        &mut self,
+        element: InMemElement,
+    ) {
+        match self
+            .stack
+            .last_mut()
+        {
+            Some(Frame::Item(elements)) => elements.push(element),
+            Some(_) => {
+                // elements can't be directly in a sequence, ignore
+            }
+            None => self
+                .root
+                .push(element),
+        }
+    }
+
+    /// Closes the innermost item or sequence, adding it to its parent.
+    fn close_frame(&mut self) {
+        match self
+            .stack
+            .pop()
+        {
+            Some(Frame::Item(elements)) => {
+                if let Some(Frame::Sequence { items, .. }) = self
+                    .stack
+                    .last_mut()
+                {
+                    items.push(InMemDicomObject::from_element_iter(elements));
+                }
+            }
+            Some(Frame::Sequence { tag, len, items }) => {
+                self.push_element(InMemElement::new_with_len(tag, VR::SQ, len, DicomValue::Sequence(DataSetSequence::new(items, len))));
+            }
+            Some(Frame::PixelSequence | Frame::Fragment) | None => {}
+        }
+    }
+
+    /// Closes all open sequences and items, keeping what has been read so far.
+    pub fn close_all(&mut self) {
+        while self.in_sequence() {
+            self.close_frame();
+        }
+    }
+
+    pub fn finish(mut self) -> InMemDicomObject {
+        self.close_all();
+        InMemDicomObject::from_element_iter(self.root)
+    }
+}
+
+/// Returns the offsets (relative to the start of `reader`) of the items of the top level sequence `sequence_tag`,
+/// e.g. of the directory records of a DICOMDIR. Data sets of transfer syntaxes with an adapter (e.g. deflated)
+/// aren't supported, the offsets wouldn't map to the input.
+pub fn sequence_item_offsets<R: Read>(
+    reader: R,
+    ts: &TransferSyntax,
+    sequence_tag: Tag,
+) -> Result<Vec<u64>, Error> {
+    if !matches!(ts.codec(), Codec::None | Codec::EncapsulatedPixelData(..)) {
+        return UnsupportedTransferSyntaxSnafu { uid: ts.uid() }.fail();
+    }
+
+    let position = Rc::new(Cell::new(0));
+    let reader = CountingReader { inner: reader, position: Rc::clone(&position) };
+
+    let dataset = DataSetReader::new_with_ts(reader, ts).context(DatasetSnafu { dataset_start: None })?;
+
+    // tags of open sequences, `None` for items
+    let mut stack: Vec<Option<Tag>> = Vec::new();
+    let mut offsets = Vec::new();
+
+    for token in dataset {
+        match token.context(DatasetSnafu { dataset_start: None })? {
+            DataToken::SequenceStart { tag, .. } => stack.push(Some(tag)),
+            DataToken::PixelSequenceStart => stack.push(Some(tags::PIXEL_DATA)),
+            DataToken::ItemStart { .. } => {
+                if stack == [Some(sequence_tag)] {
+                    // the token is produced right after the item header is read
+                    offsets.push(position.get() - ITEM_HEADER_LEN);
+                }
+                stack.push(None);
+            }
+            DataToken::ItemEnd | DataToken::SequenceEnd => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(offsets)
+}
+
+/// Size of an item header (tag and length).
+const ITEM_HEADER_LEN: u64 = 8;
+
+/// Reader adapter keeping track of the number of bytes read.
+struct CountingReader<R> {
+    inner: R,
+    position: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(
+        &mut self,
+        buf: &mut [u8],
+    ) -> std::io::Result<usize> {
+        let read = self
+            .inner
+            .read(buf)?;
+
+        self.position
+            .set(
+                self.position
+                    .get()
+                    + read as u64,
+            );
+
+        Ok(read)
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use dicom::dictionary_std::tags;
+use indexmap::IndexMap;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Value};
+
+use crate::convert::{get_string, trim_string};
+use crate::hash::{HashOptions, bytes_hash, dataset_hash};
+use crate::input::{DicomSource, collect_input};
+use crate::plugin::DcmPlugin;
+use crate::reader::{ErrorKind, read_dcm_stream_with_pixel_data};
+
+/// `dcm dedupe` command, finds files sharing a SOPInstanceUID.
+pub struct DcmDedupeCommand;
+
+impl PluginCommand for DcmDedupeCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm dedupe"
+    }
+
+    fn description(&self) -> &str {
+        "Find DICOM files sharing a SOPInstanceUID and check whether their contents match."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .switch("all", "Report all SOPInstanceUIDs, including those stored only once.", Some('a'))
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "duplicate".to_string(), "conflict".to_string(), "hash".to_string()])
+            .extra_description(
+                "Returns a record per SOPInstanceUID stored more than once, with a `status` of `identical` (byte-identical files), `dataset-identical` \
+                 (same data set, but e.g. different file meta group, preamble or transfer syntax) or `conflicting`. The `files` column lists the \
+                 `path`, `file_hash` and `dataset_hash` (as computed by `dcm hash`) of each file. With `--all`, SOPInstanceUIDs stored once have the status `unique`. \
+                 Non-DICOM files are skipped.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Find conflicting copies of instances in the current directory",
+            example: "ls **/* | where type == file | dcm dedupe | where status == conflicting",
+            result: None,
+        }]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let all = call.has_flag("all")?;
+
+        let mut instances: IndexMap<String, Vec<FileHashes>> = IndexMap::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let bytes = source
+                .bytes()
+                .map_err(|e| source.error(&e, span))?;
+            let obj = match read_dcm_stream_with_pixel_data(Cursor::new(&bytes[..]), None) {
+                Ok(obj) => obj,
+                // e.g. reports or viewers next to the DICOM files
+                Err(e) if e.kind() == ErrorKind::NotDicom => continue,
+                Err(e) => return Err(source.error(&e, span)),
+            };
+
+            let sop_instance_uid = get_string(&obj, tags::SOP_INSTANCE_UID).unwrap_or_else(|| {
+                trim_string(
+                    &obj.meta()
+                        .media_storage_sop_instance_uid,
+                )
+                .to_string()
+            });
+
+            instances
+                .entry(sop_instance_uid)
+                .or_default()
+                .push(FileHashes {
+                    path: source
+                        .path()
+                        .map(|path| Value::string(path.to_string_lossy(), span))
+                        .unwrap_or_else(|| Value::nothing(span)),
+                    file_hash: bytes_hash(&bytes),
+                    dataset_hash: dataset_hash(&obj, &HashOptions::default()),
+                });
+        }
+
+        let output = instances
+            .into_iter()
+            .filter(|(_, files)| all || files.len() > 1)
+            .map(|(sop_instance_uid, files)| group_to_value(sop_instance_uid, files, call.head))
+            .collect();
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+struct FileHashes {
+    path: Value,
+    file_hash: String,
+    dataset_hash: String,
+}
+
+/// Classifies copies of the same instance.
+fn status(files: &[FileHashes]) -> &'static str {
+    let all_equal = |hash: fn(&FileHashes) -> &str| {
+        files
+            .windows(2)
+            .all(|pair| hash(&pair[0]) == hash(&pair[1]))
+    };
+
+    if files.len() == 1 {
+        "unique"
+    } else if all_equal(|f| &f.file_hash) {
+        "identical"
+    } else if all_equal(|f| &f.dataset_hash) {
+        "dataset-identical"
+    } else {
+        "conflicting"
+    }
+}
+
+fn group_to_value(
+    sop_instance_uid: String,
+    files: Vec<FileHashes>,
+    span: Span,
+) -> Value {
+    let status = status(&files);
+
+    let files = files
+        .into_iter()
+        .map(|file| {
+            Value::record(
+                Record::from_iter([
+                    ("path".to_string(), file.path),
+                    ("file_hash".to_string(), Value::string(file.file_hash, span)),
+                    ("dataset_hash".to_string(), Value::string(file.dataset_hash, span)),
+                ]),
+                span,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Value::record(
+        Record::from_iter([
+            ("SOPInstanceUID".to_string(), Value::string(sop_instance_uid, span)),
+            ("status".to_string(), Value::string(status, span)),
+            ("count".to_string(), Value::int(files.len() as i64, span)),
+            ("files".to_string(), Value::list(files, span)),
+        ]),
+        span,
+    )
+}
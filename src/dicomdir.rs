@@ -0,0 +1,829 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+
+use dicom::core::value::DataSetSequence;
+use dicom::core::{DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::encoding::TransferSyntaxIndex;
+use dicom::object::{DefaultDicomObject, FileMetaTable, FileMetaTableBuilder, InMemDicomObject};
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+use indexmap::IndexMap;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, SyntaxShape, Value};
+use snafu::ResultExt;
+
+use crate::convert::{get_string, trim_string};
+use crate::dataset::sequence_item_offsets;
+use crate::dcm::DicomDump;
+use crate::input::{DicomSource, collect_input, resolve_path};
+use crate::plugin::DcmPlugin;
+use crate::reader::{ErrorKind, read_dcm_stream};
+use crate::uid::generate_uid;
+use crate::writer::{BuildMetaSnafu, Error as WriterError, write_dcm};
+
+/// Elements linking directory records together. They're replaced by the tree structure in the output.
+const LINK_TAGS: [Tag; 3] =
+    [tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD, tags::RECORD_IN_USE_FLAG, tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY];
+
+/// `dcm dicomdir` command, returns the directory records of a DICOMDIR as a tree.
+pub struct DcmDicomdirCommand;
+
+impl PluginCommand for DcmDicomdirCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm dicomdir"
+    }
+
+    fn description(&self) -> &str {
+        "Parse a DICOMDIR into a tree of directory records (e.g. PATIENT, STUDY, SERIES, IMAGE)."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "dicomdir".to_string(), "media".to_string()])
+            .extra_description(
+                "Directory records are linked by following their offsets, inactive records are skipped. Each record has a `children` column \
+                 with its lower level records, records referencing a file have a `path` column with the file path resolved relative to the DICOMDIR.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example { description: "List patients on a DICOM media", example: "\"/media/cdrom/DICOMDIR\" | dcm dicomdir", result: None },
+            Example {
+                description: "List all image files of the first patient",
+                example: "\"DICOMDIR\" | dcm dicomdir | get 0.children.children.children | flatten | flatten | get path",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            output.extend(read_dicomdir(plugin, &source, span)?);
+        }
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+/// Reads the root directory records of a DICOMDIR, with lower level records nested in `children`.
+pub fn read_dicomdir(
+    plugin: &DcmPlugin,
+    source: &DicomSource,
+    span: Span,
+) -> Result<Vec<Value>, LabeledError> {
+    let bytes = source
+        .bytes()
+        .map_err(|e| source.error(&e, span))?;
+
+    let obj = read_dcm_stream(Cursor::new(&bytes[..]), None).map_err(|e| source.error(&e, span))?;
+
+    let records = obj
+        .get(tags::DIRECTORY_RECORD_SEQUENCE)
+        .and_then(|e| e.items())
+        .ok_or_else(|| LabeledError::new("Not a DICOMDIR").with_label("Input has no DirectoryRecordSequence", span))?;
+
+    // directory records refer to each other by their offset in the file
+    let offsets = record_offsets(&bytes, &obj).map_err(|e| LabeledError::new("Failed to locate directory records").with_label(e, span))?;
+    if offsets.len() != records.len() {
+        return Err(LabeledError::new("Failed to locate directory records").with_label("Unexpected number of directory records", span));
+    }
+
+    let tree = DirectoryTree {
+        records: offsets
+            .into_iter()
+            .zip(records)
+            .collect(),
+        base_dir: source
+            .path()
+            .and_then(Path::parent),
+        dumper: DicomDump { dcm_dictionary: &plugin.dcm_dictionary },
+        span,
+    };
+
+    let root = get_offset(&obj, tags::OFFSET_OF_THE_FIRST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY);
+
+    tree.read_entity(root, &mut HashSet::new())
+}
+
+/// Returns file offsets of the items of DirectoryRecordSequence.
+fn record_offsets(
+    bytes: &[u8],
+    obj: &DefaultDicomObject,
+) -> Result<Vec<u64>, String> {
+    let meta = obj.meta();
+
+    let magic_start = if bytes.get(128..132) == Some(b"DICM") {
+        128
+    } else if bytes.starts_with(b"DICM") {
+        0
+    } else {
+        return Err("DICOMDIR must start with a preamble or the DICM marker".to_string());
+    };
+
+    // "DICM" and File Meta Information Group Length element precede the rest of the meta group
+    let dataset_start = magic_start + 4 + 12 + meta.information_group_length as usize;
+
+    let uid = meta.transfer_syntax();
+    let ts = TransferSyntaxRegistry
+        .get(uid)
+        .ok_or_else(|| format!("Unsupported transfer syntax `{uid}`"))?;
+
+    let dataset = bytes
+        .get(dataset_start..)
+        .unwrap_or_default();
+
+    let offsets = sequence_item_offsets(dataset, ts, tags::DIRECTORY_RECORD_SEQUENCE).map_err(|e| e.to_string())?;
+
+    Ok(offsets
+        .into_iter()
+        .map(|offset| dataset_start as u64 + offset)
+        .collect())
+}
+
+struct DirectoryTree<'a, 'b> {
+    records: HashMap<u64, &'a InMemDicomObject>,
+    base_dir: Option<&'a Path>,
+    dumper: DicomDump<'a, 'b>,
+    span: Span,
+}
+
+impl DirectoryTree<'_, '_> {
+    /// Converts the records of a directory entity, starting at `offset` and following the next record offsets.
+    fn read_entity(
+        &self,
+        mut offset: u64,
+        visited: &mut HashSet<u64>,
+    ) -> Result<Vec<Value>, LabeledError> {
+        let mut values = Vec::new();
+
+        while offset != 0 {
+            if !visited.insert(offset) {
+                return Err(
+                    LabeledError::new("Invalid DICOMDIR").with_label(format!("Directory record at offset {offset} is referenced twice"), self.span)
+                );
+            }
+
+            let record = self
+                .records
+                .get(&offset)
+                .ok_or_else(|| LabeledError::new("Invalid DICOMDIR").with_label(format!("No directory record at offset {offset}"), self.span))?;
+
+            // inactive records are skipped, including their lower level records
+            if is_in_use(record) {
+                values.push(self.read_record(record, visited)?);
+            }
+
+            offset = get_offset(record, tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD);
+        }
+
+        Ok(values)
+    }
+
+    fn read_record(
+        &self,
+        record: &InMemDicomObject,
+        visited: &mut HashSet<u64>,
+    ) -> Result<Value, LabeledError> {
+        let mut attributes = record.clone();
+        for tag in LINK_TAGS {
+            attributes.remove_element(tag);
+        }
+
+        let mut index_map = IndexMap::with_capacity(32);
+        self.dumper
+            .make_row_from_dicom_object(&self.span, &mut index_map, &attributes);
+
+        if let Some(path) = self.referenced_file(record) {
+            index_map.insert("path".to_string(), Value::string(path.to_string_lossy(), self.span));
+        }
+
+        let children = self.read_entity(get_offset(record, tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY), visited)?;
+        index_map.insert("children".to_string(), Value::list(children, self.span));
+
+        Ok(Value::record(Record::from_iter(index_map), self.span))
+    }
+
+    /// Returns the path of the file referenced by ReferencedFileID, relative to the DICOMDIR if its path is known.
+    fn referenced_file(
+        &self,
+        record: &InMemDicomObject,
+    ) -> Option<PathBuf> {
+        let components = record
+            .get(tags::REFERENCED_FILE_ID)?
+            .to_multi_str()
+            .ok()?;
+
+        let path: PathBuf = components
+            .iter()
+            .map(|c| c.trim_end_matches([' ', '\0']))
+            .collect();
+
+        match self.base_dir {
+            Some(base_dir) => Some(base_dir.join(path)),
+            None => Some(path),
+        }
+    }
+}
+
+fn get_offset(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> u64 {
+    obj.get(tag)
+        .and_then(|e| {
+            e.to_int::<u64>()
+                .ok()
+        })
+        .unwrap_or(0)
+}
+
+fn is_in_use(record: &InMemDicomObject) -> bool {
+    record
+        .get(tags::RECORD_IN_USE_FLAG)
+        .and_then(|e| {
+            e.to_int::<u16>()
+                .ok()
+        })
+        .is_none_or(|flag| flag != 0)
+}
+
+/// `dcm make-dicomdir` command, creates a DICOMDIR referencing the input files.
+pub struct DcmMakeDicomdirCommand;
+
+impl PluginCommand for DcmMakeDicomdirCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm make-dicomdir"
+    }
+
+    fn description(&self) -> &str {
+        "Create a DICOMDIR referencing a set of DICOM files."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .named(
+                "root",
+                SyntaxShape::String,
+                "Root directory of the file-set, i.e. where the DICOMDIR will be saved. Defaults to the current directory.",
+                Some('r'),
+            )
+            .named("file-set-id", SyntaxShape::String, "File-set ID, up to 16 characters A-Z, 0-9, space or _.", None)
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "dicomdir".to_string(), "media".to_string()])
+            .extra_description(
+                "Input files must be in the file-set root and their paths must conform to PS3.10 file ID naming, i.e. up to 8 path components of \
+                 up to 8 characters A-Z, 0-9 or _. Directory records are populated from the attributes of each file, which must have a \
+                 PatientID. The DICOMDIR is returned as binary data. Non-DICOM files are skipped.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Create a DICOMDIR for all files in the current directory",
+            example: "ls **/* | where type == file | dcm make-dicomdir | save DICOMDIR",
+            result: None,
+        }]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let root = match call.get_flag_value("root") {
+            Some(root) => resolve_path(root.as_str()?, current_dir.as_deref(), root.span())?,
+            None => current_dir
+                .clone()
+                .map_err(|e| LabeledError::new("Failed to get current working directory").with_label(e.to_string(), call.head))?,
+        };
+
+        let file_set_id = get_file_set_id_flag(call)?;
+
+        let mut builder = DicomdirBuilder::default();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let Some(path) = source.path() else {
+                return Err(LabeledError::new("`dcm make-dicomdir` expects files").with_label("Binary data can't be referenced by a DICOMDIR", span));
+            };
+
+            // read first, non-DICOM files (e.g. a README next to the images) don't need a valid file ID
+            let obj = match source.read() {
+                Ok(obj) => obj,
+                Err(e) if e.kind() == ErrorKind::NotDicom => continue,
+                Err(e) => return Err(source.error(&e, span)),
+            };
+
+            let file_id = file_id(&root, path)
+                .map_err(|e| LabeledError::new("Invalid file-set file name").with_label(format!("{e} [file {}]", path.to_string_lossy()), span))?;
+
+            // the DICOMDIR itself may be part of the input, e.g. `ls **/*`
+            if file_id == ["DICOMDIR"] {
+                continue;
+            }
+
+            builder
+                .add(file_id, &obj)
+                .map_err(|e| {
+                    LabeledError::new("Failed to add file to DICOMDIR").with_label(format!("{e} [file {}]", path.to_string_lossy()), span)
+                })?;
+        }
+
+        let bytes = builder
+            .build(file_set_id.as_deref())
+            .map_err(|e| LabeledError::new("Failed to create DICOMDIR").with_label(e.to_string(), call.head))?;
+
+        Ok(Value::binary(bytes, call.head).into_pipeline_data())
+    }
+}
+
+fn get_file_set_id_flag(call: &EvaluatedCall) -> Result<Option<String>, LabeledError> {
+    let Some(file_set_id) = call.get_flag_value("file-set-id") else {
+        return Ok(None);
+    };
+
+    let span = file_set_id.span();
+    let file_set_id = file_set_id.as_str()?;
+
+    if file_set_id.len() > 16
+        || !file_set_id
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == ' ' || c == '_')
+    {
+        return Err(LabeledError::new("Invalid file-set ID").with_label("Expected up to 16 characters A-Z, 0-9, space or _", span));
+    }
+
+    Ok(Some(file_set_id.to_string()))
+}
+
+/// Returns the components of the file ID of `path` in the file-set rooted at `root` (PS3.10 8.2).
+fn file_id(
+    root: &Path,
+    path: &Path,
+) -> Result<Vec<String>, String> {
+    let relative = path
+        .strip_prefix(root)
+        .map_err(|_| format!("File is not in the file-set root {}", root.to_string_lossy()))?;
+
+    let components = relative
+        .components()
+        .map(|component| match component {
+            Component::Normal(component) => component
+                .to_str()
+                .map(str::to_string),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("`{}` can't be used as a file ID", relative.to_string_lossy()))?;
+
+    if components.is_empty() || components.len() > 8 {
+        return Err(format!("`{}` must have 1 to 8 path components", relative.to_string_lossy()));
+    }
+
+    for component in &components {
+        if component.is_empty()
+            || component.len() > 8
+            || !component
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(format!("`{component}` is not a valid file ID component, expected up to 8 characters A-Z, 0-9 or _"));
+        }
+    }
+
+    Ok(components)
+}
+
+/// Attributes copied to PATIENT records (PS3.3 F.5.1), with their VR in case they're missing.
+const PATIENT_KEYS: &[(Tag, VR)] = &[(tags::PATIENT_NAME, VR::PN), (tags::PATIENT_ID, VR::LO)];
+
+/// Attributes copied to STUDY records (PS3.3 F.5.2).
+const STUDY_KEYS: &[(Tag, VR)] = &[
+    (tags::STUDY_DATE, VR::DA),
+    (tags::STUDY_TIME, VR::TM),
+    (tags::ACCESSION_NUMBER, VR::SH),
+    (tags::STUDY_DESCRIPTION, VR::LO),
+    (tags::STUDY_INSTANCE_UID, VR::UI),
+    (tags::STUDY_ID, VR::SH),
+];
+
+/// Attributes copied to SERIES records (PS3.3 F.5.3).
+const SERIES_KEYS: &[(Tag, VR)] = &[(tags::MODALITY, VR::CS), (tags::SERIES_INSTANCE_UID, VR::UI), (tags::SERIES_NUMBER, VR::IS)];
+
+/// Attributes copied to instance records (e.g. PS3.3 F.5.4).
+const INSTANCE_KEYS: &[(Tag, VR)] = &[(tags::INSTANCE_NUMBER, VR::IS)];
+
+/// A directory record and its lower level records, keyed by the UID (or ID) of the entity they describe.
+struct Node {
+    record: InMemDicomObject,
+    children: IndexMap<String, Node>,
+}
+
+impl Node {
+    fn new(record: InMemDicomObject) -> Self {
+        Self { record, children: IndexMap::new() }
+    }
+}
+
+/// Collects directory records of instances and writes them as a DICOMDIR.
+#[derive(Default)]
+struct DicomdirBuilder {
+    patients: IndexMap<String, Node>,
+}
+
+impl DicomdirBuilder {
+    fn add(
+        &mut self,
+        file_id: Vec<String>,
+        obj: &DefaultDicomObject,
+    ) -> Result<(), String> {
+        let meta = obj.meta();
+
+        let sop_instance_uid =
+            get_string(obj, tags::SOP_INSTANCE_UID).unwrap_or_else(|| trim_string(&meta.media_storage_sop_instance_uid).to_string());
+        if sop_instance_uid.is_empty() {
+            return Err("Missing SOPInstanceUID".to_string());
+        }
+
+        let study_instance_uid = get_string(obj, tags::STUDY_INSTANCE_UID).ok_or("Missing StudyInstanceUID")?;
+        let series_instance_uid = get_string(obj, tags::SERIES_INSTANCE_UID).ok_or("Missing SeriesInstanceUID")?;
+        // PatientID is type 1 in PATIENT records, instances without one can't be told apart from other patients
+        let patient_id = get_string(obj, tags::PATIENT_ID).ok_or("Missing PatientID")?;
+
+        let patient = self
+            .patients
+            .entry(patient_id)
+            .or_insert_with(|| Node::new(directory_record("PATIENT", obj, PATIENT_KEYS)));
+
+        let study = patient
+            .children
+            .entry(study_instance_uid)
+            .or_insert_with(|| Node::new(directory_record("STUDY", obj, STUDY_KEYS)));
+
+        let series = study
+            .children
+            .entry(series_instance_uid)
+            .or_insert_with(|| Node::new(directory_record("SERIES", obj, SERIES_KEYS)));
+
+        if series
+            .children
+            .contains_key(&sop_instance_uid)
+        {
+            return Err(format!("Duplicate SOPInstanceUID {sop_instance_uid}"));
+        }
+
+        let sop_class_uid = trim_string(&meta.media_storage_sop_class_uid);
+        let mut record = directory_record(instance_record_type(sop_class_uid), obj, INSTANCE_KEYS);
+        record.put(DataElement::new(tags::REFERENCED_FILE_ID, VR::CS, PrimitiveValue::Strs(file_id.into())));
+        record.put(DataElement::new(tags::REFERENCED_SOP_CLASS_UID_IN_FILE, VR::UI, PrimitiveValue::from(sop_class_uid)));
+        record.put(DataElement::new(tags::REFERENCED_SOP_INSTANCE_UID_IN_FILE, VR::UI, PrimitiveValue::from(sop_instance_uid.as_str())));
+        record.put(DataElement::new(tags::REFERENCED_TRANSFER_SYNTAX_UID_IN_FILE, VR::UI, PrimitiveValue::from(trim_string(&meta.transfer_syntax))));
+
+        series
+            .children
+            .insert(sop_instance_uid, Node::new(record));
+
+        Ok(())
+    }
+
+    /// Encodes the DICOMDIR. Directory records are written in depth-first order.
+    fn build(
+        &self,
+        file_set_id: Option<&str>,
+    ) -> Result<Vec<u8>, WriterError> {
+        let mut records = Vec::new();
+        flatten_nodes(&self.patients, None, &mut records);
+
+        let meta = FileMetaTableBuilder::new()
+            .media_storage_sop_class_uid(uids::MEDIA_STORAGE_DIRECTORY_STORAGE)
+            .media_storage_sop_instance_uid(generate_uid())
+            .transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN)
+            .build()
+            .context(BuildMetaSnafu)?;
+
+        // Offsets of the records are only known once they're encoded. All offsets are fixed size integers, so encode the
+        // DICOMDIR with placeholders first, locate the records and then encode it again with the right offsets.
+        let (bytes, dataset_start) = write_dcm(&encode_dicomdir(&meta, &records, &vec![0; records.len()], file_set_id))?;
+
+        let ts = TransferSyntaxRegistry
+            .get(uids::EXPLICIT_VR_LITTLE_ENDIAN)
+            .expect("Explicit VR Little Endian is always supported");
+
+        let offsets: Vec<u64> = sequence_item_offsets(&bytes[dataset_start..], ts, tags::DIRECTORY_RECORD_SEQUENCE)
+            .expect("encoded DICOMDIR must be readable")
+            .into_iter()
+            .map(|offset| dataset_start as u64 + offset)
+            .collect();
+
+        let (bytes, _) = write_dcm(&encode_dicomdir(&meta, &records, &offsets, file_set_id))?;
+
+        Ok(bytes)
+    }
+}
+
+/// Flattens the tree into `(record, parent index)` pairs in depth-first order.
+fn flatten_nodes<'a>(
+    nodes: &'a IndexMap<String, Node>,
+    parent: Option<usize>,
+    records: &mut Vec<(&'a InMemDicomObject, Option<usize>)>,
+) {
+    for node in nodes.values() {
+        let index = records.len();
+        records.push((&node.record, parent));
+        flatten_nodes(&node.children, Some(index), records);
+    }
+}
+
+/// Creates the DICOMDIR object, with records linked using `offsets` (of each record in `records`).
+fn encode_dicomdir(
+    meta: &FileMetaTable,
+    records: &[(&InMemDicomObject, Option<usize>)],
+    offsets: &[u64],
+    file_set_id: Option<&str>,
+) -> DefaultDicomObject {
+    let offset_of = |index: Option<usize>| index.map_or(0, |index| offsets[index]);
+
+    // records with the same parent, in order
+    let siblings = |parent: Option<usize>| {
+        records
+            .iter()
+            .enumerate()
+            .filter(move |(_, (_, p))| *p == parent)
+            .map(|(index, _)| index)
+    };
+
+    let items: Vec<InMemDicomObject> = records
+        .iter()
+        .enumerate()
+        .map(|(index, (record, parent))| {
+            let next = siblings(*parent).find(|sibling| *sibling > index);
+            let first_child = siblings(Some(index)).next();
+
+            let mut record = (*record).clone();
+            record.put(DataElement::new(tags::OFFSET_OF_THE_NEXT_DIRECTORY_RECORD, VR::UL, PrimitiveValue::from(offset_of(next) as u32)));
+            record.put(DataElement::new(tags::RECORD_IN_USE_FLAG, VR::US, PrimitiveValue::from(0xFFFF_u16)));
+            record.put(DataElement::new(
+                tags::OFFSET_OF_REFERENCED_LOWER_LEVEL_DIRECTORY_ENTITY,
+                VR::UL,
+                PrimitiveValue::from(offset_of(first_child) as u32),
+            ));
+            record
+        })
+        .collect();
+
+    let roots: Vec<usize> = siblings(None).collect();
+
+    let mut obj = InMemDicomObject::new_empty();
+    obj.put(DataElement::new(tags::FILE_SET_ID, VR::CS, PrimitiveValue::from(file_set_id.unwrap_or_default())));
+    obj.put(DataElement::new(
+        tags::OFFSET_OF_THE_FIRST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY,
+        VR::UL,
+        PrimitiveValue::from(offset_of(
+            roots
+                .first()
+                .copied(),
+        ) as u32),
+    ));
+    obj.put(DataElement::new(
+        tags::OFFSET_OF_THE_LAST_DIRECTORY_RECORD_OF_THE_ROOT_DIRECTORY_ENTITY,
+        VR::UL,
+        PrimitiveValue::from(offset_of(
+            roots
+                .last()
+                .copied(),
+        ) as u32),
+    ));
+    obj.put(DataElement::new(tags::FILE_SET_CONSISTENCY_FLAG, VR::US, PrimitiveValue::from(0_u16)));
+    obj.put(DataElement::new(tags::DIRECTORY_RECORD_SEQUENCE, VR::SQ, DataSetSequence::from(items)));
+
+    obj.with_exact_meta(meta.clone())
+}
+
+/// Creates a directory record of `record_type` with `keys` copied from `obj`. Missing keys are empty.
+fn directory_record(
+    record_type: &str,
+    obj: &InMemDicomObject,
+    keys: &[(Tag, VR)],
+) -> InMemDicomObject {
+    let mut record = InMemDicomObject::new_empty();
+    record.put(DataElement::new(tags::DIRECTORY_RECORD_TYPE, VR::CS, PrimitiveValue::from(record_type)));
+
+    // needed to decode the other attributes
+    if let Some(element) = obj.get(tags::SPECIFIC_CHARACTER_SET) {
+        record.put(element.clone());
+    }
+
+    for (tag, vr) in keys {
+        match obj.get(*tag) {
+            Some(element) => record.put(element.clone()),
+            None => record.put(DataElement::new(*tag, *vr, PrimitiveValue::Empty)),
+        };
+    }
+
+    record
+}
+
+/// Returns the directory record type of an instance of `sop_class_uid` (PS3.3 F.5), IMAGE for any other class.
+fn instance_record_type(sop_class_uid: &str) -> &'static str {
+    // SOP Classes which have their own record type
+    const RECORD_TYPES: &[(&str, &str)] = &[
+        (uids::BASIC_TEXT_SR_STORAGE, "SR DOCUMENT"),
+        (uids::ENHANCED_SR_STORAGE, "SR DOCUMENT"),
+        (uids::COMPREHENSIVE_SR_STORAGE, "SR DOCUMENT"),
+        (uids::COMPREHENSIVE3_DSR_STORAGE, "SR DOCUMENT"),
+        (uids::EXTENSIBLE_SR_STORAGE, "SR DOCUMENT"),
+        (uids::PROCEDURE_LOG_STORAGE, "SR DOCUMENT"),
+        (uids::MAMMOGRAPHY_CADSR_STORAGE, "SR DOCUMENT"),
+        (uids::CHEST_CADSR_STORAGE, "SR DOCUMENT"),
+        (uids::X_RAY_RADIATION_DOSE_SR_STORAGE, "SR DOCUMENT"),
+        (uids::RADIOPHARMACEUTICAL_RADIATION_DOSE_SR_STORAGE, "SR DOCUMENT"),
+        (uids::COLON_CADSR_STORAGE, "SR DOCUMENT"),
+        (uids::IMPLANTATION_PLAN_SR_STORAGE, "SR DOCUMENT"),
+        (uids::ACQUISITION_CONTEXT_SR_STORAGE, "SR DOCUMENT"),
+        (uids::SIMPLIFIED_ADULT_ECHO_SR_STORAGE, "SR DOCUMENT"),
+        (uids::PATIENT_RADIATION_DOSE_SR_STORAGE, "SR DOCUMENT"),
+        (uids::PLANNED_IMAGING_AGENT_ADMINISTRATION_SR_STORAGE, "SR DOCUMENT"),
+        (uids::PERFORMED_IMAGING_AGENT_ADMINISTRATION_SR_STORAGE, "SR DOCUMENT"),
+        (uids::ENHANCED_X_RAY_RADIATION_DOSE_SR_STORAGE, "SR DOCUMENT"),
+        (uids::WAVEFORM_ANNOTATION_SR_STORAGE, "SR DOCUMENT"),
+        (uids::MACULAR_GRID_THICKNESS_AND_VOLUME_REPORT_STORAGE, "SR DOCUMENT"),
+        (uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE, "KEY OBJECT DOC"),
+        (uids::ENCAPSULATED_PDF_STORAGE, "ENCAP DOC"),
+        (uids::ENCAPSULATED_CDA_STORAGE, "ENCAP DOC"),
+        (uids::ENCAPSULATED_STL_STORAGE, "ENCAP DOC"),
+        (uids::ENCAPSULATED_OBJ_STORAGE, "ENCAP DOC"),
+        (uids::ENCAPSULATED_MTL_STORAGE, "ENCAP DOC"),
+        (uids::GRAYSCALE_SOFTCOPY_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::COLOR_SOFTCOPY_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::PSEUDO_COLOR_SOFTCOPY_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::BLENDING_SOFTCOPY_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::XAXRF_GRAYSCALE_SOFTCOPY_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::GRAYSCALE_PLANAR_MPR_VOLUMETRIC_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::COMPOSITING_PLANAR_MPR_VOLUMETRIC_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::ADVANCED_BLENDING_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::VOLUME_RENDERING_VOLUMETRIC_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::SEGMENTED_VOLUME_RENDERING_VOLUMETRIC_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::MULTIPLE_VOLUME_RENDERING_VOLUMETRIC_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::VARIABLE_MODALITY_LUT_SOFTCOPY_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::WAVEFORM_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::WAVEFORM_ACQUISITION_PRESENTATION_STATE_STORAGE, "PRESENTATION"),
+        (uids::BASIC_STRUCTURED_DISPLAY_STORAGE, "PRESENTATION"),
+        (uids::TWELVE_LEAD_ECG_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::GENERAL_ECG_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::AMBULATORY_ECG_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::GENERAL32BIT_ECG_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::HEMODYNAMIC_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::CARDIAC_ELECTROPHYSIOLOGY_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::BASIC_VOICE_AUDIO_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::GENERAL_AUDIO_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::ARTERIAL_PULSE_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::RESPIRATORY_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::MULTICHANNEL_RESPIRATORY_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::ROUTINE_SCALP_ELECTROENCEPHALOGRAM_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::ELECTROMYOGRAM_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::ELECTROOCULOGRAM_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::SLEEP_ELECTROENCEPHALOGRAM_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::BODY_POSITION_WAVEFORM_STORAGE, "WAVEFORM"),
+        (uids::RAW_DATA_STORAGE, "RAW DATA"),
+        (uids::SPATIAL_REGISTRATION_STORAGE, "REGISTRATION"),
+        (uids::DEFORMABLE_SPATIAL_REGISTRATION_STORAGE, "REGISTRATION"),
+        (uids::SPATIAL_FIDUCIALS_STORAGE, "FIDUCIAL"),
+        (uids::SEGMENTATION_STORAGE, "SEGMENTATION"),
+        (uids::SURFACE_SEGMENTATION_STORAGE, "SURFACE"),
+        (uids::TRACTOGRAPHY_RESULTS_STORAGE, "TRACT"),
+        (uids::REAL_WORLD_VALUE_MAPPING_STORAGE, "VALUE MAP"),
+        (uids::SURFACE_SCAN_MESH_STORAGE, "SURFACE SCAN"),
+        (uids::SURFACE_SCAN_POINT_CLOUD_STORAGE, "SURFACE SCAN"),
+        (uids::STEREOMETRIC_RELATIONSHIP_STORAGE, "STEREOMETRIC"),
+        (uids::LENSOMETRY_MEASUREMENTS_STORAGE, "MEASUREMENT"),
+        (uids::AUTOREFRACTION_MEASUREMENTS_STORAGE, "MEASUREMENT"),
+        (uids::KERATOMETRY_MEASUREMENTS_STORAGE, "MEASUREMENT"),
+        (uids::SUBJECTIVE_REFRACTION_MEASUREMENTS_STORAGE, "MEASUREMENT"),
+        (uids::VISUAL_ACUITY_MEASUREMENTS_STORAGE, "MEASUREMENT"),
+        (uids::SPECTACLE_PRESCRIPTION_REPORT_STORAGE, "MEASUREMENT"),
+        (uids::OPHTHALMIC_AXIAL_MEASUREMENTS_STORAGE, "MEASUREMENT"),
+        (uids::INTRAOCULAR_LENS_CALCULATIONS_STORAGE, "MEASUREMENT"),
+        (uids::OPHTHALMIC_VISUAL_FIELD_STATIC_PERIMETRY_MEASUREMENTS_STORAGE, "MEASUREMENT"),
+        (uids::MR_SPECTROSCOPY_STORAGE, "SPECTROSCOPY"),
+        (uids::CONTENT_ASSESSMENT_RESULTS_STORAGE, "ASSESSMENT"),
+        (uids::MICROSCOPY_BULK_SIMPLE_ANNOTATIONS_STORAGE, "ANNOTATION"),
+        (uids::INVENTORY_STORAGE, "INVENTORY"),
+        (uids::HANGING_PROTOCOL_STORAGE, "HANGING PROTOCOL"),
+        (uids::COLOR_PALETTE_STORAGE, "PALETTE"),
+        (uids::GENERIC_IMPLANT_TEMPLATE_STORAGE, "IMPLANT"),
+        (uids::IMPLANT_ASSEMBLY_TEMPLATE_STORAGE, "IMPLANT ASSY"),
+        (uids::IMPLANT_TEMPLATE_GROUP_STORAGE, "IMPLANT GROUP"),
+        (uids::RT_DOSE_STORAGE, "RT DOSE"),
+        (uids::RT_STRUCTURE_SET_STORAGE, "RT STRUCTURE SET"),
+        (uids::RT_PLAN_STORAGE, "RT PLAN"),
+        (uids::RT_ION_PLAN_STORAGE, "RT PLAN"),
+        (uids::RT_BEAMS_TREATMENT_RECORD_STORAGE, "RT TREAT RECORD"),
+        (uids::RT_BRACHY_TREATMENT_RECORD_STORAGE, "RT TREAT RECORD"),
+        (uids::RT_TREATMENT_SUMMARY_RECORD_STORAGE, "RT TREAT RECORD"),
+        (uids::RT_ION_BEAMS_TREATMENT_RECORD_STORAGE, "RT TREAT RECORD"),
+        (uids::RT_PHYSICIAN_INTENT_STORAGE, "RADIOTHERAPY"),
+        (uids::RT_SEGMENT_ANNOTATION_STORAGE, "RADIOTHERAPY"),
+        (uids::RT_RADIATION_SET_STORAGE, "RADIOTHERAPY"),
+        (uids::C_ARM_PHOTON_ELECTRON_RADIATION_STORAGE, "RADIOTHERAPY"),
+        (uids::TOMOTHERAPEUTIC_RADIATION_STORAGE, "RADIOTHERAPY"),
+        (uids::ROBOTIC_ARM_RADIATION_STORAGE, "RADIOTHERAPY"),
+        (uids::RT_RADIATION_RECORD_SET_STORAGE, "RADIOTHERAPY"),
+        (uids::RT_RADIATION_SALVAGE_RECORD_STORAGE, "RADIOTHERAPY"),
+        (uids::TOMOTHERAPEUTIC_RADIATION_RECORD_STORAGE, "RADIOTHERAPY"),
+        (uids::C_ARM_PHOTON_ELECTRON_RADIATION_RECORD_STORAGE, "RADIOTHERAPY"),
+        (uids::ROBOTIC_RADIATION_RECORD_STORAGE, "RADIOTHERAPY"),
+        (uids::RT_RADIATION_SET_DELIVERY_INSTRUCTION_STORAGE, "RADIOTHERAPY"),
+        (uids::RT_TREATMENT_PREPARATION_STORAGE, "RADIOTHERAPY"),
+        (uids::RT_PATIENT_POSITION_ACQUISITION_INSTRUCTION_STORAGE, "RADIOTHERAPY"),
+    ];
+
+    RECORD_TYPES
+        .iter()
+        .find(|(uid, _)| *uid == sop_class_uid)
+        .map_or("IMAGE", |(_, record_type)| record_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn instance(patient_id: Option<&str>) -> DefaultDicomObject {
+        let mut obj = InMemDicomObject::new_empty();
+        obj.put(DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, PrimitiveValue::from("1.2.3.1.1.1")));
+        obj.put(DataElement::new(tags::STUDY_INSTANCE_UID, VR::UI, PrimitiveValue::from("1.2.3.1")));
+        obj.put(DataElement::new(tags::SERIES_INSTANCE_UID, VR::UI, PrimitiveValue::from("1.2.3.1.1")));
+        if let Some(patient_id) = patient_id {
+            obj.put(DataElement::new(tags::PATIENT_ID, VR::LO, PrimitiveValue::from(patient_id)));
+        }
+
+        obj.with_meta(
+            FileMetaTableBuilder::new()
+                .media_storage_sop_class_uid(uids::CT_IMAGE_STORAGE)
+                .media_storage_sop_instance_uid("1.2.3.1.1.1")
+                .transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_add_missing_patient_id() {
+        let mut builder = DicomdirBuilder::default();
+
+        assert_eq!(builder.add(vec!["IM1".to_string()], &instance(None)), Err("Missing PatientID".to_string()));
+        assert!(
+            builder
+                .add(vec!["IM1".to_string()], &instance(Some("P1")))
+                .is_ok()
+        );
+    }
+
+    #[test_case(uids::CT_IMAGE_STORAGE, "IMAGE"; "image")]
+    #[test_case(uids::ENHANCED_SR_STORAGE, "SR DOCUMENT"; "structured report")]
+    #[test_case(uids::KEY_OBJECT_SELECTION_DOCUMENT_STORAGE, "KEY OBJECT DOC"; "key object selection")]
+    #[test_case(uids::SEGMENTATION_STORAGE, "SEGMENTATION"; "segmentation")]
+    #[test_case(uids::SPATIAL_FIDUCIALS_STORAGE, "FIDUCIAL"; "spatial fiducials")]
+    #[test_case(uids::SURFACE_SEGMENTATION_STORAGE, "SURFACE"; "surface segmentation")]
+    #[test_case(uids::RT_BRACHY_TREATMENT_RECORD_STORAGE, "RT TREAT RECORD"; "brachy treatment record")]
+    #[test_case(uids::RT_ION_BEAMS_TREATMENT_RECORD_STORAGE, "RT TREAT RECORD"; "ion beams treatment record")]
+    #[test_case(uids::RT_PHYSICIAN_INTENT_STORAGE, "RADIOTHERAPY"; "physician intent")]
+    // shares its prefix with RT Dose Storage (481.2)
+    #[test_case(uids::ENHANCED_RT_IMAGE_STORAGE, "IMAGE"; "enhanced rt image")]
+    fn test_instance_record_type(
+        sop_class_uid: &str,
+        record_type: &str,
+    ) {
+        assert_eq!(instance_record_type(sop_class_uid), record_type);
+    }
+}
@@ -0,0 +1,408 @@
+use std::path::PathBuf;
+
+use dicom::core::dictionary::VirtualVr;
+use dicom::core::value::DataSetSequence;
+use dicom::core::{DataDictionary, DataElement, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{StandardDataDictionary, tags, uids};
+use dicom::object::mem::InMemElement;
+use dicom::object::{DefaultDicomObject, FileMetaTableBuilder, InMemDicomObject};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Record, Signature, SyntaxShape, Value};
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+
+use crate::convert::get_string;
+use crate::input::{DicomSource, collect_input, into_output, is_list_input};
+use crate::plugin::DcmPlugin;
+use crate::uid::generate_uid;
+use crate::writer::write_dcm;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Missing EncapsulatedDocument, not an encapsulated document"))]
+    MissingDocument,
+
+    #[snafu(display("Not a PDF file, expected a `%PDF-` header"))]
+    NotPdf,
+
+    #[snafu(display("Could not create Dicom file meta group: {}", source))]
+    BuildMeta { source: dicom::object::meta::Error },
+}
+
+/// File name extensions of the MIME types of encapsulated documents.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("application/pdf", "pdf"),
+    ("text/xml", "xml"),
+    ("model/stl", "stl"),
+    ("model/x.stl-binary", "stl"),
+    ("application/sla", "stl"),
+    ("model/obj", "obj"),
+    ("model/mtl", "mtl"),
+];
+
+/// Attributes of the created instance which can't be set with `--attributes`.
+const GENERATED_TAGS: [Tag; 6] = [
+    tags::SOP_CLASS_UID,
+    tags::SOP_INSTANCE_UID,
+    tags::SPECIFIC_CHARACTER_SET,
+    tags::MIME_TYPE_OF_ENCAPSULATED_DOCUMENT,
+    tags::ENCAPSULATED_DOCUMENT,
+    tags::ENCAPSULATED_DOCUMENT_LENGTH,
+];
+
+/// `dcm extract-document` command, returns the payload of encapsulated documents.
+pub struct DcmExtractDocumentCommand;
+
+impl PluginCommand for DcmExtractDocumentCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm extract-document"
+    }
+
+    fn description(&self) -> &str {
+        "Extract the document of encapsulated PDF, CDA, STL, OBJ or MTL DICOM objects."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec![
+                "dicom".to_string(),
+                "pdf".to_string(),
+                "cda".to_string(),
+                "stl".to_string(),
+                "encapsulated".to_string(),
+                "document".to_string(),
+            ])
+            .extra_description(
+                "Returns a record with the `SOPInstanceUID`, `DocumentTitle` and `MIMETypeOfEncapsulatedDocument` of the object, the \
+                 usual file name `extension` of the MIME type (e.g. `pdf` or `stl`) and the `document` as binary data, without the padding \
+                 of EncapsulatedDocument when its length is known. Returns a record for a single input and a list of records for a list.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Save the PDF of an Encapsulated PDF object",
+                example: "\"report.dcm\" | dcm extract-document | get document | save report.pdf",
+                result: None,
+            },
+            Example {
+                description: "Save the documents of all files, named after their SOP Instance UID",
+                example: "ls *.dcm | get name | dcm extract-document | each {|d| $d.document | save $\"($d.SOPInstanceUID).($d.extension)\" }",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let is_list = is_list_input(&input);
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let obj = source
+                .read()
+                .map_err(|e| source.error(&e, span))?;
+            let document = extract_document(&obj).map_err(|e| source.labeled_error("Failed to extract document", &e, span))?;
+
+            let mime_type = get_string(&obj, tags::MIME_TYPE_OF_ENCAPSULATED_DOCUMENT);
+            let extension = mime_type
+                .as_deref()
+                .and_then(|mime_type| {
+                    EXTENSIONS
+                        .iter()
+                        .find(|(known, _)| known.eq_ignore_ascii_case(mime_type))
+                })
+                .map(|(_, extension)| *extension);
+
+            let string = |s: Option<&str>| s.map_or_else(|| Value::nothing(span), |s| Value::string(s, span));
+
+            let mut record = Record::new();
+            record.push("SOPInstanceUID", string(get_string(&obj, tags::SOP_INSTANCE_UID).as_deref()));
+            record.push("DocumentTitle", string(get_string(&obj, tags::DOCUMENT_TITLE).as_deref()));
+            record.push("MIMETypeOfEncapsulatedDocument", string(mime_type.as_deref()));
+            record.push("extension", string(extension));
+            record.push("document", Value::binary(document, span));
+
+            output.push(Value::record(record, span));
+        }
+
+        Ok(into_output(output, is_list, call.head))
+    }
+}
+
+/// Returns the encapsulated document, without padding if EncapsulatedDocumentLength is present.
+pub fn extract_document(obj: &InMemDicomObject) -> Result<Vec<u8>, Error> {
+    let mut document = obj
+        .get(tags::ENCAPSULATED_DOCUMENT)
+        .and_then(|e| {
+            e.to_bytes()
+                .ok()
+        })
+        .context(MissingDocumentSnafu)?
+        .into_owned();
+
+    let length = obj
+        .get(tags::ENCAPSULATED_DOCUMENT_LENGTH)
+        .and_then(|e| {
+            e.to_int::<usize>()
+                .ok()
+        });
+    if let Some(length) = length {
+        document.truncate(length);
+    }
+
+    Ok(document)
+}
+
+/// `dcm wrap-pdf` command, creates Encapsulated PDF instances.
+pub struct DcmWrapPdfCommand;
+
+impl PluginCommand for DcmWrapPdfCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm wrap-pdf"
+    }
+
+    fn description(&self) -> &str {
+        "Create Encapsulated PDF DICOM instances from PDF files."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .named(
+                "attributes",
+                SyntaxShape::Record(vec![]),
+                "Attributes of the instance by keyword or tag, e.g. `{PatientName: \"Doe^John\", PatientID: P4, StudyInstanceUID: 1.2.3}`. \
+                 Values are strings.",
+                Some('a'),
+            )
+            .named("title", SyntaxShape::String, "DocumentTitle, defaults to the file name without its extension.", None)
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "pdf".to_string(), "encapsulated".to_string(), "document".to_string(), "wrap".to_string()])
+            .extra_description(
+                "Input PDF files are file names, file records or binary data. Each PDF gets a new SOPInstanceUID, and a new \
+                 StudyInstanceUID and SeriesInstanceUID unless given in --attributes. Other patient and study attributes not given in \
+                 --attributes are empty. Values are encoded in UTF-8. Returns binary data for a single input and a list of binary data for a \
+                 list.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Wrap a PDF for a patient",
+                example: "\"report.pdf\" | dcm wrap-pdf --attributes {PatientName: \"Doe^John\", PatientID: P4} | save report.dcm",
+                result: None,
+            },
+            Example {
+                description: "Wrap a PDF into the study of an existing image",
+                example: "\"report.pdf\" | dcm wrap-pdf --attributes (\"image.dcm\" | dcm | select PatientName PatientID StudyInstanceUID AccessionNumber) \
+                          | save report.dcm",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let attributes = get_attributes_flag(call, &plugin.dcm_dictionary)?;
+        let title: Option<String> = call.get_flag("title")?;
+
+        let is_list = is_list_input(&input);
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let pdf = source
+                .bytes()
+                .map_err(|e| source.error(&e, span))?;
+
+            let title = title
+                .clone()
+                .or_else(|| {
+                    source
+                        .path()
+                        .and_then(|path| path.file_stem())
+                        .map(|stem| {
+                            stem.to_string_lossy()
+                                .into_owned()
+                        })
+                })
+                .unwrap_or_default();
+
+            let obj = wrap_pdf(&pdf, &title, &attributes).map_err(|e| source.labeled_error("Failed to wrap PDF", &e, span))?;
+            let (bytes, _) = write_dcm(&obj).map_err(|e| source.labeled_error("Failed to encode", &e, span))?;
+
+            output.push(Value::binary(bytes, span));
+        }
+
+        Ok(into_output(output, is_list, call.head))
+    }
+}
+
+fn get_attributes_flag(
+    call: &EvaluatedCall,
+    dictionary: &StandardDataDictionary,
+) -> Result<Vec<InMemElement>, LabeledError> {
+    let Some(value) = call.get_flag_value("attributes") else {
+        return Ok(Vec::new());
+    };
+
+    value
+        .as_record()?
+        .iter()
+        .map(|(name, value)| {
+            let unknown = || LabeledError::new("Unknown attribute").with_label(format!("`{name}` is not an attribute keyword or tag"), value.span());
+
+            let tag = dictionary
+                .parse_tag(name)
+                .ok_or_else(unknown)?;
+            let vr = match dictionary
+                .by_tag(tag)
+                .ok_or_else(unknown)?
+                .vr
+            {
+                VirtualVr::Exact(vr) => vr,
+                vr => vr.relaxed(),
+            };
+
+            check_attribute(tag, vr).map_err(|message| LabeledError::new("Unsupported attribute").with_label(message, value.span()))?;
+
+            let text = match value {
+                Value::Nothing { .. } => String::new(),
+                value => value.coerce_string()?,
+            };
+
+            Ok(DataElement::new(tag, vr, PrimitiveValue::from(text)))
+        })
+        .collect()
+}
+
+fn check_attribute(
+    tag: Tag,
+    vr: VR,
+) -> Result<(), String> {
+    if GENERATED_TAGS.contains(&tag) {
+        return Err(format!("{tag} is set by `dcm wrap-pdf`"));
+    }
+
+    match vr {
+        VR::AE
+        | VR::AS
+        | VR::CS
+        | VR::DA
+        | VR::DS
+        | VR::DT
+        | VR::IS
+        | VR::LO
+        | VR::LT
+        | VR::PN
+        | VR::SH
+        | VR::ST
+        | VR::TM
+        | VR::UC
+        | VR::UI
+        | VR::UR
+        | VR::UT => Ok(()),
+        _ => Err(format!("{tag} has VR {vr}, only text attributes are supported")),
+    }
+}
+
+/// Creates an Encapsulated PDF instance. `attributes` override the defaults of the patient, study, series and document
+/// attributes.
+pub fn wrap_pdf(
+    pdf: &[u8],
+    title: &str,
+    attributes: &[InMemElement],
+) -> Result<DefaultDicomObject, Error> {
+    ensure!(pdf.starts_with(b"%PDF-"), NotPdfSnafu);
+
+    let sop_instance_uid = generate_uid();
+
+    let mut obj = InMemDicomObject::new_empty();
+    let mut put_str = |tag: Tag, vr: VR, value: &str| {
+        obj.put(DataElement::new(tag, vr, PrimitiveValue::from(value)));
+    };
+
+    put_str(tags::SPECIFIC_CHARACTER_SET, VR::CS, "ISO_IR 192");
+    put_str(tags::SOP_CLASS_UID, VR::UI, uids::ENCAPSULATED_PDF_STORAGE);
+    put_str(tags::SOP_INSTANCE_UID, VR::UI, &sop_instance_uid);
+
+    // Type 2 attributes of the patient, study, series and document modules are empty unless given
+    for (tag, vr) in [
+        (tags::STUDY_DATE, VR::DA),
+        (tags::STUDY_TIME, VR::TM),
+        (tags::ACCESSION_NUMBER, VR::SH),
+        (tags::REFERRING_PHYSICIAN_NAME, VR::PN),
+        (tags::PATIENT_NAME, VR::PN),
+        (tags::PATIENT_ID, VR::LO),
+        (tags::PATIENT_BIRTH_DATE, VR::DA),
+        (tags::PATIENT_SEX, VR::CS),
+        (tags::STUDY_ID, VR::SH),
+        (tags::SERIES_NUMBER, VR::IS),
+        (tags::CONTENT_DATE, VR::DA),
+        (tags::CONTENT_TIME, VR::TM),
+        (tags::ACQUISITION_DATE_TIME, VR::DT),
+    ] {
+        put_str(tag, vr, "");
+    }
+    put_str(tags::MODALITY, VR::CS, "DOC");
+    put_str(tags::CONVERSION_TYPE, VR::CS, "WSD");
+    put_str(tags::STUDY_INSTANCE_UID, VR::UI, &generate_uid());
+    put_str(tags::SERIES_INSTANCE_UID, VR::UI, &generate_uid());
+    put_str(tags::INSTANCE_NUMBER, VR::IS, "1");
+    put_str(tags::BURNED_IN_ANNOTATION, VR::CS, "YES");
+    put_str(tags::DOCUMENT_TITLE, VR::ST, title);
+    put_str(tags::MIME_TYPE_OF_ENCAPSULATED_DOCUMENT, VR::LO, "application/pdf");
+
+    obj.put(DataElement::new(tags::CONCEPT_NAME_CODE_SEQUENCE, VR::SQ, DataSetSequence::<InMemDicomObject>::empty()));
+
+    for element in attributes {
+        obj.put(element.clone());
+    }
+
+    // OB values have an even length, the length of the PDF itself is kept in EncapsulatedDocumentLength
+    let mut document = pdf.to_vec();
+    if document.len() % 2 == 1 {
+        document.push(0);
+    }
+    obj.put(DataElement::new(tags::ENCAPSULATED_DOCUMENT_LENGTH, VR::UL, PrimitiveValue::from(pdf.len() as u32)));
+    obj.put(DataElement::new(tags::ENCAPSULATED_DOCUMENT, VR::OB, PrimitiveValue::from(document)));
+
+    let meta = FileMetaTableBuilder::new()
+        .media_storage_sop_class_uid(uids::ENCAPSULATED_PDF_STORAGE)
+        .media_storage_sop_instance_uid(sop_instance_uid)
+        .transfer_syntax(uids::EXPLICIT_VR_LITTLE_ENDIAN)
+        .build()
+        .context(BuildMetaSnafu)?;
+
+    Ok(obj.with_exact_meta(meta))
+}
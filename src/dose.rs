@@ -0,0 +1,289 @@
+use std::path::PathBuf;
+
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Record, Signature, Span, Value};
+use snafu::{ResultExt, Snafu, ensure};
+
+use crate::convert::get_string;
+use crate::input::{DicomSource, collect_input, into_output, is_list_input};
+use crate::plugin::DcmPlugin;
+use crate::reader::ErrorKind;
+use crate::sr::{self, ContentItem, ContentValue, content_items};
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Invalid structured report: {}", source))]
+    StructuredReport { source: sr::Error },
+
+    #[snafu(display("Not an X-Ray Radiation Dose Report, the document title is {}", title))]
+    NotDoseReport { title: String },
+}
+
+/// Document title of X-Ray Radiation Dose SR documents (TID 10001).
+const XRAY_RADIATION_DOSE_REPORT: &str = "113701";
+
+/// Container of the accumulated dose data of CT reports (TID 10012).
+const CT_ACCUMULATED_DOSE_DATA: &str = "113811";
+
+/// Container of an irradiation event of CT reports (TID 10013).
+const CT_ACQUISITION: &str = "113819";
+
+/// Containers of the accumulated dose data of CT and projection X-ray (TID 10002) reports.
+const ACCUMULATED_DOSE_DATA: [&str; 2] = [CT_ACCUMULATED_DOSE_DATA, "113702"];
+
+/// Containers of an irradiation event of CT and projection X-ray (TID 10003) reports.
+const IRRADIATION_EVENTS: [&str; 2] = [CT_ACQUISITION, "113706"];
+
+/// Columns of irradiation events and the DCM codes of their concept names. The first matching content item of the event
+/// is used, e.g. the first X-ray source of dual-source CT.
+const EVENT_COLUMNS: [(&str, &[&str]); 16] = [
+    ("irradiation_event_uid", &["113769"]),
+    ("protocol", &["125203"]),
+    ("target_region", &["123014"]),
+    ("acquisition_type", &["113820", "113721"]),
+    ("ctdi_vol", &["113830"]),
+    ("dlp", &["113838"]),
+    ("ssde", &["113930"]),
+    ("phantom_type", &["113835"]),
+    ("kvp", &["113733"]),
+    ("tube_current", &["113734", "113833"]),
+    ("exposure_time", &["113824"]),
+    ("scanning_length", &["113825"]),
+    ("pitch_factor", &["113828"]),
+    ("dap", &["122130"]),
+    ("dose_rp", &["113738"]),
+    ("acquisition_plane", &["113764"]),
+];
+
+/// Columns of the accumulated dose data and the DCM codes of their concept names.
+const TOTAL_COLUMNS: [(&str, &[&str]); 8] = [
+    ("dlp_total", &["113813"]),
+    ("effective_dose_total", &["113814"]),
+    ("dap_total", &["113722"]),
+    ("dose_rp_total", &["113725"]),
+    ("fluoro_dap_total", &["113726"]),
+    ("acquisition_dap_total", &["113727"]),
+    ("fluoro_time_total", &["113730"]),
+    ("acquisition_time_total", &["113855"]),
+];
+
+/// `dcm dose` command, extracts irradiation events and accumulated dose from radiation dose reports.
+pub struct DcmDoseCommand;
+
+impl PluginCommand for DcmDoseCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm dose"
+    }
+
+    fn description(&self) -> &str {
+        "Extract irradiation events and accumulated dose from DICOM X-Ray Radiation Dose SR documents."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec![
+                "dicom".to_string(),
+                "dose".to_string(),
+                "rdsr".to_string(),
+                "radiation".to_string(),
+                "ctdi".to_string(),
+                "dlp".to_string(),
+            ])
+            .extra_description(
+                "Supports CT (TID 10011) and projection X-ray (TID 10001) dose reports. Returns a record per report with its \
+                 `SOPInstanceUID`, the `report` kind (`CT` or `projection`), the accumulated `totals` (dlp_total, effective_dose_total, \
+                 dap_total, dose_rp_total, ...) and a row per irradiation event in `events` (irradiation_event_uid, protocol, \
+                 target_region, acquisition_type, ctdi_vol, dlp, ssde, phantom_type, kvp, tube_current, exposure_time, scanning_length, \
+                 pitch_factor, dap, dose_rp, ...). Values are in the units of the templates, e.g. mGy for CTDIvol, mGy.cm for DLP, Gy.m2 \
+                 for DAP and Gy for dose (RP); coded values are their code meanings. Missing values are nothing. Objects that aren't dose \
+                 reports and non-DICOM files are skipped.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show the CTDIvol and DLP of each acquisition",
+                example: "\"rdsr.dcm\" | dcm dose | get events | select protocol ctdi_vol dlp",
+                result: None,
+            },
+            Example {
+                description: "List the total DLP of CT reports",
+                example: "ls *.dcm | get name | dcm dose | where report == CT | each {|r| {report: $r.SOPInstanceUID, dlp: $r.totals.dlp_total} }",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let is_list = is_list_input(&input);
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            // only dose reports produce records, listings usually contain images, other SR documents and non-DICOM files too
+            let obj = match source.read() {
+                Ok(obj) => obj,
+                Err(e) if e.kind() == ErrorKind::NotDicom => continue,
+                Err(e) => return Err(source.error(&e, span)),
+            };
+            let Ok(items) = dose_report_items(&obj) else {
+                continue;
+            };
+
+            let sop_instance_uid = get_string(&obj, tags::SOP_INSTANCE_UID).map_or_else(|| Value::nothing(span), |uid| Value::string(uid, span));
+
+            output.push(dose_report_to_value(&items, sop_instance_uid, span));
+        }
+
+        Ok(into_output(output, is_list, call.head))
+    }
+}
+
+/// Content items of an X-Ray Radiation Dose SR document.
+fn dose_report_items(obj: &InMemDicomObject) -> Result<Vec<ContentItem>, Error> {
+    let items = content_items(obj).context(StructuredReportSnafu)?;
+
+    let title = items
+        .first()
+        .and_then(|root| {
+            root.concept
+                .as_ref()
+        });
+    ensure!(
+        title.is_some_and(|title| title.scheme == "DCM" && title.value == XRAY_RADIATION_DOSE_REPORT),
+        NotDoseReportSnafu {
+            title: title.map_or_else(|| "missing".to_string(), |title| format!("{} ({}, {})", title.meaning, title.value, title.scheme))
+        }
+    );
+
+    Ok(items)
+}
+
+fn dose_report_to_value(
+    items: &[ContentItem],
+    sop_instance_uid: Value,
+    span: Span,
+) -> Value {
+    let is_ct = items
+        .iter()
+        .any(|item| is_concept(item, &[CT_ACCUMULATED_DOSE_DATA, CT_ACQUISITION]));
+
+    let totals = items
+        .iter()
+        .filter(|item| is_concept(item, &ACCUMULATED_DOSE_DATA))
+        .flat_map(|container| descendants(items, container))
+        .collect::<Vec<_>>();
+
+    let events: Vec<Value> = items
+        .iter()
+        .filter(|item| is_concept(item, &IRRADIATION_EVENTS))
+        .enumerate()
+        .map(|(index, event)| {
+            let descendants: Vec<&ContentItem> = descendants(items, event).collect();
+
+            let mut record = Record::new();
+            record.push("event", Value::int(index as i64 + 1, span));
+            for (column, codes) in EVENT_COLUMNS {
+                record.push(column, find_value(&descendants, codes, span));
+            }
+
+            Value::record(record, span)
+        })
+        .collect();
+
+    let mut totals_record = Record::new();
+    totals_record.push("events", Value::int(events.len() as i64, span));
+    for (column, codes) in TOTAL_COLUMNS {
+        totals_record.push(column, find_value(&totals, codes, span));
+    }
+
+    let mut record = Record::new();
+    record.push("SOPInstanceUID", sop_instance_uid);
+    record.push(
+        "report",
+        Value::string(
+            if is_ct {
+                "CT"
+            } else {
+                "projection"
+            },
+            span,
+        ),
+    );
+    record.push("totals", Value::record(totals_record, span));
+    record.push("events", Value::list(events, span));
+
+    Value::record(record, span)
+}
+
+/// Content items below a container, which follow it in depth-first order.
+fn descendants<'a>(
+    items: &'a [ContentItem],
+    container: &ContentItem,
+) -> impl Iterator<Item = &'a ContentItem> {
+    let prefix = format!("{}.", container.path);
+
+    items
+        .iter()
+        .filter(move |item| {
+            item.path
+                .starts_with(&prefix)
+        })
+}
+
+/// Value of the first item with one of the given concept names, in order of preference.
+fn find_value(
+    items: &[&ContentItem],
+    codes: &[&str],
+    span: Span,
+) -> Value {
+    let item = codes
+        .iter()
+        .find_map(|code| {
+            items
+                .iter()
+                .find(|item| is_concept(item, &[code]))
+        });
+
+    match item.map(|item| &item.value) {
+        Some(ContentValue::Numeric { value: Some(value), .. }) => Value::float(*value, span),
+        Some(ContentValue::Text(text)) => Value::string(text, span),
+        Some(ContentValue::Code(code)) => Value::string(&code.meaning, span),
+        _ => Value::nothing(span),
+    }
+}
+
+fn is_concept(
+    item: &ContentItem,
+    codes: &[&str],
+) -> bool {
+    item.concept
+        .as_ref()
+        .is_some_and(|concept| {
+            concept.scheme == "DCM"
+                && codes.contains(
+                    &concept
+                        .value
+                        .as_str(),
+                )
+        })
+}
@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use indexmap::IndexMap;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Value};
+
+use crate::convert::get_string;
+use crate::dcm::DicomDump;
+use crate::input::{DicomSource, collect_input};
+use crate::multiframe::{frame_attributes, number_of_frames};
+use crate::plugin::DcmPlugin;
+
+/// `dcm frames` command, returns the functional group attributes of each frame of multi-frame objects.
+pub struct DcmFramesCommand;
+
+impl PluginCommand for DcmFramesCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm frames"
+    }
+
+    fn description(&self) -> &str {
+        "Return a row per frame of enhanced multi-frame DICOM objects, with the attributes of their functional groups."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "multi-frame".to_string(), "enhanced".to_string(), "functional groups".to_string()])
+            .extra_description(
+                "Each row has the `SOPInstanceUID` of the object, the `frame` number (starting at 1) and the attributes of the \
+                 SharedFunctionalGroupsSequence, overridden by those of the frame's item in PerFrameFunctionalGroupsSequence. Functional group \
+                 sequences are flattened, e.g. ImagePositionPatient of the PlanePositionSequence is a column. Objects without functional \
+                 groups have a row per frame with just the frame number.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show the position and window of each frame",
+                example: "\"enhanced.dcm\" | dcm frames | select frame ImagePositionPatient WindowCenter WindowWidth",
+                result: None,
+            },
+            Example {
+                description: "Find frames of the second temporal position",
+                example: "\"enhanced.dcm\" | dcm frames | where TemporalPositionIndex == 2",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let dumper = DicomDump { dcm_dictionary: &plugin.dcm_dictionary };
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let obj = source
+                .read()
+                .map_err(|e| source.error(&e, span))?;
+
+            let sop_instance_uid = get_string(&obj, tags::SOP_INSTANCE_UID).map_or_else(|| Value::nothing(span), |uid| Value::string(uid, span));
+
+            for frame in 0..number_of_frames(&obj) {
+                let attributes = frame_attributes(&obj, frame).map_err(|e| source.labeled_error("Invalid multi-frame object", &e, span))?;
+
+                let mut index_map = IndexMap::with_capacity(attributes.len() + 2);
+                index_map.insert("SOPInstanceUID".to_string(), sop_instance_uid.clone());
+                index_map.insert("frame".to_string(), Value::int(frame as i64 + 1, span));
+                dumper.make_row_from_dicom_object(&span, &mut index_map, &InMemDicomObject::from_element_iter(attributes.into_values()));
+
+                output.push(Value::record(Record::from_iter(index_map), span));
+            }
+        }
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
@@ -0,0 +1,651 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use dicom::object::mem::InMemElement;
+use indexmap::IndexMap;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Value};
+
+use crate::convert::get_string;
+use crate::input::{DicomSource, collect_input};
+use crate::multiframe::{frame_attributes, number_of_frames};
+use crate::plugin::DcmPlugin;
+
+/// Positions closer than this (in mm) along the normal are the same slice.
+const POSITION_TOLERANCE: f64 = 1e-3;
+
+/// Relative difference from the median slice spacing that makes the spacing non-uniform.
+const SPACING_TOLERANCE: f64 = 0.01;
+
+/// Slices further apart than this multiple of the median slice spacing have a gap (e.g. a missing slice) between them.
+const GAP_FACTOR: f64 = 1.5;
+
+/// Minimum angle (in degrees) reported as gantry tilt or inconsistent orientation.
+const ANGLE_TOLERANCE: f64 = 0.01;
+
+/// `dcm geometry` command, computes the patient-space geometry of images and validates series.
+pub struct DcmGeometryCommand;
+
+impl PluginCommand for DcmGeometryCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm geometry"
+    }
+
+    fn description(&self) -> &str {
+        "Compute the patient-space geometry of DICOM images and check the slices of each series."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec![
+                "dicom".to_string(),
+                "geometry".to_string(),
+                "affine".to_string(),
+                "slice".to_string(),
+                "spacing".to_string(),
+                "orientation".to_string(),
+            ])
+            .extra_description(
+                "Returns a record per SeriesInstanceUID. Its `images` are sorted along the slice normal (the cross product of the row and column \
+                 directions of ImageOrientationPatient), each with its `distance` along the normal, the `spacing` to the previous image and the \
+                 4x4 voxel-to-patient `affine` (LPS, mm) mapping (column, row, slice) indices. The series reports the median `slice_spacing`, \
+                 whether the spacing is `uniform`, `duplicates` (images at the same position), `gaps` (spacing over 1.5 times the median), the \
+                 `gantry_tilt` in degrees and the volume `affine`. `issues` summarizes anything that prevents treating the series as a \
+                 regular volume. Frames of enhanced multi-frame objects are separate images, with geometry from their functional groups.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Check the series in a directory",
+                example: "ls *.dcm | dcm geometry | select SeriesInstanceUID count slice_spacing issues",
+                result: None,
+            },
+            Example {
+                description: "List the files of a series in slice order",
+                example: "ls *.dcm | dcm geometry | first | get images | select path distance",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let mut series: IndexMap<String, Vec<Image>> = IndexMap::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let obj = source
+                .read()
+                .map_err(|e| source.error(&e, span))?;
+
+            let path = source
+                .path()
+                .map(|path| Value::string(path.to_string_lossy(), span))
+                .unwrap_or_else(|| Value::nothing(span));
+
+            let frames = number_of_frames(&obj);
+            for frame in 0..frames {
+                let attributes = frame_attributes(&obj, frame).map_err(|e| source.labeled_error("Invalid multi-frame object", &e, span))?;
+
+                series
+                    .entry(get_string(&obj, tags::SERIES_INSTANCE_UID).unwrap_or_default())
+                    .or_default()
+                    .push(Image {
+                        path: path.clone(),
+                        sop_instance_uid: get_string(&obj, tags::SOP_INSTANCE_UID),
+                        frame: (frames > 1).then_some(frame + 1),
+                        geometry: ImageGeometry::from_attributes(&obj, &attributes),
+                    });
+            }
+        }
+
+        let output = series
+            .into_iter()
+            .map(|(series_instance_uid, images)| series_to_value(series_instance_uid, images, call.head))
+            .collect();
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+/// An image (or a frame of a multi-frame object) of a series.
+struct Image {
+    path: Value,
+    sop_instance_uid: Option<String>,
+    frame: Option<usize>,
+    geometry: Option<ImageGeometry>,
+}
+
+/// Position and orientation of an image in the patient coordinate system (LPS, mm).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageGeometry {
+    /// ImagePositionPatient, the center of the first (top left) pixel.
+    pub position: [f64; 3],
+    /// Direction of the rows, i.e. of increasing column index.
+    pub row_direction: [f64; 3],
+    /// Direction of the columns, i.e. of increasing row index.
+    pub column_direction: [f64; 3],
+    /// PixelSpacing, the spacing between rows followed by the spacing between columns.
+    pub pixel_spacing: [f64; 2],
+    pub slice_thickness: Option<f64>,
+}
+
+impl ImageGeometry {
+    /// Reads the geometry of an image from `attributes` (e.g. the functional groups of a frame), falling back to
+    /// `obj`. Returns `None` if ImagePositionPatient, ImageOrientationPatient or PixelSpacing are missing or invalid.
+    pub fn from_attributes(
+        obj: &InMemDicomObject,
+        attributes: &BTreeMap<Tag, InMemElement>,
+    ) -> Option<Self> {
+        let get_floats = |tag| {
+            attributes
+                .get(&tag)
+                .or_else(|| obj.get(tag))?
+                .to_multi_float64()
+                .ok()
+        };
+
+        let position: [f64; 3] = get_floats(tags::IMAGE_POSITION_PATIENT)?
+            .try_into()
+            .ok()?;
+        let orientation: [f64; 6] = get_floats(tags::IMAGE_ORIENTATION_PATIENT)?
+            .try_into()
+            .ok()?;
+        let pixel_spacing: [f64; 2] = get_floats(tags::PIXEL_SPACING)?
+            .try_into()
+            .ok()?;
+        let slice_thickness = get_floats(tags::SLICE_THICKNESS).and_then(|values| {
+            values
+                .first()
+                .copied()
+        });
+
+        let row_direction = normalize([orientation[0], orientation[1], orientation[2]])?;
+        let column_direction = normalize([orientation[3], orientation[4], orientation[5]])?;
+
+        Some(Self { position, row_direction, column_direction, pixel_spacing, slice_thickness })
+    }
+
+    /// Unit vector perpendicular to the image, pointing towards increasing slice positions.
+    pub fn normal(&self) -> [f64; 3] {
+        cross(self.row_direction, self.column_direction)
+    }
+
+    /// True if the image has the same orientation as slices with the given normal.
+    pub fn has_normal(
+        &self,
+        normal: [f64; 3],
+    ) -> bool {
+        angle(self.normal(), normal) <= ANGLE_TOLERANCE
+    }
+
+    /// Voxel-to-patient affine mapping (column, row, slice, 1) to patient coordinates, with `slice_vector` the offset
+    /// between consecutive slices.
+    pub fn affine(
+        &self,
+        slice_vector: [f64; 3],
+    ) -> [[f64; 4]; 4] {
+        let [row_spacing, column_spacing] = self.pixel_spacing;
+        let mut affine = [[0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+        for axis in 0..3 {
+            affine[axis] =
+                [self.row_direction[axis] * column_spacing, self.column_direction[axis] * row_spacing, slice_vector[axis], self.position[axis]];
+        }
+
+        affine
+    }
+}
+
+/// Slices of a series sorted along their normal.
+#[derive(Debug, Clone)]
+pub struct SliceOrder {
+    /// Indices of the images with geometry, sorted by distance along the normal of the first image.
+    pub order: Vec<usize>,
+    /// Distance of each sorted image along the normal.
+    pub distances: Vec<f64>,
+    /// Normal of the first image.
+    pub normal: [f64; 3],
+}
+
+impl SliceOrder {
+    /// Sorts images along the normal of the first one. Images without geometry are left out.
+    pub fn new(geometries: &[Option<ImageGeometry>]) -> Option<Self> {
+        let normal = geometries
+            .iter()
+            .flatten()
+            .next()?
+            .normal();
+
+        let mut order: Vec<(usize, f64)> = geometries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, geometry)| {
+                Some((
+                    index,
+                    dot(
+                        normal,
+                        geometry
+                            .as_ref()?
+                            .position,
+                    ),
+                ))
+            })
+            .collect();
+        order.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        Some(Self {
+            order: order
+                .iter()
+                .map(|(index, _)| *index)
+                .collect(),
+            distances: order
+                .iter()
+                .map(|(_, distance)| *distance)
+                .collect(),
+            normal,
+        })
+    }
+
+    /// True if any images are at the same position.
+    pub fn has_duplicates(&self) -> bool {
+        self.distances
+            .windows(2)
+            .any(|pair| pair[1] - pair[0] <= POSITION_TOLERANCE)
+    }
+
+    /// Spacing between consecutive distinct positions.
+    pub fn spacings(&self) -> Vec<f64> {
+        self.distances
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .filter(|spacing| *spacing > POSITION_TOLERANCE)
+            .collect()
+    }
+
+    /// Median spacing between consecutive distinct positions.
+    pub fn slice_spacing(&self) -> Option<f64> {
+        let mut spacings = self.spacings();
+        if spacings.is_empty() {
+            return None;
+        }
+
+        spacings.sort_by(f64::total_cmp);
+        Some(spacings[spacings.len() / 2])
+    }
+}
+
+/// Voxel-to-patient affine of a volume of `count` slices from `first` to `last`. The slice axis steps from the first
+/// to the last position, which includes any gantry tilt.
+pub fn volume_affine(
+    first: &ImageGeometry,
+    last: &ImageGeometry,
+    count: usize,
+) -> [[f64; 4]; 4] {
+    let slice_vector = if count > 1 {
+        scale(sub(last.position, first.position), 1.0 / (count - 1) as f64)
+    } else {
+        scale(
+            first.normal(),
+            first
+                .slice_thickness
+                .unwrap_or(1.0),
+        )
+    };
+
+    first.affine(slice_vector)
+}
+
+fn series_to_value(
+    series_instance_uid: String,
+    images: Vec<Image>,
+    span: Span,
+) -> Value {
+    let geometries: Vec<Option<ImageGeometry>> = images
+        .iter()
+        .map(|image| {
+            image
+                .geometry
+                .clone()
+        })
+        .collect();
+    let slices = SliceOrder::new(&geometries);
+
+    let mut issues = Vec::new();
+
+    let missing = geometries
+        .iter()
+        .filter(|geometry| geometry.is_none())
+        .count();
+    if missing > 0 {
+        issues.push(format!("{missing} images without ImagePositionPatient, ImageOrientationPatient or PixelSpacing"));
+    }
+
+    let Some(slices) = slices else {
+        let images = images
+            .into_iter()
+            .map(|image| image_to_value(image, None, None, None, span))
+            .collect();
+
+        return Value::record(
+            Record::from_iter([
+                ("SeriesInstanceUID".to_string(), Value::string(series_instance_uid, span)),
+                ("count".to_string(), Value::int(geometries.len() as i64, span)),
+                (
+                    "issues".to_string(),
+                    Value::list(
+                        issues
+                            .into_iter()
+                            .map(|issue| Value::string(issue, span))
+                            .collect(),
+                        span,
+                    ),
+                ),
+                ("images".to_string(), Value::list(images, span)),
+            ]),
+            span,
+        );
+    };
+
+    let sorted: Vec<&ImageGeometry> = slices
+        .order
+        .iter()
+        .filter_map(|index| geometries[*index].as_ref())
+        .collect();
+    let first = sorted[0];
+    let last = sorted[sorted.len() - 1];
+    let slice_spacing = slices.slice_spacing();
+
+    // orientation
+    let inconsistent = sorted
+        .iter()
+        .filter(|geometry| !geometry.has_normal(slices.normal))
+        .count();
+    if inconsistent > 0 {
+        issues.push(format!("{inconsistent} images with a different orientation"));
+    }
+
+    // duplicates, i.e. consecutive images at the same position
+    let mut duplicates: Vec<(f64, usize)> = Vec::new();
+    for pair in slices
+        .distances
+        .windows(2)
+    {
+        if pair[1] - pair[0] > POSITION_TOLERANCE {
+            continue;
+        }
+        match duplicates.last_mut() {
+            Some((distance, count)) if (pair[0] - *distance).abs() <= POSITION_TOLERANCE => *count += 1,
+            _ => duplicates.push((pair[0], 2)),
+        }
+    }
+    for (distance, count) in &duplicates {
+        issues.push(format!("{count} images at position {distance:.3}"));
+    }
+
+    // spacing
+    let spacings = slices.spacings();
+    let mut uniform = true;
+    let mut gaps = Vec::new();
+    if let Some(median) = slice_spacing {
+        uniform = spacings
+            .iter()
+            .all(|spacing| (spacing - median).abs() <= SPACING_TOLERANCE * median);
+        if !uniform {
+            issues.push("non-uniform slice spacing".to_string());
+        }
+
+        let mut previous = None;
+        for distance in &slices.distances {
+            if let Some(previous) = previous
+                && distance - previous > GAP_FACTOR * median
+            {
+                gaps.push((previous, *distance));
+                issues.push(format!("gap of {:.3} between positions {previous:.3} and {distance:.3}", distance - previous));
+            }
+            previous = Some(*distance);
+        }
+    }
+
+    // gantry tilt, i.e. the slices are stacked at an angle to their normal
+    let stack_direction = normalize(sub(last.position, first.position));
+    let gantry_tilt = stack_direction.map(|direction| angle(direction, slices.normal));
+    if let Some(tilt) = gantry_tilt
+        && tilt > ANGLE_TOLERANCE
+    {
+        issues.push(format!("gantry tilt of {tilt:.2} degrees"));
+    }
+
+    let image_slice_vector = |geometry: &ImageGeometry| {
+        scale(
+            geometry.normal(),
+            slice_spacing
+                .or(geometry.slice_thickness)
+                .unwrap_or(1.0),
+        )
+    };
+
+    let mut images: Vec<Option<Image>> = images
+        .into_iter()
+        .map(Some)
+        .collect();
+    let mut image_values = Vec::with_capacity(images.len());
+
+    let mut previous = None;
+    for (index, distance) in slices
+        .order
+        .iter()
+        .zip(&slices.distances)
+    {
+        let Some(image) = images[*index].take() else {
+            continue;
+        };
+        let affine = image
+            .geometry
+            .as_ref()
+            .map(|geometry| geometry.affine(image_slice_vector(geometry)));
+        let spacing = previous.map(|previous| distance - previous);
+        image_values.push(image_to_value(image, Some(*distance), spacing, affine, span));
+        previous = Some(*distance);
+    }
+    // images without geometry go last
+    image_values.extend(
+        images
+            .into_iter()
+            .flatten()
+            .map(|image| image_to_value(image, None, None, None, span)),
+    );
+
+    let optional_float = |value: Option<f64>| value.map_or_else(|| Value::nothing(span), |value| Value::float(value, span));
+
+    Value::record(
+        Record::from_iter([
+            ("SeriesInstanceUID".to_string(), Value::string(series_instance_uid, span)),
+            ("count".to_string(), Value::int(geometries.len() as i64, span)),
+            ("normal".to_string(), floats_to_value(&slices.normal, span)),
+            ("slice_spacing".to_string(), optional_float(slice_spacing)),
+            ("uniform".to_string(), Value::bool(uniform, span)),
+            ("gantry_tilt".to_string(), optional_float(gantry_tilt)),
+            (
+                "duplicates".to_string(),
+                Value::list(
+                    duplicates
+                        .into_iter()
+                        .map(|(distance, count)| {
+                            Value::record(
+                                Record::from_iter([
+                                    ("distance".to_string(), Value::float(distance, span)),
+                                    ("count".to_string(), Value::int(count as i64, span)),
+                                ]),
+                                span,
+                            )
+                        })
+                        .collect(),
+                    span,
+                ),
+            ),
+            (
+                "gaps".to_string(),
+                Value::list(
+                    gaps.into_iter()
+                        .map(|(from, to)| {
+                            Value::record(
+                                Record::from_iter([
+                                    ("from".to_string(), Value::float(from, span)),
+                                    ("to".to_string(), Value::float(to, span)),
+                                    ("spacing".to_string(), Value::float(to - from, span)),
+                                ]),
+                                span,
+                            )
+                        })
+                        .collect(),
+                    span,
+                ),
+            ),
+            ("affine".to_string(), affine_to_value(&volume_affine(first, last, sorted.len()), span)),
+            (
+                "issues".to_string(),
+                Value::list(
+                    issues
+                        .into_iter()
+                        .map(|issue| Value::string(issue, span))
+                        .collect(),
+                    span,
+                ),
+            ),
+            ("images".to_string(), Value::list(image_values, span)),
+        ]),
+        span,
+    )
+}
+
+fn image_to_value(
+    image: Image,
+    distance: Option<f64>,
+    spacing: Option<f64>,
+    affine: Option<[[f64; 4]; 4]>,
+    span: Span,
+) -> Value {
+    let optional = |value: Option<Value>| value.unwrap_or_else(|| Value::nothing(span));
+    let geometry = image
+        .geometry
+        .as_ref();
+
+    Value::record(
+        Record::from_iter([
+            ("path".to_string(), image.path),
+            (
+                "SOPInstanceUID".to_string(),
+                optional(
+                    image
+                        .sop_instance_uid
+                        .map(|uid| Value::string(uid, span)),
+                ),
+            ),
+            (
+                "frame".to_string(),
+                optional(
+                    image
+                        .frame
+                        .map(|frame| Value::int(frame as i64, span)),
+                ),
+            ),
+            ("ImagePositionPatient".to_string(), optional(geometry.map(|geometry| floats_to_value(&geometry.position, span)))),
+            ("normal".to_string(), optional(geometry.map(|geometry| floats_to_value(&geometry.normal(), span)))),
+            ("distance".to_string(), optional(distance.map(|distance| Value::float(distance, span)))),
+            ("spacing".to_string(), optional(spacing.map(|spacing| Value::float(spacing, span)))),
+            ("affine".to_string(), optional(affine.map(|affine| affine_to_value(&affine, span)))),
+        ]),
+        span,
+    )
+}
+
+fn floats_to_value(
+    values: &[f64],
+    span: Span,
+) -> Value {
+    Value::list(
+        values
+            .iter()
+            .map(|value| Value::float(*value, span))
+            .collect(),
+        span,
+    )
+}
+
+fn affine_to_value(
+    affine: &[[f64; 4]; 4],
+    span: Span,
+) -> Value {
+    Value::list(
+        affine
+            .iter()
+            .map(|row| floats_to_value(row, span))
+            .collect(),
+        span,
+    )
+}
+
+fn dot(
+    a: [f64; 3],
+    b: [f64; 3],
+) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(
+    a: [f64; 3],
+    b: [f64; 3],
+) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn sub(
+    a: [f64; 3],
+    b: [f64; 3],
+) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(
+    a: [f64; 3],
+    factor: f64,
+) -> [f64; 3] {
+    [a[0] * factor, a[1] * factor, a[2] * factor]
+}
+
+/// Returns `a` scaled to unit length, `None` for a zero vector.
+fn normalize(a: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(a, a).sqrt();
+    (length > f64::EPSILON).then(|| scale(a, 1.0 / length))
+}
+
+/// Angle between unit vectors, in degrees.
+fn angle(
+    a: [f64; 3],
+    b: [f64; 3],
+) -> f64 {
+    dot(a, b)
+        .clamp(-1.0, 1.0)
+        .acos()
+        .to_degrees()
+}
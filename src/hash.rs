@@ -0,0 +1,281 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use dicom::core::header::Header;
+use dicom::core::{DataDictionary, DicomValue, PrimitiveValue, Tag};
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Value};
+use sha2::{Digest, Sha256};
+
+use crate::input::{DicomSource, collect_input, into_output, is_list_input};
+use crate::plugin::DcmPlugin;
+use crate::reader::read_dcm_stream_with_pixel_data;
+
+/// Trailing padding of a data set, it doesn't contribute to the content.
+const DATA_SET_TRAILING_PADDING: Tag = Tag(0xFFFC, 0xFFFC);
+
+const PIXEL_DATA_TAGS: [Tag; 3] = [tags::PIXEL_DATA, tags::FLOAT_PIXEL_DATA, tags::DOUBLE_FLOAT_PIXEL_DATA];
+
+/// `dcm hash` command, computes a hash of the data set independent of its encoding.
+pub struct DcmHashCommand;
+
+impl PluginCommand for DcmHashCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm hash"
+    }
+
+    fn description(&self) -> &str {
+        "Compute a SHA-256 hash of the DICOM data set contents, independent of how the data set is encoded."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .switch("no-pixel-data", "Leave pixel data out of the hash.", None)
+            .switch("no-private", "Leave private elements out of the hash.", None)
+            .named(
+                "exclude",
+                SyntaxShape::List(Box::new(SyntaxShape::String)),
+                "Attribute keywords or tags (e.g. `(0008,0018)`) to leave out of the hash, at any nesting level.",
+                Some('x'),
+            )
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "hash".to_string(), "checksum".to_string(), "compare".to_string()])
+            .extra_description(
+                "The hash is the same regardless of the transfer syntax byte order, explicit or implicit VR, the preamble, the file meta group, \
+                 group length elements and value padding. Values are compared as read, so e.g. `1.0` and `1` in a DS element differ. Private \
+                 elements read without their VR (implicit VR) are hashed as raw bytes, use --no-private to ignore them. Returns a string for a \
+                 single input and a list of strings for a list.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example { description: "Hash a DICOM file", example: "\"file.dcm\" | dcm hash", result: None },
+            Example {
+                description: "Check that a transcoded file has the same content, ignoring its new SOP Instance UID",
+                example: "(\"original.dcm\" | dcm hash -x [SOPInstanceUID]) == (\"transcoded.dcm\" | dcm hash -x [SOPInstanceUID])",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let options = HashOptions {
+            exclude_pixel_data: call.has_flag("no-pixel-data")?,
+            exclude_private: call.has_flag("no-private")?,
+            exclude: get_exclude_flag(call, &plugin.dcm_dictionary)?,
+        };
+
+        let is_list = is_list_input(&input);
+
+        let mut hashes = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let bytes = source
+                .bytes()
+                .map_err(|e| source.error(&e, span))?;
+            let obj = read_dcm_stream_with_pixel_data(Cursor::new(&bytes[..]), None).map_err(|e| source.error(&e, span))?;
+
+            hashes.push(Value::string(dataset_hash(&obj, &options), span));
+        }
+
+        Ok(into_output(hashes, is_list, call.head))
+    }
+}
+
+fn get_exclude_flag(
+    call: &EvaluatedCall,
+    dictionary: &impl DataDictionary,
+) -> Result<Vec<Tag>, LabeledError> {
+    let Some(value) = call.get_flag_value("exclude") else {
+        return Ok(Vec::new());
+    };
+
+    value
+        .as_list()?
+        .iter()
+        .map(|value| {
+            let name = value.as_str()?;
+            dictionary
+                .parse_tag(name)
+                .ok_or_else(|| {
+                    LabeledError::new("Unknown attribute").with_label(format!("`{name}` is not an attribute keyword or tag"), value.span())
+                })
+        })
+        .collect()
+}
+
+/// Elements left out of the data set hash.
+#[derive(Debug, Default, Clone)]
+pub struct HashOptions {
+    /// Leave out PixelData (and float pixel data), at any nesting level.
+    pub exclude_pixel_data: bool,
+    /// Leave out private elements, including private creators.
+    pub exclude_private: bool,
+    /// Leave out these elements, at any nesting level.
+    pub exclude: Vec<Tag>,
+}
+
+impl HashOptions {
+    fn is_excluded(
+        &self,
+        tag: Tag,
+    ) -> bool {
+        // group lengths depend on the encoding, trailing padding is not content
+        if tag.element() == 0x0000 || tag == DATA_SET_TRAILING_PADDING {
+            return true;
+        }
+
+        (self.exclude_pixel_data && PIXEL_DATA_TAGS.contains(&tag))
+            || (self.exclude_private && tag.group() % 2 == 1)
+            || self
+                .exclude
+                .contains(&tag)
+    }
+}
+
+/// Returns the SHA-256 hash of `bytes` as a hex string.
+pub fn bytes_hash(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Returns a SHA-256 hash of the data set contents as a hex string.
+///
+/// The hash doesn't depend on how the data set is encoded: the file meta group and group length elements are ignored,
+/// VRs are not hashed (so explicit and implicit VR encodings match), binary values are hashed in little endian and
+/// text values without their padding. Values which implicit VR can't decode (e.g. private elements read as UN) are
+/// hashed as their raw bytes, so they may differ between explicit and implicit VR encodings.
+pub fn dataset_hash(
+    obj: &InMemDicomObject,
+    options: &HashOptions,
+) -> String {
+    let mut hasher = Sha256::new();
+    hash_dataset(&mut hasher, obj, options);
+
+    format!("{:x}", hasher.finalize())
+}
+
+fn hash_dataset(
+    hasher: &mut Sha256,
+    obj: &InMemDicomObject,
+    options: &HashOptions,
+) {
+    for element in obj {
+        let tag = element.tag();
+
+        if options.is_excluded(tag) {
+            continue;
+        }
+
+        hasher.update(
+            tag.group()
+                .to_le_bytes(),
+        );
+        hasher.update(
+            tag.element()
+                .to_le_bytes(),
+        );
+
+        match element.value() {
+            DicomValue::Primitive(value) => {
+                hasher.update([b'P']);
+                hash_bytes(hasher, &primitive_bytes(value));
+            }
+            DicomValue::Sequence(sequence) => {
+                hasher.update([b'S']);
+                hasher.update(
+                    (sequence
+                        .items()
+                        .len() as u64)
+                        .to_le_bytes(),
+                );
+                for item in sequence.items() {
+                    hash_dataset(hasher, item, options);
+                    // items have no length in the hash, mark their end instead
+                    hasher.update([b'E']);
+                }
+            }
+            DicomValue::PixelSequence(sequence) => {
+                // the basic offset table only depends on the fragments
+                hasher.update([b'F']);
+                hasher.update(
+                    (sequence
+                        .fragments()
+                        .len() as u64)
+                        .to_le_bytes(),
+                );
+                for fragment in sequence.fragments() {
+                    hash_bytes(hasher, fragment);
+                }
+            }
+        }
+    }
+}
+
+/// Hashes `bytes` prefixed with their length, so that consecutive values can't run into each other.
+fn hash_bytes(
+    hasher: &mut Sha256,
+    bytes: &[u8],
+) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+/// Returns the canonical byte representation of a value.
+fn primitive_bytes(value: &PrimitiveValue) -> Vec<u8> {
+    fn le_bytes<T, const N: usize>(
+        values: &[T],
+        to_le_bytes: impl Fn(&T) -> [u8; N],
+    ) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(to_le_bytes)
+            .collect()
+    }
+
+    match value {
+        PrimitiveValue::Empty => Vec::new(),
+        PrimitiveValue::U8(values) => values.to_vec(),
+        PrimitiveValue::I16(values) => le_bytes(values, |v| v.to_le_bytes()),
+        PrimitiveValue::U16(values) => le_bytes(values, |v| v.to_le_bytes()),
+        PrimitiveValue::I32(values) => le_bytes(values, |v| v.to_le_bytes()),
+        PrimitiveValue::U32(values) => le_bytes(values, |v| v.to_le_bytes()),
+        PrimitiveValue::I64(values) => le_bytes(values, |v| v.to_le_bytes()),
+        PrimitiveValue::U64(values) => le_bytes(values, |v| v.to_le_bytes()),
+        PrimitiveValue::F32(values) => le_bytes(values, |v| v.to_le_bytes()),
+        PrimitiveValue::F64(values) => le_bytes(values, |v| v.to_le_bytes()),
+        PrimitiveValue::Tags(values) => le_bytes(values, |tag| {
+            let [g0, g1] = tag
+                .group()
+                .to_le_bytes();
+            let [e0, e1] = tag
+                .element()
+                .to_le_bytes();
+            [g0, g1, e0, e1]
+        }),
+        PrimitiveValue::Str(_) | PrimitiveValue::Strs(_) | PrimitiveValue::Date(_) | PrimitiveValue::Time(_) | PrimitiveValue::DateTime(_) => value
+            .to_multi_str()
+            .iter()
+            .map(|s| s.trim_end_matches(['\0', ' ']))
+            .collect::<Vec<_>>()
+            .join("\\")
+            .into_bytes(),
+    }
+}
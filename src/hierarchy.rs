@@ -0,0 +1,488 @@
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Value};
+
+use crate::dcm::DicomDump;
+use crate::input::{DicomSource, collect_input, get_record_string};
+use crate::plugin::DcmPlugin;
+use crate::reader::ErrorKind;
+
+/// Attributes of PATIENT level records, checked for consistency across the instances of a study.
+const PATIENT_ATTRIBUTES: &[&str] = &["PatientID", "PatientName", "PatientBirthDate", "PatientSex"];
+
+/// Attributes of STUDY level records, taken from the first instance of the study.
+const STUDY_ATTRIBUTES: &[&str] = &["StudyInstanceUID", "StudyDate", "StudyTime", "StudyID", "AccessionNumber", "StudyDescription"];
+
+/// Attributes of SERIES level records, taken from the first instance of the series.
+const SERIES_ATTRIBUTES: &[&str] = &["SeriesInstanceUID", "SeriesNumber", "Modality", "SeriesDescription", "BodyPartExamined"];
+
+/// Dates of an instance, in order of preference. Used for the date range of series and studies.
+const INSTANCE_DATE_ATTRIBUTES: &[&str] = &["ContentDate", "AcquisitionDate", "SeriesDate", "StudyDate"];
+
+/// `dcm hierarchy` command, groups instances into patients, studies and series.
+pub struct DcmHierarchyCommand;
+
+impl PluginCommand for DcmHierarchyCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm hierarchy"
+    }
+
+    fn description(&self) -> &str {
+        "Group DICOM instances into a patient/study/series hierarchy."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "group".to_string(), "study".to_string(), "series".to_string()])
+            .extra_description(
+                "Input is either files (file names, file records or binary data) or records produced by `dcm`. Records may have `name` and `size` \
+                 columns with the file path and size. `dcm` doesn't output them, merge its records with `ls` to keep them, otherwise series \
+                 have no `paths` and sizes are 0. Returns a list of patients with nested `studies`, and studies with nested `series`. Each \
+                 level has the number of `instances`, the total `size` and the `first_date`/`last_date` range of instance dates. Series list the \
+                 `paths` of their files. Studies whose instances disagree on patient attributes list them in `inconsistent`. Non-DICOM files are skipped.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show patients, studies and series of all files in the current directory",
+                example: "ls **/* | where type == file | dcm hierarchy",
+                result: None,
+            },
+            Example {
+                description: "Group parsed files and keep their paths and sizes",
+                example: "let files = ls **/* | where type == file; $files | select name size | merge ($files | dcm) | dcm hierarchy",
+                result: None,
+            },
+            Example {
+                description: "Find studies with inconsistent patient attributes",
+                example: "ls **/* | dcm hierarchy | get studies | flatten | where ($it.inconsistent | is-not-empty)",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let dumper = DicomDump { dcm_dictionary: &plugin.dcm_dictionary };
+        let mut hierarchy = Hierarchy::default();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+
+            let instance = match value {
+                Value::Record { val, .. } if !is_file_record(&val) => Instance::from_record(val.into_owned()),
+                value => {
+                    let source = DicomSource::from_value(value, current_dir.as_deref())?;
+                    match Instance::from_source(&source, &dumper, span)? {
+                        Some(instance) => instance,
+                        None => continue,
+                    }
+                }
+            };
+
+            hierarchy.add(instance, span)?;
+        }
+
+        Ok(hierarchy
+            .into_value(call.head)
+            .into_pipeline_data())
+    }
+}
+
+fn is_file_record(record: &Record) -> bool {
+    matches!(get_record_string(record, "type"), Some("file" | "symlink")) && get_record_string(record, "name").is_some()
+}
+
+/// Attributes of a single instance, with its file path and size if known.
+struct Instance {
+    attributes: Record,
+    path: Option<Value>,
+    size: Option<Value>,
+}
+
+impl Instance {
+    fn from_record(mut attributes: Record) -> Self {
+        let path = attributes.remove("name");
+        let size = attributes.remove("size");
+
+        Self { attributes, path, size }
+    }
+
+    /// Reads the instance from a file or binary data, none if it's not DICOM.
+    fn from_source(
+        source: &DicomSource,
+        dumper: &DicomDump,
+        span: Span,
+    ) -> Result<Option<Self>, LabeledError> {
+        let obj = match source.read() {
+            Ok(obj) => obj,
+            Err(e) if e.kind() == ErrorKind::NotDicom => return Ok(None),
+            Err(e) => return Err(source.error(&e, span)),
+        };
+
+        let size = source
+            .size()
+            .map_err(|e| source.error(&e, span))?;
+
+        let mut index_map = IndexMap::with_capacity(100);
+        dumper.make_row_from_dicom_object(&span, &mut index_map, &obj);
+
+        Ok(Some(Self {
+            attributes: Record::from_iter(index_map),
+            path: source
+                .path()
+                .map(|path| Value::string(path.to_string_lossy(), span)),
+            size: Some(Value::filesize(size as i64, span)),
+        }))
+    }
+
+    fn get(
+        &self,
+        attribute: &str,
+    ) -> Value {
+        self.attributes
+            .get(attribute)
+            .cloned()
+            .unwrap_or_else(|| Value::nothing(Span::unknown()))
+    }
+
+    /// Returns the attribute as a string, e.g. to be used as a key. Empty values are ignored.
+    fn get_string(
+        &self,
+        attribute: &str,
+    ) -> Option<String> {
+        let value = self
+            .attributes
+            .get(attribute)?
+            .coerce_string()
+            .ok()?;
+
+        (!value.is_empty()).then_some(value)
+    }
+
+    fn date(&self) -> Option<String> {
+        INSTANCE_DATE_ATTRIBUTES
+            .iter()
+            .find_map(|attribute| self.get_string(attribute))
+    }
+
+    fn size(&self) -> i64 {
+        self.size
+            .as_ref()
+            .and_then(|size| {
+                size.as_filesize()
+                    .ok()
+            })
+            .map_or(0, |size| size.get())
+    }
+}
+
+/// Instance count, total size and date range of a group of instances.
+#[derive(Default)]
+struct Summary {
+    instances: usize,
+    size: i64,
+    first_date: Option<String>,
+    last_date: Option<String>,
+}
+
+impl Summary {
+    fn add(
+        &mut self,
+        instance: &Instance,
+    ) {
+        self.instances += 1;
+        self.size += instance.size();
+
+        // DA values compare chronologically as strings
+        if let Some(date) = instance.date() {
+            self.add_dates(&date, &date);
+        }
+    }
+
+    fn merge(
+        &mut self,
+        other: &Summary,
+    ) {
+        self.instances += other.instances;
+        self.size += other.size;
+
+        if let (Some(first), Some(last)) = (&other.first_date, &other.last_date) {
+            self.add_dates(first, last);
+        }
+    }
+
+    fn add_dates(
+        &mut self,
+        first: &str,
+        last: &str,
+    ) {
+        if self
+            .first_date
+            .as_deref()
+            .is_none_or(|date| first < date)
+        {
+            self.first_date = Some(first.to_string());
+        }
+        if self
+            .last_date
+            .as_deref()
+            .is_none_or(|date| last > date)
+        {
+            self.last_date = Some(last.to_string());
+        }
+    }
+
+    fn insert_into(
+        &self,
+        record: &mut Record,
+        span: Span,
+    ) {
+        let date = |date: &Option<String>| {
+            date.as_ref()
+                .map_or(Value::nothing(span), |date| Value::string(date, span))
+        };
+
+        record.push("instances", Value::int(self.instances as i64, span));
+        record.push("size", Value::filesize(self.size, span));
+        record.push("first_date", date(&self.first_date));
+        record.push("last_date", date(&self.last_date));
+    }
+}
+
+struct Series {
+    attributes: Vec<Value>,
+    modality: Value,
+    summary: Summary,
+    paths: Vec<Value>,
+}
+
+struct Study {
+    attributes: Vec<Value>,
+    /// Distinct values of each of `PATIENT_ATTRIBUTES`, in order of appearance.
+    patient: Vec<Vec<Value>>,
+    summary: Summary,
+    series: IndexMap<String, Series>,
+}
+
+#[derive(Default)]
+struct Hierarchy {
+    studies: IndexMap<String, Study>,
+}
+
+impl Hierarchy {
+    fn add(
+        &mut self,
+        instance: Instance,
+        span: Span,
+    ) -> Result<(), LabeledError> {
+        let missing = |attribute: &str| LabeledError::new("Invalid DICOM instance").with_label(format!("Missing {attribute}"), span);
+
+        let study_key = instance
+            .get_string("StudyInstanceUID")
+            .ok_or_else(|| missing("StudyInstanceUID"))?;
+        let series_key = instance
+            .get_string("SeriesInstanceUID")
+            .ok_or_else(|| missing("SeriesInstanceUID"))?;
+
+        let study = self
+            .studies
+            .entry(study_key)
+            .or_insert_with(|| Study {
+                attributes: get_all(&instance, STUDY_ATTRIBUTES),
+                patient: vec![Vec::new(); PATIENT_ATTRIBUTES.len()],
+                summary: Summary::default(),
+                series: IndexMap::new(),
+            });
+
+        for (values, attribute) in study
+            .patient
+            .iter_mut()
+            .zip(PATIENT_ATTRIBUTES)
+        {
+            let value = instance.get(attribute);
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+
+        study
+            .summary
+            .add(&instance);
+
+        let series = study
+            .series
+            .entry(series_key)
+            .or_insert_with(|| Series {
+                attributes: get_all(&instance, SERIES_ATTRIBUTES),
+                modality: instance.get("Modality"),
+                summary: Summary::default(),
+                paths: Vec::new(),
+            });
+
+        series
+            .summary
+            .add(&instance);
+        series
+            .paths
+            .extend(instance.path);
+
+        Ok(())
+    }
+
+    /// Converts the studies to a list of patient records. Studies are assigned to patients by the first PatientID seen.
+    fn into_value(
+        self,
+        span: Span,
+    ) -> Value {
+        let mut patients: IndexMap<String, (Record, Summary, Vec<Value>)> = IndexMap::new();
+
+        for study in self
+            .studies
+            .into_values()
+        {
+            let patient_key = study.patient[0]
+                .first()
+                .and_then(|id| {
+                    id.coerce_string()
+                        .ok()
+                })
+                .unwrap_or_default();
+
+            let (_, summary, studies) = patients
+                .entry(patient_key)
+                .or_insert_with(|| {
+                    let record = PATIENT_ATTRIBUTES
+                        .iter()
+                        .zip(&study.patient)
+                        .map(|(attribute, values)| {
+                            (
+                                attribute.to_string(),
+                                values[0]
+                                    .clone()
+                                    .with_span(span),
+                            )
+                        })
+                        .collect();
+
+                    (record, Summary::default(), Vec::new())
+                });
+
+            summary.merge(&study.summary);
+            studies.push(study.into_value(span));
+        }
+
+        let patients = patients
+            .into_values()
+            .map(|(mut record, summary, studies)| {
+                summary.insert_into(&mut record, span);
+                record.push("studies", Value::list(studies, span));
+                Value::record(record, span)
+            })
+            .collect();
+
+        Value::list(patients, span)
+    }
+}
+
+impl Study {
+    fn into_value(
+        self,
+        span: Span,
+    ) -> Value {
+        let mut record = to_record(STUDY_ATTRIBUTES, self.attributes, span);
+
+        let mut modalities: Vec<Value> = Vec::new();
+        for series in self
+            .series
+            .values()
+        {
+            let modality = series
+                .modality
+                .clone()
+                .with_span(span);
+            if !modality.is_nothing() && !modalities.contains(&modality) {
+                modalities.push(modality);
+            }
+        }
+        record.push("modalities", Value::list(modalities, span));
+
+        self.summary
+            .insert_into(&mut record, span);
+
+        let inconsistent = PATIENT_ATTRIBUTES
+            .iter()
+            .zip(self.patient)
+            .filter(|(_, values)| values.len() > 1)
+            .map(|(attribute, values)| {
+                let values = values
+                    .into_iter()
+                    .map(|value| value.with_span(span))
+                    .collect();
+
+                Value::record(
+                    Record::from_iter([
+                        ("attribute".to_string(), Value::string(*attribute, span)),
+                        ("values".to_string(), Value::list(values, span)),
+                    ]),
+                    span,
+                )
+            })
+            .collect();
+        record.push("inconsistent", Value::list(inconsistent, span));
+
+        let series = self
+            .series
+            .into_values()
+            .map(|series| {
+                let mut record = to_record(SERIES_ATTRIBUTES, series.attributes, span);
+                series
+                    .summary
+                    .insert_into(&mut record, span);
+                record.push("paths", Value::list(series.paths, span));
+                Value::record(record, span)
+            })
+            .collect();
+        record.push("series", Value::list(series, span));
+
+        Value::record(record, span)
+    }
+}
+
+fn get_all(
+    instance: &Instance,
+    attributes: &[&str],
+) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|attribute| instance.get(attribute))
+        .collect()
+}
+
+fn to_record(
+    attributes: &[&str],
+    values: Vec<Value>,
+    span: Span,
+) -> Record {
+    attributes
+        .iter()
+        .zip(values)
+        .map(|(attribute, value)| (attribute.to_string(), value.with_span(span)))
+        .collect()
+}
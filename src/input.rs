@@ -0,0 +1,169 @@
+use std::{
+    borrow::Cow,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use dicom::object::DefaultDicomObject;
+use nu_protocol::{IntoPipelineData, LabeledError, PipelineData, Record, ShellError, Span, Value};
+use snafu::ResultExt;
+
+use crate::reader::{Error as ReaderError, IoSnafu, read_dcm_file, read_dcm_stream};
+
+/// A DICOM object passed to a `dcm` subcommand, either as a file name, a file record (e.g. from `ls`) or binary data.
+pub enum DicomSource {
+    File(PathBuf),
+    Binary(Vec<u8>),
+}
+
+impl DicomSource {
+    pub fn from_value(
+        value: Value,
+        current_dir: Result<&Path, &ShellError>,
+    ) -> Result<Self, LabeledError> {
+        let span = value.span();
+
+        match value {
+            Value::String { val, .. } => Ok(DicomSource::File(resolve_path(&val, current_dir, span)?)),
+            Value::Record { val, .. } => {
+                let record_type = get_record_string(&val, "type");
+                let record_name = get_record_string(&val, "name");
+
+                match (record_type, record_name) {
+                    (Some("file" | "symlink"), Some(name)) => Ok(DicomSource::File(resolve_path(name, current_dir, span)?)),
+                    _ => Err(LabeledError::new("Cannot process records directly, unless they are File records")
+                        .with_label("Select file name, binary data, or use records with `name` and `type`", span)),
+                }
+            }
+            Value::Binary { val, .. } => Ok(DicomSource::Binary(val)),
+            _ => Err(LabeledError::new("Unrecognized type in stream").with_label("Expected a string (filepath), binary, or file record", span)),
+        }
+    }
+
+    /// Path of the file, if the source is a file.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            DicomSource::File(path) => Some(path),
+            DicomSource::Binary(_) => None,
+        }
+    }
+
+    /// Returns the raw bytes of the source.
+    pub fn bytes(&self) -> Result<Cow<'_, [u8]>, ReaderError> {
+        match self {
+            DicomSource::File(path) => Ok(Cow::Owned(fs::read(path).context(IoSnafu)?)),
+            DicomSource::Binary(bytes) => Ok(Cow::Borrowed(bytes)),
+        }
+    }
+
+    /// Reads the DICOM object, without PixelData.
+    pub fn read(&self) -> Result<DefaultDicomObject, ReaderError> {
+        match self {
+            DicomSource::File(path) => read_dcm_file(path, None),
+            DicomSource::Binary(bytes) => read_dcm_stream(Cursor::new(bytes), None),
+        }
+    }
+
+    /// Size of the source in bytes.
+    pub fn size(&self) -> Result<u64, ReaderError> {
+        match self {
+            DicomSource::File(path) => Ok(fs::metadata(path)
+                .context(IoSnafu)?
+                .len()),
+            DicomSource::Binary(bytes) => Ok(bytes.len() as u64),
+        }
+    }
+
+    /// Converts a reader error to an error labelled with the source file name, if any.
+    pub fn error(
+        &self,
+        error: &ReaderError,
+        span: Span,
+    ) -> LabeledError {
+        self.labeled_error("Invalid DICOM data", error, span)
+    }
+
+    /// Converts an error processing the source to an error titled `msg`, labelled with the source file name, if any.
+    pub fn labeled_error(
+        &self,
+        msg: &str,
+        error: &dyn std::error::Error,
+        span: Span,
+    ) -> LabeledError {
+        let text = match self {
+            DicomSource::File(path) => format!("{} [file {}]", error, path.to_string_lossy()),
+            DicomSource::Binary(_) => error.to_string(),
+        };
+
+        LabeledError::new(msg).with_label(text, span)
+    }
+}
+
+/// Collects all input values of a subcommand. A byte stream is collected into a single binary value.
+pub fn collect_input(
+    input: PipelineData,
+    span: Span,
+) -> Result<Vec<Value>, LabeledError> {
+    match input {
+        PipelineData::Empty => Ok(Vec::new()),
+        PipelineData::Value(Value::List { vals, .. }, ..) => Ok(vals),
+        PipelineData::Value(value, ..) => Ok(vec![value]),
+        PipelineData::ListStream(list_stream, ..) => Ok(list_stream
+            .into_iter()
+            .collect()),
+        PipelineData::ByteStream(byte_stream, ..) => Ok(vec![Value::binary(byte_stream.into_bytes()?, span)]),
+    }
+}
+
+/// True if the input is a list of values, e.g. so that a single value can be returned for a single input.
+pub fn is_list_input(input: &PipelineData) -> bool {
+    matches!(input, PipelineData::ListStream(..) | PipelineData::Value(Value::List { .. }, ..))
+}
+
+/// Returns `values` as the output of a subcommand: a single value for a single input, a list otherwise.
+pub fn into_output(
+    mut values: Vec<Value>,
+    is_list: bool,
+    span: Span,
+) -> PipelineData {
+    if !is_list && values.len() == 1 {
+        return values
+            .remove(0)
+            .into_pipeline_data();
+    }
+
+    Value::list(values, span).into_pipeline_data()
+}
+
+pub fn get_record_string<'a>(
+    record: &'a Record,
+    field_name: &str,
+) -> Option<&'a str> {
+    let value = record.get(field_name)?;
+    let Value::String { val, .. } = value else {
+        return None;
+    };
+    Some(val.as_str())
+}
+
+pub fn resolve_path(
+    filename: &str,
+    current_dir: Result<&Path, &ShellError>,
+    span: Span,
+) -> Result<PathBuf, LabeledError> {
+    let path = Path::new(filename);
+
+    // If path is already absolute, return it as-is
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+
+    // Path is relative, need to resolve against current working directory
+    let current_dir = current_dir.map_err(|e| {
+        LabeledError::new("Failed to get current working directory")
+            .with_label(format!("Cannot resolve relative path '{filename}'\n\nError: {e}"), span)
+    })?;
+
+    Ok(PathBuf::from(current_dir).join(filename))
+}
@@ -0,0 +1,177 @@
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use dicom::{
+    encoding::{Codec, TransferSyntax, TransferSyntaxIndex, transfer_syntax::Endianness},
+    object::{DefaultDicomObject, FileMetaTable, InMemDicomObject},
+    transfer_syntax::TransferSyntaxRegistry,
+};
+use snafu::{OptionExt, ResultExt};
+
+use crate::dataset::ObjectBuilder;
+use crate::reader::{
+    Error, IoSnafu, Layout, MetaSnafu, UnsupportedTransferSyntaxSnafu, error_chain, parser_error_position, raw_dataset_transfer_syntax,
+    with_synthesized_meta,
+};
+
+/// Describes why a DICOM object read in lenient mode is incomplete.
+#[derive(Debug, Clone)]
+pub struct PartialRead {
+    /// Byte offset in the input where parsing failed, if known.
+    pub offset: Option<u64>,
+    /// Error message of the first failure.
+    pub reason: String,
+    /// True if parsing continued after the failure.
+    pub resynchronized: bool,
+}
+
+/// Reads a DICOM file, returning all elements parsed before a failure (see `read_dcm_bytes_lenient()`).
+pub fn read_dcm_file_lenient<P: AsRef<Path>>(
+    path: P,
+    resync: bool,
+    transfer_syntax: Option<&str>,
+) -> Result<(DefaultDicomObject, Option<PartialRead>), Error> {
+    let bytes = fs::read(path).context(IoSnafu)?;
+    read_dcm_bytes_lenient(&bytes, resync, transfer_syntax)
+}
+
+/// Reads a DICOM stream, returning all elements parsed before a failure (see `read_dcm_bytes_lenient()`).
+pub fn read_dcm_stream_lenient<F: Read>(
+    mut input: F,
+    resync: bool,
+    transfer_syntax: Option<&str>,
+) -> Result<(DefaultDicomObject, Option<PartialRead>), Error> {
+    let mut bytes = Vec::new();
+    input
+        .read_to_end(&mut bytes)
+        .context(IoSnafu)?;
+
+    read_dcm_bytes_lenient(&bytes, resync, transfer_syntax)
+}
+
+/// Reads a DICOM object, returning all elements parsed before the data set failed to parse.
+///
+/// The file meta group must be valid, otherwise there's no way to tell how to parse the data set. Raw data sets
+/// without the file meta group are read using `transfer_syntax` or a guessed one (see `read_dcm_stream()`).
+/// When `resync` is set and parsing fails inside a sequence, parsing continues after the next sequence delimitation
+/// item. This recovers from bogus undefined length items which would otherwise consume the rest of the data set.
+pub fn read_dcm_bytes_lenient(
+    bytes: &[u8],
+    resync: bool,
+    transfer_syntax: Option<&str>,
+) -> Result<(DefaultDicomObject, Option<PartialRead>), Error> {
+    let (meta, dataset_start) = match Layout::detect(bytes, transfer_syntax) {
+        Layout::Preamble => read_meta(&bytes[128..], 128)?,
+        Layout::Magic => read_meta(bytes, 0)?,
+        Layout::MetaWithoutMagic => {
+            // offsets are relative to the input, not including the added "DICM" marker
+            let (meta, dataset_start) = read_meta(&[b"DICM".as_slice(), bytes].concat(), 0)?;
+            (meta, dataset_start - 4)
+        }
+        Layout::Raw => {
+            let ts = raw_dataset_transfer_syntax(bytes, transfer_syntax)?;
+            let (obj, partial) = read_dataset_lenient(bytes, 0, ts, resync)?;
+            return Ok((with_synthesized_meta(obj, ts.uid())?, partial));
+        }
+    };
+
+    let ts = TransferSyntaxRegistry
+        .get(meta.transfer_syntax())
+        .context(UnsupportedTransferSyntaxSnafu { uid: meta.transfer_syntax() })?;
+
+    let (obj, partial) = read_dataset_lenient(bytes, dataset_start, ts, resync)?;
+
+    Ok((obj.with_exact_meta(meta), partial))
+}
+
+/// Reads the file meta group from `bytes` starting with the "DICM" marker, returning it with the data set position
+/// (`offset` is the position of `bytes` in the input).
+fn read_meta(
+    bytes: &[u8],
+    offset: usize,
+) -> Result<(FileMetaTable, usize), Error> {
+    let mut cursor = Cursor::new(bytes);
+    let meta = FileMetaTable::from_reader(&mut cursor).context(MetaSnafu)?;
+
+    Ok((meta, offset + cursor.position() as usize))
+}
+
+/// Reads a data set starting at `dataset_start` in `bytes`. See `read_dcm_bytes_lenient()`.
+pub fn read_dataset_lenient(
+    bytes: &[u8],
+    dataset_start: usize,
+    ts: &TransferSyntax,
+    resync: bool,
+) -> Result<(InMemDicomObject, Option<PartialRead>), Error> {
+    let mut builder = ObjectBuilder::default();
+    let mut partial: Option<PartialRead> = None;
+    let mut position = dataset_start;
+
+    loop {
+        let dataset = &bytes[position.min(bytes.len())..];
+
+        let error = match builder.read(dataset, ts) {
+            Ok(()) => break,
+            Err(error @ Error::UnsupportedTransferSyntax { .. }) => return Err(error),
+            Err(error) => error,
+        };
+
+        // positions in adapted (e.g. deflated) data sets don't map to the input bytes
+        let is_adapted = matches!(ts.codec(), Codec::Dataset(Some(_)));
+        let failed_at = parser_error_position(&error)
+            .filter(|_| !is_adapted)
+            .map(|p| position + p as usize);
+
+        let partial = partial.get_or_insert_with(|| PartialRead {
+            offset: failed_at.map(|offset| offset as u64),
+            reason: error_chain(&error)
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(": "),
+            resynchronized: false,
+        });
+
+        if !resync || !builder.in_sequence() {
+            break;
+        }
+
+        // skip the broken sequence and carry on with the top level data set
+        let Some(next) = failed_at.and_then(|failed_at| find_sequence_delimiter(bytes, failed_at, ts.endianness())) else {
+            break;
+        };
+
+        if next <= position {
+            break;
+        }
+
+        builder.close_all();
+        partial.resynchronized = true;
+        position = next;
+    }
+
+    Ok((builder.finish(), partial))
+}
+
+/// Returns the position after the first sequence delimitation item found near or after `from`.
+fn find_sequence_delimiter(
+    bytes: &[u8],
+    from: usize,
+    endianness: Endianness,
+) -> Option<usize> {
+    let delimiter: [u8; 8] = match endianness {
+        Endianness::Little => [0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0],
+        Endianness::Big => [0xFF, 0xFE, 0xE0, 0xDD, 0, 0, 0, 0],
+    };
+
+    // the parser may report the position after the header it failed on, which may be the delimiter itself
+    let from = from.saturating_sub(delimiter.len());
+
+    bytes
+        .get(from..)?
+        .windows(delimiter.len())
+        .position(|window| window == delimiter)
+        .map(|index| from + index + delimiter.len())
+}
@@ -1,6 +1,31 @@
+mod archive;
 mod convert;
+mod dataset;
 mod dcm;
+mod dedupe;
+mod dicomdir;
 mod dicomweb;
+mod document;
+mod dose;
+mod frames;
+mod geometry;
+mod hash;
+mod hierarchy;
+mod input;
+mod lenient;
 mod meta;
+mod multiframe;
+mod nifti;
+mod organize;
+mod overlays;
+mod parallel;
 pub mod plugin;
 mod reader;
+mod rtdose;
+mod rtplan;
+mod rtstruct;
+mod sr;
+mod transcode;
+mod uid;
+mod waveform;
+mod writer;
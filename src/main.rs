@@ -1,11 +1,36 @@
 use nu_plugin::MsgPackSerializer;
 
+mod archive;
 mod convert;
+mod dataset;
 mod dcm;
+mod dedupe;
+mod dicomdir;
 mod dicomweb;
+mod document;
+mod dose;
+mod frames;
+mod geometry;
+mod hash;
+mod hierarchy;
+mod input;
+mod lenient;
 mod meta;
+mod multiframe;
+mod nifti;
+mod organize;
+mod overlays;
+mod parallel;
 mod plugin;
 mod reader;
+mod rtdose;
+mod rtplan;
+mod rtstruct;
+mod sr;
+mod transcode;
+mod uid;
+mod waveform;
+mod writer;
 
 fn main() {
     let plugin = plugin::DcmPlugin::default();
@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use dicom::core::header::Header;
+use dicom::core::value::PixelFragmentSequence;
+use dicom::core::{DataElement, DicomValue, PrimitiveValue, Tag, VR};
+use dicom::dictionary_std::{tags, uids};
+use dicom::object::mem::InMemElement;
+use dicom::object::{DefaultDicomObject, InMemDicomObject};
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Signature, Value};
+use snafu::{OptionExt, Snafu, ensure};
+
+use crate::convert::get_string;
+use crate::input::{DicomSource, collect_input};
+use crate::plugin::DcmPlugin;
+use crate::reader::read_dcm_stream_with_pixel_data;
+use crate::uid::generate_uid;
+use crate::writer::write_dcm;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("PerFrameFunctionalGroupsSequence has no item for frame {}", frame))]
+    MissingFrame { frame: usize },
+
+    #[snafu(display("Missing PixelData"))]
+    MissingPixelData,
+
+    #[snafu(display("Unsupported pixel data: {}", reason))]
+    UnsupportedPixelData { reason: String },
+}
+
+/// Elements describing the object as a whole rather than a frame, left out of split single-frame instances.
+const MULTI_FRAME_TAGS: [Tag; 7] = [
+    tags::NUMBER_OF_FRAMES,
+    tags::FRAME_INCREMENT_POINTER,
+    tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE,
+    tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE,
+    tags::DIMENSION_ORGANIZATION_SEQUENCE,
+    tags::DIMENSION_INDEX_SEQUENCE,
+    tags::DIMENSION_ORGANIZATION_TYPE,
+];
+
+/// Frame attributes which have a top-level counterpart in single-frame instances.
+const LEGACY_TAGS: [(Tag, Tag); 2] = [(tags::FRAME_TYPE, tags::IMAGE_TYPE), (tags::FRAME_ACQUISITION_DATE_TIME, tags::ACQUISITION_DATE_TIME)];
+
+/// SOP classes of single-frame instances split from enhanced multi-frame ones. Other SOP classes are kept.
+const LEGACY_SOP_CLASSES: [(&str, &str); 6] = [
+    (uids::ENHANCED_CT_IMAGE_STORAGE, uids::CT_IMAGE_STORAGE),
+    (uids::LEGACY_CONVERTED_ENHANCED_CT_IMAGE_STORAGE, uids::CT_IMAGE_STORAGE),
+    (uids::ENHANCED_MR_IMAGE_STORAGE, uids::MR_IMAGE_STORAGE),
+    (uids::LEGACY_CONVERTED_ENHANCED_MR_IMAGE_STORAGE, uids::MR_IMAGE_STORAGE),
+    (uids::ENHANCED_PET_IMAGE_STORAGE, uids::POSITRON_EMISSION_TOMOGRAPHY_IMAGE_STORAGE),
+    (uids::LEGACY_CONVERTED_ENHANCED_PET_IMAGE_STORAGE, uids::POSITRON_EMISSION_TOMOGRAPHY_IMAGE_STORAGE),
+];
+
+/// `dcm split-frames` command, converts multi-frame objects into single-frame instances.
+pub struct DcmSplitFramesCommand;
+
+impl PluginCommand for DcmSplitFramesCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm split-frames"
+    }
+
+    fn description(&self) -> &str {
+        "Split enhanced multi-frame DICOM objects into single-frame instances."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec![
+                "dicom".to_string(),
+                "multi-frame".to_string(),
+                "enhanced".to_string(),
+                "legacy".to_string(),
+                "functional groups".to_string(),
+            ])
+            .extra_description(
+                "Each frame becomes an instance with a new SOPInstanceUID and InstanceNumber set to the frame number. The attributes of the \
+                 SharedFunctionalGroupsSequence and PerFrameFunctionalGroupsSequence (e.g. ImagePositionPatient, PixelSpacing, SliceThickness, \
+                 WindowCenter) are moved to the top level, FrameType becomes ImageType. Enhanced CT, MR and PET SOP classes are replaced by their \
+                 single-frame counterparts, other SOP classes are kept. Pixel data must be native or have one fragment per frame, it is not \
+                 decoded. Returns a list of binary data with the frames of all inputs.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Split a multi-frame file into files named by frame number",
+            example: "\"enhanced.dcm\" | dcm split-frames | enumerate | each {|f| $f.item | save $\"frame-($f.index + 1).dcm\" }",
+            result: None,
+        }]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let bytes = source
+                .bytes()
+                .map_err(|e| source.error(&e, span))?;
+            let obj = read_dcm_stream_with_pixel_data(Cursor::new(&bytes[..]), None).map_err(|e| source.error(&e, span))?;
+
+            for frame in split_frames(&obj).map_err(|e| source.labeled_error("Failed to split frames", &e, span))? {
+                let (bytes, _) = write_dcm(&frame).map_err(|e| source.labeled_error("Failed to encode", &e, span))?;
+                output.push(Value::binary(bytes, span));
+            }
+        }
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+/// Number of frames of the object, 1 if NumberOfFrames is missing.
+pub fn number_of_frames(obj: &InMemDicomObject) -> usize {
+    obj.get(tags::NUMBER_OF_FRAMES)
+        .and_then(|e| {
+            e.to_int::<usize>()
+                .ok()
+        })
+        .unwrap_or(1)
+}
+
+/// Returns the functional group attributes of a frame (0-based), i.e. the attributes of the shared functional groups
+/// overridden by the per-frame ones.
+///
+/// Each functional group is a sequence with a single item (e.g. PlanePositionSequence), the elements of that item are
+/// returned. Objects without functional groups have no frame attributes.
+pub fn frame_attributes(
+    obj: &InMemDicomObject,
+    frame: usize,
+) -> Result<BTreeMap<Tag, InMemElement>, Error> {
+    let mut attributes = BTreeMap::new();
+
+    if let Some(shared) = sequence_items(obj, tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE) {
+        for item in shared {
+            add_functional_groups(&mut attributes, item);
+        }
+    }
+
+    if let Some(per_frame) = sequence_items(obj, tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE) {
+        let item = per_frame
+            .get(frame)
+            .context(MissingFrameSnafu { frame: frame + 1 })?;
+        add_functional_groups(&mut attributes, item);
+    }
+
+    Ok(attributes)
+}
+
+fn add_functional_groups(
+    attributes: &mut BTreeMap<Tag, InMemElement>,
+    item: &InMemDicomObject,
+) {
+    for group in item {
+        match group.value() {
+            DicomValue::Sequence(sequence) => {
+                for element in sequence
+                    .items()
+                    .iter()
+                    .flatten()
+                {
+                    attributes.insert(element.tag(), element.clone());
+                }
+            }
+            // not a functional group macro, but keep it anyway
+            _ => {
+                attributes.insert(group.tag(), group.clone());
+            }
+        }
+    }
+}
+
+fn sequence_items(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> Option<&[InMemDicomObject]> {
+    obj.get(tag)?
+        .items()
+}
+
+/// Returns the PixelData element of each frame, without decoding encapsulated pixel data.
+pub fn frame_pixel_data(
+    obj: &InMemDicomObject,
+    frames: usize,
+) -> Result<Vec<InMemElement>, Error> {
+    let pixel_data = obj
+        .get(tags::PIXEL_DATA)
+        .context(MissingPixelDataSnafu)?;
+    let vr = pixel_data.vr();
+
+    let values = match pixel_data.value() {
+        DicomValue::Primitive(value) => split_native_pixel_data(obj, value, frames)?,
+        DicomValue::PixelSequence(sequence) => {
+            let fragments = sequence.fragments();
+            ensure!(
+                fragments.len() == frames,
+                UnsupportedPixelDataSnafu { reason: format!("{} fragments for {} frames, expected one fragment per frame", fragments.len(), frames) }
+            );
+
+            fragments
+                .iter()
+                .map(|fragment| DicomValue::PixelSequence(PixelFragmentSequence::new_fragments(vec![fragment.clone()])))
+                .collect()
+        }
+        DicomValue::Sequence(_) => return UnsupportedPixelDataSnafu { reason: "PixelData is a sequence" }.fail(),
+    };
+
+    Ok(values
+        .into_iter()
+        .map(|value| DataElement::new(tags::PIXEL_DATA, vr, value))
+        .collect())
+}
+
+fn split_native_pixel_data(
+    obj: &InMemDicomObject,
+    value: &PrimitiveValue,
+    frames: usize,
+) -> Result<Vec<DicomValue<InMemDicomObject, Vec<u8>>>, Error> {
+    let get_int = |tag| {
+        obj.get(tag)
+            .and_then(|e| {
+                e.to_int::<usize>()
+                    .ok()
+            })
+    };
+
+    let (Some(rows), Some(columns), Some(bits_allocated)) = (get_int(tags::ROWS), get_int(tags::COLUMNS), get_int(tags::BITS_ALLOCATED)) else {
+        return UnsupportedPixelDataSnafu { reason: "missing Rows, Columns or BitsAllocated" }.fail();
+    };
+    let samples_per_pixel = get_int(tags::SAMPLES_PER_PIXEL).unwrap_or(1);
+
+    // frames of 1-bit pixel data (e.g. segmentations) don't start at byte boundaries
+    ensure!(bits_allocated % 8 == 0, UnsupportedPixelDataSnafu { reason: format!("BitsAllocated {bits_allocated} is not a multiple of 8") });
+    let frame_len = rows * columns * samples_per_pixel * bits_allocated / 8;
+
+    fn split<T: Clone>(
+        values: &[T],
+        frame_len: usize,
+        frames: usize,
+    ) -> Result<Vec<Vec<T>>, Error> {
+        ensure!(
+            values.len() >= frame_len * frames,
+            UnsupportedPixelDataSnafu { reason: format!("{} values are too short for {} frames of {} values", values.len(), frames, frame_len) }
+        );
+
+        Ok(values
+            .chunks(frame_len.max(1))
+            .take(frames)
+            .map(<[T]>::to_vec)
+            .collect())
+    }
+
+    // native pixel data is read as bytes (OB) or words (OW)
+    let values = match value {
+        PrimitiveValue::U8(values) => split(values, frame_len, frames)?
+            .into_iter()
+            .map(|values| DicomValue::Primitive(PrimitiveValue::U8(values.into())))
+            .collect(),
+        PrimitiveValue::U16(values) => split(values, frame_len / 2, frames)?
+            .into_iter()
+            .map(|values| DicomValue::Primitive(PrimitiveValue::U16(values.into())))
+            .collect(),
+        _ => return UnsupportedPixelDataSnafu { reason: "PixelData is neither OB nor OW" }.fail(),
+    };
+
+    Ok(values)
+}
+
+/// Splits a multi-frame object into single-frame instances, see `DcmSplitFramesCommand`.
+pub fn split_frames(obj: &DefaultDicomObject) -> Result<Vec<DefaultDicomObject>, Error> {
+    let frames = number_of_frames(obj);
+    let pixel_data = frame_pixel_data(obj, frames)?;
+
+    let sop_class_uid = get_string(obj, tags::SOP_CLASS_UID).unwrap_or_default();
+    let sop_class_uid = LEGACY_SOP_CLASSES
+        .iter()
+        .find(|(enhanced, _)| *enhanced == sop_class_uid)
+        .map_or(sop_class_uid.as_str(), |(_, legacy)| legacy)
+        .to_string();
+
+    let mut instances = Vec::with_capacity(frames);
+
+    for (frame, pixel_data) in pixel_data
+        .into_iter()
+        .enumerate()
+    {
+        let mut instance = obj.clone();
+        for tag in MULTI_FRAME_TAGS {
+            instance.remove_element(tag);
+        }
+
+        for (tag, mut element) in frame_attributes(obj, frame)? {
+            if let Some((_, legacy_tag)) = LEGACY_TAGS
+                .iter()
+                .find(|(frame_tag, _)| *frame_tag == tag)
+            {
+                element = DataElement::new(*legacy_tag, element.vr(), element.into_value());
+            }
+            instance.put(element);
+        }
+
+        let sop_instance_uid = generate_uid();
+        instance.put(DataElement::new(tags::SOP_CLASS_UID, VR::UI, PrimitiveValue::from(sop_class_uid.as_str())));
+        instance.put(DataElement::new(tags::SOP_INSTANCE_UID, VR::UI, PrimitiveValue::from(sop_instance_uid.as_str())));
+        instance.put(DataElement::new(tags::INSTANCE_NUMBER, VR::IS, PrimitiveValue::from((frame + 1).to_string())));
+        instance.put(pixel_data);
+
+        instance.update_meta(|meta| {
+            meta.media_storage_sop_class_uid = sop_class_uid.clone();
+            meta.media_storage_sop_instance_uid = sop_instance_uid;
+        });
+
+        instances.push(instance);
+    }
+
+    Ok(instances)
+}
@@ -0,0 +1,405 @@
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+
+use dicom::dictionary_std::tags;
+use dicom::object::{DefaultDicomObject, InMemDicomObject};
+use dicom_pixeldata::PixelDecoder;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use indexmap::IndexMap;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Value};
+
+use crate::convert::get_string;
+use crate::dcm::DicomDump;
+use crate::geometry::{ImageGeometry, SliceOrder, volume_affine};
+use crate::input::{DicomSource, collect_input};
+use crate::multiframe::{frame_attributes, number_of_frames};
+use crate::plugin::DcmPlugin;
+use crate::reader::read_dcm_stream_with_pixel_data;
+
+/// Size of the NIfTI-1 header.
+const HEADER_SIZE: usize = 348;
+
+/// Offset of the voxel data, after the header and an empty extension flag.
+const VOX_OFFSET: usize = HEADER_SIZE + 4;
+
+/// NIfTI-1 datatype code of 32-bit floats.
+const DT_FLOAT32: i16 = 16;
+
+/// NIfTI-1 code of millimetres in `xyzt_units`.
+const NIFTI_UNITS_MM: u8 = 2;
+
+/// NIfTI-1 code of scanner-based anatomical coordinates in `qform_code` and `sform_code`.
+const NIFTI_XFORM_SCANNER_ANAT: i16 = 1;
+
+/// `dcm to-nifti` command, stacks the images of a series into a NIfTI-1 volume.
+pub struct DcmToNiftiCommand;
+
+impl PluginCommand for DcmToNiftiCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm to-nifti"
+    }
+
+    fn description(&self) -> &str {
+        "Convert the images of a series to a NIfTI-1 volume."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .switch("gzip", "Compress the NIfTI file with gzip, i.e. a .nii.gz file.", Some('z'))
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "nifti".to_string(), "nii".to_string(), "volume".to_string(), "convert".to_string()])
+            .extra_description(
+                "All images (or frames of multi-frame objects) must belong to the same series, have the same orientation and size, and be \
+                 at distinct positions. They are sorted along the slice normal, decoded and rescaled with RescaleSlope and \
+                 RescaleIntercept into 32-bit floats. The sform and qform of the NIfTI header map voxels to RAS patient coordinates in mm. \
+                 Returns a record with the `nifti` file as binary data and a `sidecar` record with the attributes of the first slice, to be \
+                 saved as JSON.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![Example {
+            description: "Convert a series to a compressed NIfTI file with a JSON sidecar",
+            example: "ls series/*.dcm | get name | dcm to-nifti --gzip | do {|v| $v.nifti | save volume.nii.gz; $v.sidecar | to json | save volume.json }",
+            result: None,
+        }]
+    }
+
+    fn run(
+        &self,
+        plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let gzip = call.has_flag("gzip")?;
+
+        let mut objects = Vec::new();
+        let mut slices = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let bytes = source
+                .bytes()
+                .map_err(|e| source.error(&e, span))?;
+            let obj = read_dcm_stream_with_pixel_data(Cursor::new(&bytes[..]), None).map_err(|e| source.error(&e, span))?;
+
+            let decoded = obj
+                .decode_pixel_data()
+                .map_err(|e| source.labeled_error("Failed to decode pixel data", &e, span))?;
+            if decoded.samples_per_pixel() != 1 {
+                return Err(LabeledError::new("Unsupported pixel data")
+                    .with_label(format!("{} samples per pixel, only single-sample images are supported", decoded.samples_per_pixel()), span));
+            }
+
+            for frame in 0..number_of_frames(&obj) {
+                let attributes = frame_attributes(&obj, frame).map_err(|e| source.labeled_error("Invalid multi-frame object", &e, span))?;
+                let pixels = decoded
+                    .to_vec_frame::<f32>(frame as u32)
+                    .map_err(|e| source.labeled_error("Failed to decode pixel data", &e, span))?;
+
+                slices.push(Slice {
+                    object: objects.len(),
+                    geometry: ImageGeometry::from_attributes(&obj, &attributes),
+                    attributes: InMemDicomObject::from_element_iter(attributes.into_values()),
+                    size: (decoded.columns(), decoded.rows()),
+                    pixels,
+                    span,
+                });
+            }
+
+            objects.push(obj);
+        }
+
+        if slices.is_empty() {
+            return Err(LabeledError::new("No images").with_label("Expected DICOM images of a series", call.head));
+        }
+
+        check_series(&objects, call.head)?;
+        let order = sort_slices(&slices, call.head)?;
+
+        let nifti = write_nifti(&slices, &order);
+        let nifti = if gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(&nifti)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| LabeledError::new("Failed to compress").with_label(e.to_string(), call.head))?
+        } else {
+            nifti
+        };
+
+        let first = &slices[order[0]];
+        let sidecar = make_sidecar(&DicomDump { dcm_dictionary: &plugin.dcm_dictionary }, &objects[first.object], &first.attributes, call.head);
+
+        let record = Record::from_iter([("nifti".to_string(), Value::binary(nifti, call.head)), ("sidecar".to_string(), sidecar)]);
+
+        Ok(Value::record(record, call.head).into_pipeline_data())
+    }
+}
+
+/// A single image, i.e. a single-frame object or a frame of a multi-frame object.
+struct Slice {
+    /// Index of the object in the input.
+    object: usize,
+    geometry: Option<ImageGeometry>,
+    /// Functional group attributes of the frame.
+    attributes: InMemDicomObject,
+    /// Columns and rows.
+    size: (u32, u32),
+    /// Rescaled pixel values, row by row.
+    pixels: Vec<f32>,
+    span: Span,
+}
+
+fn check_series(
+    objects: &[DefaultDicomObject],
+    span: Span,
+) -> Result<(), LabeledError> {
+    let mut series: Vec<String> = objects
+        .iter()
+        .map(|obj| get_string(obj, tags::SERIES_INSTANCE_UID).unwrap_or_default())
+        .collect();
+    series.sort();
+    series.dedup();
+
+    if series.len() > 1 {
+        return Err(LabeledError::new("Multiple series").with_label(format!("Images belong to series {}", series.join(", ")), span));
+    }
+
+    Ok(())
+}
+
+/// Sorts the slices along their normal, checking that they form a volume.
+fn sort_slices(
+    slices: &[Slice],
+    span: Span,
+) -> Result<Vec<usize>, LabeledError> {
+    if let Some(slice) = slices
+        .iter()
+        .find(|slice| {
+            slice
+                .geometry
+                .is_none()
+        })
+    {
+        return Err(LabeledError::new("Missing geometry")
+            .with_label("Image without ImagePositionPatient, ImageOrientationPatient or PixelSpacing", slice.span));
+    }
+
+    let geometries: Vec<Option<ImageGeometry>> = slices
+        .iter()
+        .map(|slice| {
+            slice
+                .geometry
+                .clone()
+        })
+        .collect();
+    let slice_order =
+        SliceOrder::new(&geometries).ok_or_else(|| LabeledError::new("No images").with_label("Expected DICOM images of a series", span))?;
+
+    if let Some(slice) = slices
+        .iter()
+        .find(|slice| {
+            !slice
+                .geometry
+                .as_ref()
+                .is_some_and(|geometry| geometry.has_normal(slice_order.normal))
+        })
+    {
+        return Err(LabeledError::new("Inconsistent orientation").with_label("Image with a different orientation than the first one", slice.span));
+    }
+
+    if slice_order.has_duplicates() {
+        return Err(
+            LabeledError::new("Duplicate positions").with_label("Several images at the same position, e.g. multiple echoes or time points", span)
+        );
+    }
+
+    let size = slices[0].size;
+    if let Some(slice) = slices
+        .iter()
+        .find(|slice| slice.size != size)
+    {
+        return Err(LabeledError::new("Inconsistent image size").with_label(
+            format!(
+                "Image of {}x{} pixels, expected {}x{}",
+                slice
+                    .size
+                    .0,
+                slice
+                    .size
+                    .1,
+                size.0,
+                size.1
+            ),
+            slice.span,
+        ));
+    }
+
+    Ok(slice_order.order)
+}
+
+/// Encodes the slices in the given order as a single-file NIfTI-1 volume of 32-bit floats.
+fn write_nifti(
+    slices: &[Slice],
+    order: &[usize],
+) -> Vec<u8> {
+    let first = &slices[order[0]];
+    let last = &slices[order[order.len() - 1]];
+    let (columns, rows) = first.size;
+
+    // presence of the geometry was checked when sorting
+    let geometry = first
+        .geometry
+        .as_ref()
+        .expect("slice geometry");
+    let mut affine = volume_affine(
+        geometry,
+        last.geometry
+            .as_ref()
+            .expect("slice geometry"),
+        order.len(),
+    );
+
+    // DICOM patient coordinates are LPS, NIfTI ones RAS
+    for row in &mut affine[..2] {
+        for value in row.iter_mut() {
+            *value = -*value;
+        }
+    }
+
+    let voxel_size: Vec<f64> = (0..3)
+        .map(|axis| {
+            (0..3)
+                .map(|row| affine[row][axis] * affine[row][axis])
+                .sum::<f64>()
+                .sqrt()
+        })
+        .collect();
+    let normal = geometry.normal();
+    let rotation = [
+        [-geometry.row_direction[0], -geometry.column_direction[0], -normal[0]],
+        [-geometry.row_direction[1], -geometry.column_direction[1], -normal[1]],
+        [geometry.row_direction[2], geometry.column_direction[2], normal[2]],
+    ];
+    let [quatern_b, quatern_c, quatern_d] = quaternion(&rotation);
+
+    let mut header = vec![0u8; VOX_OFFSET];
+    let mut put = |offset: usize, bytes: &[u8]| header[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+    put(0, &(HEADER_SIZE as i32).to_le_bytes());
+    put(38, b"r");
+    for (index, dim) in [3, columns as i16, rows as i16, order.len() as i16, 1, 1, 1, 1]
+        .into_iter()
+        .enumerate()
+    {
+        put(40 + index * 2, &dim.to_le_bytes());
+    }
+    put(70, &DT_FLOAT32.to_le_bytes());
+    put(72, &32i16.to_le_bytes());
+    // qfac is 1 as the rotation is proper, the normal being the cross product of the row and column directions
+    for (index, pixdim) in [1.0, voxel_size[0], voxel_size[1], voxel_size[2]]
+        .into_iter()
+        .enumerate()
+    {
+        put(76 + index * 4, &(pixdim as f32).to_le_bytes());
+    }
+    put(108, &(VOX_OFFSET as f32).to_le_bytes());
+    put(112, &1f32.to_le_bytes());
+    put(123, &[NIFTI_UNITS_MM]);
+    put(148, format!("nu_plugin_dcm {}", env!("CARGO_PKG_VERSION")).as_bytes());
+    put(252, &NIFTI_XFORM_SCANNER_ANAT.to_le_bytes());
+    put(254, &NIFTI_XFORM_SCANNER_ANAT.to_le_bytes());
+    for (index, value) in [quatern_b, quatern_c, quatern_d, affine[0][3], affine[1][3], affine[2][3]]
+        .into_iter()
+        .enumerate()
+    {
+        put(256 + index * 4, &(value as f32).to_le_bytes());
+    }
+    for (row, values) in affine[..3]
+        .iter()
+        .enumerate()
+    {
+        for (column, value) in values
+            .iter()
+            .enumerate()
+        {
+            put(280 + row * 16 + column * 4, &(*value as f32).to_le_bytes());
+        }
+    }
+    put(344, b"n+1\0");
+
+    let mut nifti = header;
+    nifti.reserve(order.len() * columns as usize * rows as usize * 4);
+    for index in order {
+        for value in &slices[*index].pixels {
+            nifti.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    nifti
+}
+
+/// Quaternion parameters (b, c, d) of a proper rotation matrix, as in `nifti_mat44_to_quatern` of the NIfTI reference
+/// library.
+fn quaternion(r: &[[f64; 3]; 3]) -> [f64; 3] {
+    let a = r[0][0] + r[1][1] + r[2][2] + 1.0;
+    let (a, b, c, d) = if a > 0.5 {
+        let a = 0.5 * a.sqrt();
+        (a, 0.25 * (r[2][1] - r[1][2]) / a, 0.25 * (r[0][2] - r[2][0]) / a, 0.25 * (r[1][0] - r[0][1]) / a)
+    } else {
+        let xd = 1.0 + r[0][0] - (r[1][1] + r[2][2]);
+        let yd = 1.0 + r[1][1] - (r[0][0] + r[2][2]);
+        let zd = 1.0 + r[2][2] - (r[0][0] + r[1][1]);
+        if xd > 1.0 {
+            let b = 0.5 * xd.sqrt();
+            (0.25 * (r[2][1] - r[1][2]) / b, b, 0.25 * (r[0][1] + r[1][0]) / b, 0.25 * (r[0][2] + r[2][0]) / b)
+        } else if yd > 1.0 {
+            let c = 0.5 * yd.sqrt();
+            (0.25 * (r[0][2] - r[2][0]) / c, 0.25 * (r[0][1] + r[1][0]) / c, c, 0.25 * (r[1][2] + r[2][1]) / c)
+        } else {
+            let d = 0.5 * zd.sqrt();
+            (0.25 * (r[1][0] - r[0][1]) / d, 0.25 * (r[0][2] + r[2][0]) / d, 0.25 * (r[1][2] + r[2][1]) / d, d)
+        }
+    };
+
+    if a < 0.0 {
+        [-b, -c, -d]
+    } else {
+        [b, c, d]
+    }
+}
+
+/// Attributes of the first slice, without pixel data and functional group sequences, which are flattened instead.
+fn make_sidecar(
+    dumper: &DicomDump,
+    obj: &DefaultDicomObject,
+    attributes: &InMemDicomObject,
+    span: Span,
+) -> Value {
+    let mut top_level = obj
+        .clone()
+        .into_inner();
+    for tag in [tags::PIXEL_DATA, tags::SHARED_FUNCTIONAL_GROUPS_SEQUENCE, tags::PER_FRAME_FUNCTIONAL_GROUPS_SEQUENCE] {
+        top_level.remove_element(tag);
+    }
+
+    let mut index_map = IndexMap::new();
+    dumper.make_row_from_dicom_object(&span, &mut index_map, &top_level);
+    dumper.make_row_from_dicom_object(&span, &mut index_map, attributes);
+    index_map.insert("ConversionSoftware".to_string(), Value::string("nu_plugin_dcm", span));
+    index_map.insert("ConversionSoftwareVersion".to_string(), Value::string(env!("CARGO_PKG_VERSION"), span));
+
+    Value::record(Record::from_iter(index_map), span)
+}
@@ -0,0 +1,473 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use dicom::core::{DataDictionary, Tag};
+use dicom::dictionary_std::tags;
+use dicom::object::DefaultDicomObject;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Spanned, SyntaxShape, Value};
+
+use crate::convert::get_string;
+use crate::input::{DicomSource, collect_input, resolve_path};
+use crate::plugin::DcmPlugin;
+use crate::reader::ErrorKind;
+
+/// Substituted for missing or empty attributes, so that every path component is non-empty.
+const UNKNOWN: &str = "UNKNOWN";
+
+/// Maximum length of a substituted attribute value, to keep paths within filesystem limits.
+const MAX_VALUE_LEN: usize = 64;
+
+/// `dcm organize` command, copies or moves files into a layout built from their attributes.
+pub struct DcmOrganizeCommand;
+
+impl PluginCommand for DcmOrganizeCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm organize"
+    }
+
+    fn description(&self) -> &str {
+        "Copy or move DICOM files into a folder layout built from their attributes."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required(
+                "template",
+                SyntaxShape::String,
+                "Destination path template, e.g. `out/{PatientID}/{StudyDate}_{StudyInstanceUID}/{SeriesNumber}/{InstanceNumber}.dcm`.",
+            )
+            .switch("move", "Move files instead of copying them.", Some('m'))
+            .switch("dry-run", "Only report what would be done, don't touch any files.", Some('n'))
+            .named(
+                "on-collision",
+                SyntaxShape::String,
+                "What to do when the destination already exists: `rename` (default, appends a number), `skip`, `overwrite` (only files that \
+                 existed before, destinations written earlier are renamed) or `error`.",
+                None,
+            )
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "sort".to_string(), "rename".to_string(), "move".to_string(), "copy".to_string()])
+            .extra_description(
+                "Placeholders in the template are attribute keywords or tags in braces, e.g. `{PatientID}` or `{(0010,0020)}`. Substituted values \
+                 are made filesystem-safe, missing values become `UNKNOWN`. Files are read up to PixelData. Files with an already seen SOPInstanceUID \
+                 and non-DICOM files are skipped. Files that can't be read or transferred, and collisions with `--on-collision error`, are \
+                 reported with the `error` action instead of stopping. Returns a table with the `source`, `destination`, `action` (copy, move, \
+                 skip or error) and the `reason` of skipped files and errors.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show where files would be copied to",
+                example: "ls **/* | where type == file | dcm organize --dry-run 'sorted/{PatientID}/{StudyDate}_{StudyInstanceUID}/{SeriesNumber}_{SeriesDescription}/{InstanceNumber}.dcm'",
+                result: None,
+            },
+            Example {
+                description: "Move files into a folder per series",
+                example: "ls incoming/* | dcm organize --move 'archive/{SeriesInstanceUID}/{SOPInstanceUID}.dcm'",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let template: Spanned<String> = call.req(0)?;
+        let template = Template::parse(&template.item, &plugin.dcm_dictionary)
+            .map_err(|e| LabeledError::new("Invalid template").with_label(e, template.span))?;
+
+        let mut organizer = Organizer {
+            template,
+            current_dir: current_dir.clone(),
+            mode: if call.has_flag("move")? {
+                Mode::Move
+            } else {
+                Mode::Copy
+            },
+            dry_run: call.has_flag("dry-run")?,
+            on_collision: get_on_collision_flag(call)?,
+            seen_instances: HashMap::new(),
+            destinations: HashSet::new(),
+        };
+
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let Some(path) = source.path() else {
+                return Err(LabeledError::new("`dcm organize` expects files").with_label("Binary data can't be copied or moved", span));
+            };
+
+            output.push(organizer.organize(&source, path, span)?);
+        }
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Copy,
+    Move,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnCollision {
+    Rename,
+    Skip,
+    Overwrite,
+    Error,
+}
+
+fn get_on_collision_flag(call: &EvaluatedCall) -> Result<OnCollision, LabeledError> {
+    let Some(value) = call.get_flag_value("on-collision") else {
+        return Ok(OnCollision::Rename);
+    };
+
+    match value.as_str()? {
+        "rename" => Ok(OnCollision::Rename),
+        "skip" => Ok(OnCollision::Skip),
+        "overwrite" => Ok(OnCollision::Overwrite),
+        "error" => Ok(OnCollision::Error),
+        _ => {
+            Err(LabeledError::new("Invalid --on-collision value")
+                .with_label("Expected one of `rename`, `skip`, `overwrite` or `error`", value.span()))
+        }
+    }
+}
+
+/// Part of a destination path template.
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Attribute(Tag),
+}
+
+#[derive(Debug, PartialEq)]
+struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Parses a template with `{Keyword}` (or `{(gggg,eeee)}`) placeholders.
+    fn parse(
+        template: &str,
+        dictionary: &impl DataDictionary,
+    ) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if rest[..start].contains('}') {
+                return Err("Unexpected `}`".to_string());
+            }
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed placeholder `{}`", &rest[start..]))?;
+            let name = &rest[start + 1..start + end];
+
+            let tag = dictionary
+                .parse_tag(name)
+                .ok_or_else(|| format!("Unknown attribute `{name}`"))?;
+            segments.push(Segment::Attribute(tag));
+
+            rest = &rest[start + end + 1..];
+        }
+
+        if rest.contains('}') {
+            return Err("Unexpected `}`".to_string());
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        Ok(Self { segments })
+    }
+
+    fn render(
+        &self,
+        obj: &DefaultDicomObject,
+    ) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal.clone(),
+                Segment::Attribute(tag) => {
+                    let value = obj
+                        .get(*tag)
+                        .and_then(|e| {
+                            e.to_str()
+                                .ok()
+                        })
+                        .unwrap_or_default();
+
+                    sanitize(&value)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Makes an attribute value safe to use as (a part of) a file name on any common filesystem.
+fn sanitize(value: &str) -> String {
+    let sanitized: String = value
+        .trim_matches([' ', '\0'])
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_VALUE_LEN)
+        .collect();
+
+    // Windows doesn't like trailing dots and spaces, and "." or ".." would change the layout
+    let sanitized = sanitized.trim_end_matches(['.', ' ']);
+    if sanitized.is_empty() {
+        UNKNOWN.to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+struct Organizer {
+    template: Template,
+    current_dir: Result<PathBuf, nu_protocol::ShellError>,
+    mode: Mode,
+    dry_run: bool,
+    on_collision: OnCollision,
+    /// Source file of each SOPInstanceUID seen so far.
+    seen_instances: HashMap<String, PathBuf>,
+    /// Destinations of files organized so far, they collide even in a dry run.
+    destinations: HashSet<PathBuf>,
+}
+
+impl Organizer {
+    /// Copies or moves a single file and returns its output row.
+    fn organize(
+        &mut self,
+        source: &DicomSource,
+        path: &Path,
+        span: Span,
+    ) -> Result<Value, LabeledError> {
+        // unreadable files are reported rather than aborting, which would leave the files moved so far unreported
+        let obj = match source.read() {
+            Ok(obj) => obj,
+            Err(e) if e.kind() == ErrorKind::NotDicom => return Ok(row(path, None, "skip", Some(&e.to_string()), span)),
+            Err(e) => return Ok(row(path, None, "error", Some(&e.to_string()), span)),
+        };
+
+        let sop_instance_uid = get_string(&obj, tags::SOP_INSTANCE_UID).unwrap_or_default();
+
+        if let Some(original) = self
+            .seen_instances
+            .get(&sop_instance_uid)
+        {
+            let reason = format!("Duplicate of {}", original.to_string_lossy());
+            return Ok(row(path, None, "skip", Some(&reason), span));
+        }
+
+        let destination = resolve_path(
+            &self
+                .template
+                .render(&obj),
+            self.current_dir
+                .as_deref(),
+            span,
+        )?;
+
+        if destination == path {
+            return Ok(row(path, Some(&destination), "skip", Some("Already in place"), span));
+        }
+
+        let destination = match self.resolve_collision(destination) {
+            Ok(destination) => destination,
+            Err(destination) => {
+                let action = match self.on_collision {
+                    OnCollision::Skip => "skip",
+                    _ => "error",
+                };
+                return Ok(row(path, Some(&destination), action, Some("Destination exists"), span));
+            }
+        };
+
+        if !self.dry_run
+            && let Err(e) = transfer(path, &destination, self.mode)
+        {
+            return Ok(row(path, Some(&destination), "error", Some(&e.to_string()), span));
+        }
+
+        if !sop_instance_uid.is_empty() {
+            self.seen_instances
+                .insert(sop_instance_uid, path.to_path_buf());
+        }
+        self.destinations
+            .insert(destination.clone());
+
+        let action = match self.mode {
+            Mode::Copy => "copy",
+            Mode::Move => "move",
+        };
+
+        Ok(row(path, Some(&destination), action, None, span))
+    }
+
+    /// Returns the destination to use, or `Err` with the colliding destination if it can't be used.
+    ///
+    /// Only files that existed before the run are overwritten, a destination written earlier in the run is renamed
+    /// instead, as overwriting it would lose the file organized first.
+    fn resolve_collision(
+        &self,
+        destination: PathBuf,
+    ) -> Result<PathBuf, PathBuf> {
+        let is_taken = |destination: &Path| {
+            self.destinations
+                .contains(destination)
+                || destination.exists()
+        };
+
+        if !is_taken(&destination) {
+            return Ok(destination);
+        }
+
+        match self.on_collision {
+            OnCollision::Overwrite
+                if !self
+                    .destinations
+                    .contains(&destination) =>
+            {
+                Ok(destination)
+            }
+            OnCollision::Skip | OnCollision::Error => Err(destination),
+            OnCollision::Rename | OnCollision::Overwrite => {
+                let stem = destination
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                let extension = destination
+                    .extension()
+                    .map(|extension| format!(".{}", extension.to_string_lossy()))
+                    .unwrap_or_default();
+
+                let renamed = (1..)
+                    .map(|n| destination.with_file_name(format!("{stem}_{n}{extension}")))
+                    .find(|renamed| !is_taken(renamed))
+                    .expect("some file name must be free");
+
+                Ok(renamed)
+            }
+        }
+    }
+}
+
+fn transfer(
+    source: &Path,
+    destination: &Path,
+    mode: Mode,
+) -> io::Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match mode {
+        Mode::Copy => fs::copy(source, destination).map(|_| ()),
+        // renaming fails across filesystems, fall back to copy and delete
+        Mode::Move => fs::rename(source, destination).or_else(|_| {
+            fs::copy(source, destination)?;
+            fs::remove_file(source)
+        }),
+    }
+}
+
+fn row(
+    source: &Path,
+    destination: Option<&Path>,
+    action: &str,
+    reason: Option<&str>,
+    span: Span,
+) -> Value {
+    let optional_string = |s: Option<String>| s.map_or(Value::nothing(span), |s| Value::string(s, span));
+
+    Value::record(
+        Record::from_iter([
+            ("source".to_string(), Value::string(source.to_string_lossy(), span)),
+            (
+                "destination".to_string(),
+                optional_string(destination.map(|destination| {
+                    destination
+                        .to_string_lossy()
+                        .into_owned()
+                })),
+            ),
+            ("action".to_string(), Value::string(action, span)),
+            ("reason".to_string(), optional_string(reason.map(str::to_string))),
+        ]),
+        span,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use dicom::dictionary_std::StandardDataDictionary;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        let template = Template::parse("out/{PatientID}/{(0020,0013)}.dcm", &StandardDataDictionary).unwrap();
+
+        assert_eq!(
+            template.segments,
+            [
+                Segment::Literal("out/".to_string()),
+                Segment::Attribute(tags::PATIENT_ID),
+                Segment::Literal("/".to_string()),
+                Segment::Attribute(tags::INSTANCE_NUMBER),
+                Segment::Literal(".dcm".to_string()),
+            ]
+        );
+    }
+
+    #[test_case("out/{PatientID"; "unclosed")]
+    #[test_case("out/PatientID}"; "unopened")]
+    #[test_case("out/{NoSuchAttribute}"; "unknown attribute")]
+    fn test_parse_invalid_template(template: &str) {
+        assert!(Template::parse(template, &StandardDataDictionary).is_err());
+    }
+
+    #[test_case("Doe^John", "Doe^John"; "unchanged")]
+    #[test_case("CT/MR", "CT_MR"; "separator")]
+    #[test_case("1\\2", "1_2"; "multiple values")]
+    #[test_case("..", "UNKNOWN"; "parent dir")]
+    #[test_case(" ", "UNKNOWN"; "empty")]
+    #[test_case("HEAD. ", "HEAD"; "trailing dot")]
+    fn test_sanitize(
+        value: &str,
+        expected: &str,
+    ) {
+        assert_eq!(sanitize(value), expected);
+    }
+}
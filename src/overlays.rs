@@ -0,0 +1,442 @@
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use flate2::Compression;
+use flate2::Crc;
+use flate2::write::ZlibEncoder;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Value};
+use snafu::{OptionExt, Snafu, ensure};
+
+use crate::convert::{bytes, get_int, get_string, words};
+use crate::input::{DicomSource, collect_input};
+use crate::plugin::DcmPlugin;
+use crate::reader::read_dcm_stream_with_pixel_data;
+
+/// Overlay planes are stored in the repeating groups 6000 to 601E.
+const OVERLAY_GROUPS: std::ops::RangeInclusive<u16> = 0x6000..=0x601E;
+
+const OVERLAY_ROWS: u16 = 0x0010;
+const OVERLAY_COLUMNS: u16 = 0x0011;
+const NUMBER_OF_FRAMES_IN_OVERLAY: u16 = 0x0015;
+const OVERLAY_DESCRIPTION: u16 = 0x0022;
+const OVERLAY_TYPE: u16 = 0x0040;
+const OVERLAY_SUBTYPE: u16 = 0x0045;
+const OVERLAY_ORIGIN: u16 = 0x0050;
+const IMAGE_FRAME_ORIGIN: u16 = 0x0051;
+const OVERLAY_BITS_ALLOCATED: u16 = 0x0100;
+const OVERLAY_BIT_POSITION: u16 = 0x0102;
+const OVERLAY_LABEL: u16 = 0x1500;
+const OVERLAY_DATA: u16 = 0x3000;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Overlay {:04X} has no {}", group, name))]
+    MissingAttribute { group: u16, name: &'static str },
+
+    #[snafu(display("Overlay {:04X} has {} bits of OverlayData, expected {}", group, actual, expected))]
+    OverlayDataTooShort { group: u16, expected: usize, actual: usize },
+
+    #[snafu(display("Overlay {:04X} is embedded in pixel data, but {}", group, reason))]
+    UnsupportedEmbeddedOverlay { group: u16, reason: String },
+}
+
+/// Single frame of an overlay plane, unpacked to a byte per pixel.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    /// Group of the overlay attributes, none for undeclared bits above HighBit.
+    pub group: Option<u16>,
+    /// Frame of the overlay, starting at 1.
+    pub frame: usize,
+    pub rows: usize,
+    pub columns: usize,
+    /// Row and column of the first overlay pixel relative to the image, starting at 1.
+    pub origin: Option<Vec<i64>>,
+    pub overlay_type: Option<String>,
+    pub subtype: Option<String>,
+    pub description: Option<String>,
+    pub label: Option<String>,
+    /// Bit of the pixel data words holding the overlay, for embedded overlays.
+    pub bit_position: Option<u16>,
+    /// 1 for overlay pixels, 0 otherwise, row by row.
+    pub bitmap: Vec<u8>,
+}
+
+impl Overlay {
+    pub fn is_embedded(&self) -> bool {
+        self.bit_position
+            .is_some()
+    }
+}
+
+/// Native pixel data of a 16-bit single-sample image, used for overlays embedded in its unused high bits.
+struct PixelWords {
+    rows: usize,
+    columns: usize,
+    frames: usize,
+    high_bit: u16,
+    words: Vec<u16>,
+}
+
+/// `dcm overlays` command, unpacks overlay planes.
+pub struct DcmOverlaysCommand;
+
+impl PluginCommand for DcmOverlaysCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm overlays"
+    }
+
+    fn description(&self) -> &str {
+        "Unpack the overlay planes of DICOM images, from group 60xx and from the high bits of pixel data."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .switch("png", "Return each bitmap as a 1-bit grayscale PNG image instead of a byte per pixel.", Some('p'))
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "overlay".to_string(), "annotation".to_string(), "60xx".to_string()])
+            .extra_description(
+                "Returns a record per overlay and frame, with the `SOPInstanceUID` of the image, the `group` of the overlay (e.g. \"6000\"), \
+                 the `frame` (starting at 1), OverlayRows, OverlayColumns, OverlayOrigin, OverlayType, OverlaySubtype, OverlayDescription \
+                 and OverlayLabel, whether the overlay is `embedded` in the pixel data with its `bit_position`, and the `bitmap`. The bitmap \
+                 has a byte per pixel, row by row, 1 for overlay pixels and 0 otherwise, or is a PNG image with `--png`. Overlays without \
+                 OverlayData are read from bit OverlayBitPosition of 16-bit native pixel data. Bits above HighBit that are set in unsigned \
+                 pixel data without being declared as an overlay are returned as well, without group.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example { description: "List the overlays of an image", example: "\"image.dcm\" | dcm overlays | reject bitmap", result: None },
+            Example {
+                description: "Save the first overlay as PNG",
+                example: "\"image.dcm\" | dcm overlays --png | first | get bitmap | save overlay.png",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let png = call.has_flag("png")?;
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let bytes = source
+                .bytes()
+                .map_err(|e| source.error(&e, span))?;
+            let obj = read_dcm_stream_with_pixel_data(Cursor::new(&bytes[..]), None).map_err(|e| source.error(&e, span))?;
+
+            let sop_instance_uid = get_string(&obj, tags::SOP_INSTANCE_UID)
+                .map(|uid| Value::string(uid, span))
+                .unwrap_or_else(|| Value::nothing(span));
+
+            for overlay in overlays(&obj).map_err(|e| source.labeled_error("Invalid overlay", &e, span))? {
+                let bitmap = if png {
+                    encode_png(&overlay).map_err(|e| source.labeled_error("Failed to encode PNG", &e, span))?
+                } else {
+                    overlay
+                        .bitmap
+                        .clone()
+                };
+                output.push(overlay_to_value(&overlay, bitmap, &sop_instance_uid, span));
+            }
+        }
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+/// Unpacks the overlay planes of `obj`, in group order, followed by undeclared bits set above HighBit.
+pub fn overlays(obj: &InMemDicomObject) -> Result<Vec<Overlay>, Error> {
+    let pixels = pixel_words(obj);
+    let mut overlays = Vec::new();
+    let mut embedded_bits = Vec::new();
+
+    for group in OVERLAY_GROUPS.step_by(2) {
+        let element = |element: u16| obj.get(Tag(group, element));
+        if element(OVERLAY_ROWS).is_none() && element(OVERLAY_DATA).is_none() {
+            continue;
+        }
+
+        let rows = get_int(obj, Tag(group, OVERLAY_ROWS)).context(MissingAttributeSnafu { group, name: "OverlayRows" })?;
+        let columns = get_int(obj, Tag(group, OVERLAY_COLUMNS)).context(MissingAttributeSnafu { group, name: "OverlayColumns" })?;
+        let frames = get_int(obj, Tag(group, NUMBER_OF_FRAMES_IN_OVERLAY)).unwrap_or(1);
+        let origin = element(OVERLAY_ORIGIN).and_then(|e| {
+            e.to_multi_int::<i64>()
+                .ok()
+        });
+
+        let template = Overlay {
+            group: Some(group),
+            frame: 0,
+            rows,
+            columns,
+            origin,
+            overlay_type: get_string(obj, Tag(group, OVERLAY_TYPE)),
+            subtype: get_string(obj, Tag(group, OVERLAY_SUBTYPE)),
+            description: get_string(obj, Tag(group, OVERLAY_DESCRIPTION)),
+            label: get_string(obj, Tag(group, OVERLAY_LABEL)),
+            bit_position: None,
+            bitmap: Vec::new(),
+        };
+
+        match element(OVERLAY_DATA).and_then(|e| {
+            e.value()
+                .primitive()
+        }) {
+            Some(data) => {
+                let data = bytes(data);
+                let size = rows * columns;
+                let expected = size * frames;
+                ensure!(data.len() * 8 >= expected, OverlayDataTooShortSnafu { group, expected, actual: data.len() * 8 });
+
+                // bits are packed row by row, the first pixel in the least significant bit of the first byte
+                for frame in 0..frames {
+                    let bitmap = (frame * size..(frame + 1) * size)
+                        .map(|bit| (data[bit / 8] >> (bit % 8)) & 1)
+                        .collect();
+                    overlays.push(Overlay { frame: frame + 1, bitmap, ..template.clone() });
+                }
+            }
+            None => {
+                // overlays without OverlayData (retired) use a bit of the pixel data words
+                let bits_allocated = get_int(obj, Tag(group, OVERLAY_BITS_ALLOCATED)).unwrap_or(1);
+                ensure!(bits_allocated > 1, MissingAttributeSnafu { group, name: "OverlayData" });
+                let bit_position: u16 =
+                    get_int(obj, Tag(group, OVERLAY_BIT_POSITION)).context(MissingAttributeSnafu { group, name: "OverlayBitPosition" })?;
+                let unsupported = |reason: String| UnsupportedEmbeddedOverlaySnafu { group, reason };
+
+                let pixels = pixels
+                    .as_ref()
+                    .context(unsupported("the image has no native 16-bit single-sample pixel data".to_string()))?;
+                ensure!(bit_position < 16, unsupported(format!("OverlayBitPosition {bit_position} is out of range")));
+                ensure!(
+                    rows == pixels.rows && columns == pixels.columns,
+                    unsupported(format!("its size {rows}x{columns} differs from the image size {}x{}", pixels.rows, pixels.columns))
+                );
+
+                let first_frame = get_int(obj, Tag(group, IMAGE_FRAME_ORIGIN)).unwrap_or(1);
+                ensure!(
+                    first_frame >= 1 && first_frame - 1 + frames <= pixels.frames,
+                    unsupported(format!("its frames {}..{} are not in the image", first_frame, first_frame + frames - 1))
+                );
+
+                for frame in 0..frames {
+                    let bitmap = pixels.frame_bit(first_frame - 1 + frame, bit_position);
+                    overlays.push(Overlay { frame: frame + 1, bit_position: Some(bit_position), bitmap, ..template.clone() });
+                }
+                embedded_bits.push(bit_position);
+            }
+        }
+    }
+
+    // legacy images may carry overlays in the high bits without overlay attributes
+    if let Some(pixels) = &pixels {
+        for bit_position in (pixels.high_bit + 1..16).filter(|bit| !embedded_bits.contains(bit)) {
+            for frame in 0..pixels.frames {
+                let bitmap = pixels.frame_bit(frame, bit_position);
+                if bitmap.contains(&1) {
+                    overlays.push(Overlay {
+                        group: None,
+                        frame: frame + 1,
+                        rows: pixels.rows,
+                        columns: pixels.columns,
+                        origin: None,
+                        overlay_type: None,
+                        subtype: None,
+                        description: None,
+                        label: None,
+                        bit_position: Some(bit_position),
+                        bitmap,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(overlays)
+}
+
+impl PixelWords {
+    fn frame_bit(
+        &self,
+        frame: usize,
+        bit_position: u16,
+    ) -> Vec<u8> {
+        let size = self.rows * self.columns;
+        self.words[frame * size..(frame + 1) * size]
+            .iter()
+            .map(|word| ((word >> bit_position) & 1) as u8)
+            .collect()
+    }
+}
+
+/// Pixel data words of native 16-bit single-sample images, none for other images.
+fn pixel_words(obj: &InMemDicomObject) -> Option<PixelWords> {
+    let int = |tag: Tag| get_int::<usize>(obj, tag);
+
+    if int(tags::BITS_ALLOCATED)? != 16 || int(tags::SAMPLES_PER_PIXEL).unwrap_or(1) != 1 {
+        return None;
+    }
+
+    let rows = int(tags::ROWS)?;
+    let columns = int(tags::COLUMNS)?;
+    let frames = int(tags::NUMBER_OF_FRAMES).unwrap_or(1);
+    let bits_stored = int(tags::BITS_STORED).unwrap_or(16);
+    let high_bit = int(tags::HIGH_BIT).unwrap_or(bits_stored.saturating_sub(1)) as u16;
+
+    let data = obj
+        .get(tags::PIXEL_DATA)?
+        .value()
+        .primitive()?;
+    let words = words(data);
+    if words.len() < rows * columns * frames {
+        return None;
+    }
+
+    // bits above HighBit hold the sign of signed pixel data, not overlays
+    let high_bit = if int(tags::PIXEL_REPRESENTATION) == Some(1) {
+        15
+    } else {
+        high_bit
+    };
+
+    Some(PixelWords { rows, columns, frames, high_bit, words })
+}
+
+/// Encodes the bitmap of `overlay` as 1-bit grayscale PNG, overlay pixels being white.
+pub fn encode_png(overlay: &Overlay) -> std::io::Result<Vec<u8>> {
+    let mut header = Vec::new();
+    header.extend((overlay.columns as u32).to_be_bytes());
+    header.extend((overlay.rows as u32).to_be_bytes());
+    // bit depth 1, grayscale, deflate, adaptive filtering, no interlace
+    header.extend([1, 0, 0, 0, 0]);
+
+    // each scanline starts with its filter type (none), pixels are packed from the most significant bit
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in overlay
+        .bitmap
+        .chunks(
+            overlay
+                .columns
+                .max(1),
+        )
+    {
+        let mut scanline = vec![
+            0;
+            1 + row
+                .len()
+                .div_ceil(8)
+        ];
+        for (column, pixel) in row
+            .iter()
+            .enumerate()
+        {
+            scanline[1 + column / 8] |= pixel << (7 - column % 8);
+        }
+        encoder.write_all(&scanline)?;
+    }
+    let data = encoder.finish()?;
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &data);
+    write_chunk(&mut png, b"IEND", &[]);
+
+    Ok(png)
+}
+
+fn write_chunk(
+    png: &mut Vec<u8>,
+    kind: &[u8; 4],
+    data: &[u8],
+) {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    png.extend(
+        crc.sum()
+            .to_be_bytes(),
+    );
+}
+
+fn overlay_to_value(
+    overlay: &Overlay,
+    bitmap: Vec<u8>,
+    sop_instance_uid: &Value,
+    span: Span,
+) -> Value {
+    let string = |s: &Option<String>| {
+        s.as_ref()
+            .map_or_else(|| Value::nothing(span), |s| Value::string(s, span))
+    };
+
+    let mut record = Record::new();
+    record.push("SOPInstanceUID", sop_instance_uid.clone());
+    record.push(
+        "group",
+        overlay
+            .group
+            .map_or_else(|| Value::nothing(span), |group| Value::string(format!("{group:04X}"), span)),
+    );
+    record.push("frame", Value::int(overlay.frame as i64, span));
+    record.push("OverlayRows", Value::int(overlay.rows as i64, span));
+    record.push("OverlayColumns", Value::int(overlay.columns as i64, span));
+    record.push(
+        "OverlayOrigin",
+        overlay
+            .origin
+            .as_ref()
+            .map_or_else(
+                || Value::nothing(span),
+                |origin| {
+                    Value::list(
+                        origin
+                            .iter()
+                            .map(|v| Value::int(*v, span))
+                            .collect(),
+                        span,
+                    )
+                },
+            ),
+    );
+    record.push("OverlayType", string(&overlay.overlay_type));
+    record.push("OverlaySubtype", string(&overlay.subtype));
+    record.push("OverlayDescription", string(&overlay.description));
+    record.push("OverlayLabel", string(&overlay.label));
+    record.push("embedded", Value::bool(overlay.is_embedded(), span));
+    record.push(
+        "bit_position",
+        overlay
+            .bit_position
+            .map_or_else(|| Value::nothing(span), |bit| Value::int(bit as i64, span)),
+    );
+    record.push("bitmap", Value::binary(bitmap, span));
+
+    Value::record(record, span)
+}
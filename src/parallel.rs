@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use nu_protocol::{LabeledError, Span, Value};
+
+type Job = (Value, Sender<Vec<Value>>);
+
+/// Maps each value to zero or more values using a bounded pool of worker threads while preserving the input order.
+///
+/// At most `2 * threads` values are in flight at any time so that long (or infinite) streams
+/// are not collected into memory. Workers exit once the iterator is dropped.
+pub struct OrderedParallelMap<I> {
+    input: I,
+    jobs: Option<Sender<Job>>,
+    pending: VecDeque<(Span, Receiver<Vec<Value>>)>,
+    ready: std::vec::IntoIter<Value>,
+    capacity: usize,
+}
+
+impl<I> OrderedParallelMap<I>
+where
+    I: Iterator<Item = Value>,
+{
+    pub fn new<F>(
+        input: I,
+        threads: NonZeroUsize,
+        mapping: F,
+    ) -> Self
+    where
+        F: Fn(Value) -> Vec<Value> + Send + Sync + 'static,
+    {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let mapping = Arc::new(mapping);
+
+        for _ in 0..threads.get() {
+            let job_receiver = Arc::clone(&job_receiver);
+            let mapping = Arc::clone(&mapping);
+
+            thread::spawn(move || {
+                loop {
+                    // only hold the lock while waiting for the next job, not while processing it
+                    let job = match job_receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+
+                    let Ok((value, result_sender)) = job else {
+                        // the iterator has been dropped
+                        return;
+                    };
+
+                    // the receiver may be gone if the output stream was dropped early, that's fine
+                    let _ = result_sender.send(mapping(value));
+                }
+            });
+        }
+
+        Self { input, jobs: Some(jobs), pending: VecDeque::new(), ready: Vec::new().into_iter(), capacity: threads.get() * 2 }
+    }
+
+    /// Sends values from the input to the workers until the in-flight queue is full.
+    fn fill(&mut self) {
+        let Some(jobs) = &self.jobs else {
+            return;
+        };
+
+        while self
+            .pending
+            .len()
+            < self.capacity
+        {
+            let Some(value) = self
+                .input
+                .next()
+            else {
+                // input exhausted, let the workers exit once they're done
+                self.jobs = None;
+                return;
+            };
+
+            let span = value.span();
+            let (result_sender, result_receiver) = mpsc::channel();
+
+            // if all workers are gone, the result channel is dropped and reported as an error in `next()`
+            let sent = jobs
+                .send((value, result_sender))
+                .is_ok();
+
+            self.pending
+                .push_back((span, result_receiver));
+
+            if !sent {
+                self.jobs = None;
+                return;
+            }
+        }
+    }
+}
+
+impl<I> Iterator for OrderedParallelMap<I>
+where
+    I: Iterator<Item = Value>,
+{
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        loop {
+            if let Some(value) = self
+                .ready
+                .next()
+            {
+                return Some(value);
+            }
+
+            self.fill();
+
+            let (span, result_receiver) = self
+                .pending
+                .pop_front()?;
+
+            self.ready = result_receiver
+                .recv()
+                .unwrap_or_else(|_| {
+                    let error = LabeledError::new("Worker thread failed").with_label("Failed to process this value in a worker thread", span);
+                    vec![Value::error(error.into(), span)]
+                })
+                .into_iter();
+        }
+    }
+}
@@ -1,15 +1,43 @@
-use std::io::Cursor;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 
-use crate::dicomweb::{is_dicom_record, DicomWebDump};
+use crate::archive::{self, ArchiveKind, DETECT_LEN};
+use crate::dedupe::DcmDedupeCommand;
+use crate::dicomdir::{DcmDicomdirCommand, DcmMakeDicomdirCommand};
+use crate::dicomweb::{DicomWebDump, is_dicom_record};
+use crate::document::{DcmExtractDocumentCommand, DcmWrapPdfCommand};
+use crate::dose::DcmDoseCommand;
+use crate::frames::DcmFramesCommand;
+use crate::geometry::DcmGeometryCommand;
+use crate::hash::DcmHashCommand;
+use crate::hierarchy::DcmHierarchyCommand;
+use crate::input::{get_record_string, resolve_path};
+use crate::lenient::{PartialRead, read_dcm_file_lenient, read_dcm_stream_lenient};
 use crate::meta::make_row_from_dicom_metadata;
-use crate::reader::{read_dcm_file, read_dcm_stream};
+use crate::multiframe::DcmSplitFramesCommand;
+use crate::nifti::DcmToNiftiCommand;
+use crate::organize::DcmOrganizeCommand;
+use crate::overlays::DcmOverlaysCommand;
+use crate::parallel::OrderedParallelMap;
+use crate::reader::{DETECTION_LEN, Error as ReaderError, ErrorKind, IoSnafu, Layout, read_dcm_file, read_dcm_stream, read_interruptible};
+use crate::rtdose::DcmRtdoseCommand;
+use crate::rtplan::DcmRtplanCommand;
+use crate::rtstruct::DcmRtstructCommand;
+use crate::sr::DcmSrCommand;
+use crate::transcode::DcmTranscodeCommand;
+use crate::waveform::DcmWaveformCommand;
 
 use crate::dcm;
+use dicom::encoding::TransferSyntaxIndex;
 use dicom::object::DefaultDicomObject;
 use dicom::object::StandardDataDictionary;
+use dicom::transfer_syntax::TransferSyntaxRegistry;
 use indexmap::IndexMap;
 use nu_plugin::{EngineInterface, Plugin, PluginCommand};
+use snafu::ResultExt;
+
 use nu_protocol::{
     Category, Example, IntoInterruptiblePipelineData, IntoPipelineData, LabeledError, PipelineData, Record, ShellError, Signals, Signature, Span,
     SyntaxShape, Value,
@@ -26,13 +54,98 @@ impl Plugin for DcmPlugin {
     }
 
     fn commands(&self) -> Vec<Box<dyn nu_plugin::PluginCommand<Plugin = Self>>> {
-        vec![Box::new(DcmPluginCommand)]
+        vec![
+            Box::new(DcmPluginCommand),
+            Box::new(DcmDicomdirCommand),
+            Box::new(DcmMakeDicomdirCommand),
+            Box::new(DcmHierarchyCommand),
+            Box::new(DcmOrganizeCommand),
+            Box::new(DcmDedupeCommand),
+            Box::new(DcmHashCommand),
+            Box::new(DcmTranscodeCommand),
+            Box::new(DcmSplitFramesCommand),
+            Box::new(DcmFramesCommand),
+            Box::new(DcmGeometryCommand),
+            Box::new(DcmToNiftiCommand),
+            Box::new(DcmSrCommand),
+            Box::new(DcmDoseCommand),
+            Box::new(DcmExtractDocumentCommand),
+            Box::new(DcmWrapPdfCommand),
+            Box::new(DcmWaveformCommand),
+            Box::new(DcmOverlaysCommand),
+            Box::new(DcmRtstructCommand),
+            Box::new(DcmRtplanCommand),
+            Box::new(DcmRtdoseCommand),
+        ]
     }
 }
 
 #[derive(Default)]
 pub struct DcmPluginCommand;
 
+/// Options of the `dcm` command, as passed on the command line.
+#[derive(Default, Clone)]
+pub struct DcmOptions {
+    /// Column to report errors in instead of producing error values (`--error`).
+    pub error_column: Option<String>,
+
+    /// Number of worker threads used to process list streams (`--threads`). Sequential if not set.
+    pub threads: Option<NonZeroUsize>,
+
+    /// Return elements parsed before a failure instead of an error (`--lenient`).
+    pub lenient: bool,
+
+    /// In lenient mode, try to continue parsing after a broken sequence (`--resync`).
+    pub resync: bool,
+
+    /// Transfer syntax UID of data sets without file meta group (`--transfer-syntax`). Guessed if not set.
+    pub transfer_syntax: Option<String>,
+}
+
+impl DcmOptions {
+    fn read_file(
+        &self,
+        file: &Path,
+    ) -> Result<(DefaultDicomObject, Option<PartialRead>), ReaderError> {
+        if self.lenient {
+            read_dcm_file_lenient(
+                file,
+                self.resync,
+                self.transfer_syntax
+                    .as_deref(),
+            )
+        } else {
+            read_dcm_file(
+                file,
+                self.transfer_syntax
+                    .as_deref(),
+            )
+            .map(|obj| (obj, None))
+        }
+    }
+
+    fn read_stream<R: Read>(
+        &self,
+        input: R,
+    ) -> Result<(DefaultDicomObject, Option<PartialRead>), ReaderError> {
+        if self.lenient {
+            read_dcm_stream_lenient(
+                input,
+                self.resync,
+                self.transfer_syntax
+                    .as_deref(),
+            )
+        } else {
+            read_dcm_stream(
+                input,
+                self.transfer_syntax
+                    .as_deref(),
+            )
+            .map(|obj| (obj, None))
+        }
+    }
+}
+
 impl PluginCommand for DcmPluginCommand {
     type Plugin = DcmPlugin;
 
@@ -92,6 +205,24 @@ impl PluginCommand for DcmPluginCommand {
                 SyntaxShape::String,
                 "If an error occurs when Dicom object is parsed, the error message will be inserted in this column instead producing an error result.",
                 Some('e'))
+            .named(
+                "threads",
+                SyntaxShape::Int,
+                "Number of files to parse concurrently when processing a list. The output order is preserved.",
+                Some('t'))
+            .switch(
+                "lenient",
+                "Return all elements parsed before a truncated or corrupt data set fails to parse. Such records have a `partial` column with the failure offset and reason.",
+                Some('l'))
+            .switch(
+                "resync",
+                "Implies --lenient. If parsing fails inside a sequence (e.g. a bogus undefined length item), skip the sequence and continue parsing.",
+                None)
+            .named(
+                "transfer-syntax",
+                SyntaxShape::String,
+                "Transfer syntax UID used to parse data sets without file meta group (e.g. 1.2.840.10008.1.2 for Implicit VR Little Endian). Guessed from the first element if not set.",
+                None)
             .category(Category::Formats)  // More appropriate category
             .search_terms(vec!["dicom".to_string(), "medical".to_string(), "parse".to_string()])
             .description("Parse DICOM files and binary data")
@@ -113,6 +244,12 @@ impl PluginCommand for DcmPluginCommand {
             Example { description: "Parse DICOM files from a list", example: "ls *.dcm | dcm", result: None },
             Example { description: "Parse a specific file by filename", example: "\"file.dcm\" | dcm", result: None },
             Example { description: "Parse with error handling", example: "ls *.dcm | dcm --error parse_error", result: None },
+            Example { description: "Parse files using 8 worker threads", example: "ls **/*.dcm | dcm --threads 8", result: None },
+            Example {
+                description: "Parse raw Implicit VR Little Endian data sets without file meta group",
+                example: "ls *.raw | dcm --transfer-syntax 1.2.840.10008.1.2",
+                result: None,
+            },
         ]
     }
 
@@ -133,10 +270,21 @@ impl PluginCommand for DcmPluginCommand {
         let input_metadata = input.metadata();
 
         let error_column = call.get_flag::<String>("error")?;
+        let threads = get_threads_flag(call)?;
+
+        let resync = call.has_flag("resync")?;
+        let lenient = resync || call.has_flag("lenient")?;
+
+        let transfer_syntax = get_transfer_syntax_flag(call)?;
+
+        let options = DcmOptions { error_column, threads, lenient, resync, transfer_syntax };
+        let signals = engine
+            .signals()
+            .clone();
 
         // run
         // TODO find a way without cloning? ListStream::map() requires 'static lifetime, maybe I can use iterators directly?
-        let output = self.process_pipeline_data(plugin.clone(), current_dir, error_column, &input_span, input)?;
+        let output = self.process_pipeline_data(plugin.clone(), current_dir, options, signals, &input_span, input)?;
 
         // Forward DataSource metadata from input to output, but clear any content type. This keeps the source.
         let output_metadata = input_metadata.map(|m| m.with_content_type(None));
@@ -150,7 +298,8 @@ impl DcmPluginCommand {
         &self,
         plugin: DcmPlugin,
         current_dir: Result<PathBuf, ShellError>,
-        error_column: Option<String>,
+        options: DcmOptions,
+        signals: Signals,
         input_span: &Span,
         input: PipelineData,
     ) -> Result<PipelineData, LabeledError> {
@@ -160,30 +309,72 @@ impl DcmPluginCommand {
 
             // process value directly
             PipelineData::Value(value, ..) => {
-                Self::process_value(&plugin, current_dir.as_deref(), &value, &error_column).map(Value::into_pipeline_data)
+                Self::process_value(&plugin, current_dir.as_deref(), &value, &options).map(|output| output.into_pipeline_data(*input_span, signals))
             }
 
-            // map list of values one by one
+            // map list of values one by one, optionally using a pool of worker threads
             PipelineData::ListStream(list_stream, ..) => {
-                // TODO should this fail immediately or generate errors?
-                let mapped_stream = list_stream.map(move |v| match Self::process_value(&plugin, current_dir.as_deref(), &v, &error_column) {
-                    Ok(value) => value,
-                    Err(e) => Value::error(e.into(), v.span()),
-                });
+                let threads = options.threads;
 
-                Ok(mapped_stream.into_pipeline_data(*input_span, Signals::EMPTY))
+                // TODO should this fail immediately or generate errors?
+                // archives produce multiple values, stream them individually
+                let process = move |v: Value| match Self::process_value(&plugin, current_dir.as_deref(), &v, &options) {
+                    Ok(output) => output.into_values(),
+                    Err(e) => Box::new(std::iter::once(Value::error(e.into(), v.span()))),
+                };
+
+                // workers return all values of an input at once, sequentially archive entries are read as they're pulled
+                let mapped_stream = match threads {
+                    Some(threads) if threads.get() > 1 => {
+                        list_stream.modify(|iter| OrderedParallelMap::new(iter, threads, move |v| process(v).collect()))
+                    }
+                    _ => list_stream.modify(|iter| iter.flat_map(process)),
+                };
+
+                // stop pulling values from the input stream once interrupted
+                Ok(mapped_stream.into_pipeline_data(*input_span, signals))
             }
 
             // process input bytestream directly without collecting it into memory
             PipelineData::ByteStream(byte_stream, ..) => {
-                let byte_stream_reader = byte_stream
+                let mut byte_stream_reader = byte_stream
                     .reader()
                     .ok_or_else(|| LabeledError::new("Empty bytestream"))?;
 
-                let obj = read_dcm_stream(byte_stream_reader)
-                    .map_err(|e| LabeledError::new("Invalid DICOM data").with_label(e.to_string(), *input_span))?;
+                let map_reader_error = |e| match e {
+                    ReaderError::Interrupted => LabeledError::from(ShellError::Interrupted { span: *input_span }),
+                    e => LabeledError::new("Invalid DICOM data").with_label(e.to_string(), *input_span),
+                };
+
+                // peek at the start of the stream to detect archives
+                let mut head = Vec::with_capacity(DETECT_LEN);
+                read_interruptible(byte_stream_reader.by_ref(), &signals, |reader| {
+                    reader
+                        .take(DETECT_LEN as u64)
+                        .read_to_end(&mut head)
+                        .context(IoSnafu)
+                })
+                .map_err(map_reader_error)?;
+
+                if let Some(kind) = ArchiveKind::detect(&head) {
+                    // zip archives need random access, read the whole archive into memory
+                    let mut bytes = head;
+                    read_interruptible(byte_stream_reader, &signals, |mut reader| {
+                        reader
+                            .read_to_end(&mut bytes)
+                            .context(IoSnafu)
+                    })
+                    .map_err(map_reader_error)?;
+
+                    return Ok(
+                        Self::process_archive(&plugin, input_span, Cursor::new(bytes), kind, None, &options).into_pipeline_data(*input_span, signals)
+                    );
+                }
+
+                let input = Cursor::new(head).chain(byte_stream_reader);
+                let (obj, partial) = read_interruptible(input, &signals, |reader| options.read_stream(reader)).map_err(map_reader_error)?;
 
-                Self::process_dicom_object(&plugin, input_span, obj, &error_column).map(Value::into_pipeline_data)
+                Self::process_dicom_object(&plugin, input_span, obj, partial, None, &options).map(Value::into_pipeline_data)
             }
         }
     }
@@ -192,26 +383,17 @@ impl DcmPluginCommand {
         plugin: &DcmPlugin,
         current_dir: Result<&Path, &ShellError>,
         value: &Value,
-        error_column: &Option<String>,
-    ) -> Result<Value, LabeledError> {
-        let result = Self::process_value_with_normal_error(plugin, current_dir, value, error_column);
+        options: &DcmOptions,
+    ) -> Result<Output, LabeledError> {
+        let result = Self::process_value_with_normal_error(plugin, current_dir, value, options);
 
         // TODO better value.span().unwrap()
-        match (error_column, &result) {
-            (Some(error_column), Err(err)) => Ok(Value::record(
-                Record::from_raw_cols_vals(
-                    vec![error_column.to_string()],
-                    vec![Value::string(
-                        err.msg
-                            .to_string(),
-                        value.span(),
-                    )],
-                    Span::unknown(),
-                    Span::unknown(),
-                )?,
-                value.span(),
-            )),
-            _ => result,
+        match (&options.error_column, result) {
+            (_, Ok(output)) => Ok(output),
+            (Some(error_column), Err(err)) => {
+                Ok(Output::Value(Value::record(Record::from_iter([(error_column.to_string(), err.into_value(value.span()))]), value.span())))
+            }
+            (None, Err(err)) => Err(*err.error),
         }
     }
 
@@ -219,8 +401,8 @@ impl DcmPluginCommand {
         plugin: &DcmPlugin,
         current_dir: Result<&Path, &ShellError>,
         value: &Value,
-        error_column: &Option<String>,
-    ) -> Result<Value, LabeledError> {
+        options: &DcmOptions,
+    ) -> Result<Output, ProcessingError> {
         match &value {
             Value::String { val, internal_span, .. } => {
                 // make absolute if needed
@@ -229,19 +411,28 @@ impl DcmPluginCommand {
                 // TODO add some heuristics to determine if the input string is filename or DICOM binary data connverted to utf-8 by nu?
                 // (see `ByteStream::into_value()` which does the conversion to string.)
 
-                let obj = read_dcm_file(&file).map_err(|e| {
-                    // Report a better error if the input string looks like DICOM binary data with preamble.
-                    // TODO this is messy. In fact the whole error reporting is messy.
-                    let text = if val.get(128..132) == Some("DICM") {
-                        "Input string looks like DICOM binary data. Either pass binary data, or a filename.".to_string()
-                    } else {
-                        format!("{} [file {}]", e, file.to_string_lossy())
-                    };
+                if let Some(kind) = ArchiveKind::detect_file(&file) {
+                    return Self::process_archive_file(plugin, internal_span, &file, kind, options);
+                }
 
-                    LabeledError::new("`dcm` expects valid DICOM binary data").with_label(text, *internal_span)
-                })?;
+                let (obj, partial) = options
+                    .read_file(&file)
+                    .map_err(|e| {
+                        // Report a better error if the input string looks like DICOM binary data with preamble.
+                        // TODO this is messy. In fact the whole error reporting is messy.
+                        let text = if val.get(128..132) == Some("DICM") {
+                            "Input string looks like DICOM binary data. Either pass binary data, or a filename.".to_string()
+                        } else {
+                            format!("{} [file {}]", e, file.to_string_lossy())
+                        };
+
+                        let labeled_error = LabeledError::new("`dcm` expects valid DICOM binary data").with_label(text, *internal_span);
+                        ProcessingError::from_reader_error(&e, Some(file.clone()), labeled_error)
+                    })?;
 
-                Self::process_dicom_object(plugin, internal_span, obj, error_column)
+                let value = Self::process_dicom_object(plugin, internal_span, obj, partial, None, options)
+                    .map_err(|e| ProcessingError::from_labeled_error(e, Some(file.clone())))?;
+                Ok(Output::Value(value))
             }
             Value::Record { val, internal_span, .. } => {
                 // Check if a file record
@@ -254,14 +445,23 @@ impl DcmPluginCommand {
                     // make absolute if needed
                     let file = resolve_path(record_name, current_dir, value.span())?;
 
-                    // merge with file-reading above (Value::String)?
-                    let obj = read_dcm_file(&file).map_err(|e| {
-                        let text = format!("{} [file {}]", e, file.to_string_lossy());
+                    if let Some(kind) = ArchiveKind::detect_file(&file) {
+                        return Self::process_archive_file(plugin, internal_span, &file, kind, options);
+                    }
 
-                        LabeledError::new("`dcm` expects valid DICOM binary data").with_label(text, *internal_span)
-                    })?;
-
-                    return Self::process_dicom_object(plugin, internal_span, obj, error_column);
+                    // merge with file-reading above (Value::String)?
+                    let (obj, partial) = options
+                        .read_file(&file)
+                        .map_err(|e| {
+                            let text = format!("{} [file {}]", e, file.to_string_lossy());
+
+                            let labeled_error = LabeledError::new("`dcm` expects valid DICOM binary data").with_label(text, *internal_span);
+                            ProcessingError::from_reader_error(&e, Some(file.clone()), labeled_error)
+                        })?;
+
+                    let value = Self::process_dicom_object(plugin, internal_span, obj, partial, None, options)
+                        .map_err(|e| ProcessingError::from_labeled_error(e, Some(file.clone())))?;
+                    return Ok(Output::Value(value));
                 }
 
                 // Check if it looks like a dicomweb record.
@@ -271,48 +471,190 @@ impl DcmPluginCommand {
                         .process_dicomweb_record(val, *internal_span)
                         .map_err(|e| LabeledError::new("Failed to proess DicomWeb record").with_label(e.to_string(), e.span()))?;
 
-                    return Ok(result);
+                    return Ok(Output::Value(result));
                 }
 
                 // Output generic error
                 Err(LabeledError::new("Cannot process records directly, unless they are File or DicomWeb records")
-                    .with_label("For files, select file name, binary data, or use records with `name` and `type`", *internal_span))
+                    .with_label("For files, select file name, binary data, or use records with `name` and `type`", *internal_span)
+                    .into())
             }
             Value::Binary { val, internal_span, .. } => {
+                if let Some(kind) = ArchiveKind::detect(val) {
+                    // entries are read on another thread
+                    return Ok(Self::process_archive(plugin, internal_span, Cursor::new(val.clone()), kind, None, options));
+                }
+
                 let cursor = Cursor::new(val);
-                let obj = read_dcm_stream(cursor).map_err(|e| LabeledError::new("Invalid DICOM data").with_label(e.to_string(), *internal_span))?;
+                let (obj, partial) = options
+                    .read_stream(cursor)
+                    .map_err(|e| {
+                        let labeled_error = LabeledError::new("Invalid DICOM data").with_label(e.to_string(), *internal_span);
+                        ProcessingError::from_reader_error(&e, None, labeled_error)
+                    })?;
 
-                Self::process_dicom_object(plugin, internal_span, obj, error_column)
+                Ok(Output::Value(Self::process_dicom_object(plugin, internal_span, obj, partial, None, options)?))
             }
             Value::List { vals, internal_span, .. } => {
                 // Use either a dicom result or an error for each input element>
+                // Archives are flattened, i.e. produce a result for each entry.
                 let result: Vec<Value> = vals
                     .iter()
-                    .map(|v| Self::process_value(plugin, current_dir, v, error_column).unwrap_or_else(|e| Value::error(e.into(), *internal_span)))
+                    .flat_map(|v| match Self::process_value(plugin, current_dir, v, options) {
+                        Ok(output) => output.into_values(),
+                        Err(e) => Box::new(std::iter::once(Value::error(e.into(), *internal_span))),
+                    })
                     .collect();
 
-                Ok(Value::list(result, *internal_span))
+                Ok(Output::Value(Value::list(result, *internal_span)))
             }
             _ => Err(LabeledError::new("Unrecognized type in stream")
-                .with_label("'dcm' expects a string (filepath), binary, or column path", value.span())),
+                .with_label("'dcm' expects a string (filepath), binary, or column path", value.span())
+                .into()),
         }
     }
 
+    /// Reads DICOM objects from all entries of an archive file, see `process_archive()`.
+    fn process_archive_file(
+        plugin: &DcmPlugin,
+        span: &Span,
+        file: &Path,
+        kind: ArchiveKind,
+        options: &DcmOptions,
+    ) -> Result<Output, ProcessingError> {
+        let input = File::open(file)
+            .context(IoSnafu)
+            .map_err(|e| {
+                let labeled_error = LabeledError::new("Could not read archive").with_label(format!("{} [file {}]", e, file.to_string_lossy()), *span);
+                ProcessingError::from_reader_error(&e, Some(file.to_path_buf()), labeled_error)
+            })?;
+
+        Ok(Self::process_archive(plugin, span, BufReader::new(input), kind, Some(file), options))
+    }
+
+    /// Reads DICOM objects from all entries of an archive, lazily. Each entry produces a record with an `archive_entry`
+    /// column. Entries that fail to parse are reported the same way as other inputs, except that entries which are
+    /// not recognized as DICOM are skipped unless `--error` is used.
+    fn process_archive<R: Read + Seek + Send + 'static>(
+        plugin: &DcmPlugin,
+        span: &Span,
+        input: R,
+        kind: ArchiveKind,
+        path: Option<&Path>,
+        options: &DcmOptions,
+    ) -> Output {
+        let plugin = plugin.clone();
+        let span = *span;
+        let path = path.map(Path::to_path_buf);
+        let options = options.clone();
+        let error_column = options
+            .error_column
+            .clone();
+        let archive_path = path.clone();
+
+        let entries = archive::map_entries(input, kind, move |name, entry| {
+            let processing_error = |e: ReaderError| {
+                let labeled_error = LabeledError::new("Invalid DICOM data").with_label(format!("{} [archive entry {}]", e, name), span);
+                ProcessingError::from_reader_error(&e, path.clone(), labeled_error)
+            };
+
+            // peek at the start of the entry to detect what it is
+            let mut head = Vec::with_capacity(DETECTION_LEN);
+            let result = entry
+                .take(DETECTION_LEN as u64)
+                .read_to_end(&mut head)
+                .context(IoSnafu)
+                .map_err(processing_error);
+
+            // archives often contain other files, e.g. reports or viewers
+            let recognized = Layout::is_recognized(
+                &head,
+                options
+                    .transfer_syntax
+                    .as_deref(),
+            );
+            if result.is_ok()
+                && !recognized
+                && options
+                    .error_column
+                    .is_none()
+            {
+                return None;
+            }
+
+            let result = result
+                .and_then(|_| {
+                    options
+                        .read_stream(Cursor::new(&head).chain(entry))
+                        .map_err(processing_error)
+                })
+                .and_then(|(obj, partial)| {
+                    Self::process_dicom_object(&plugin, &span, obj, partial, Some(name), &options)
+                        .map_err(|e| ProcessingError::from_labeled_error(e, path.clone()))
+                });
+
+            match (&options.error_column, result) {
+                (_, Ok(value)) => Some(value),
+                (Some(error_column), Err(err)) => Some(Value::record(
+                    Record::from_iter([(error_column.to_string(), err.into_value(span)), ("archive_entry".to_string(), Value::string(name, span))]),
+                    span,
+                )),
+                (None, Err(err)) if err.kind == ErrorKind::NotDicom => None,
+                (None, Err(err)) => Some(Value::error(
+                    err.error
+                        .into(),
+                    span,
+                )),
+            }
+        });
+
+        Output::Archive(Box::new(entries.map(move |result| {
+            result.unwrap_or_else(|e| {
+                let text = match &archive_path {
+                    Some(path) => format!("{} [file {}]", e, path.to_string_lossy()),
+                    None => e.to_string(),
+                };
+
+                let labeled_error = LabeledError::new("Could not read archive").with_label(text, span);
+                let err = ProcessingError::from_reader_error(&e, archive_path.clone(), labeled_error);
+                match &error_column {
+                    Some(error_column) => Value::record(Record::from_iter([(error_column.to_string(), err.into_value(span))]), span),
+                    None => Value::error(
+                        err.error
+                            .into(),
+                        span,
+                    ),
+                }
+            })
+        })))
+    }
+
     fn process_dicom_object(
         plugin: &DcmPlugin,
         span: &Span,
         obj: DefaultDicomObject,
-        error_column: &Option<String>,
+        partial: Option<PartialRead>,
+        archive_entry: Option<&str>,
+        options: &DcmOptions,
     ) -> Result<Value, LabeledError> {
         let dcm_dumper = dcm::DicomDump { dcm_dictionary: &plugin.dcm_dictionary };
 
         let mut index_map = IndexMap::with_capacity(1000);
 
         // make sure that when --error is used, the column always exists
-        if let Some(error_column) = error_column {
+        if let Some(error_column) = &options.error_column {
             index_map.insert(error_column.to_string(), Value::string(String::new(), *span));
         }
 
+        // similarly, in lenient mode `partial` is either nothing or the reason why the object is incomplete
+        if options.lenient {
+            index_map.insert("partial".to_string(), partial_to_value(partial, *span));
+        }
+
+        if let Some(archive_entry) = archive_entry {
+            index_map.insert("archive_entry".to_string(), Value::string(archive_entry, *span));
+        }
+
         // dump both metadata and data into a single table
         make_row_from_dicom_metadata(span, &mut index_map, obj.meta());
         dcm_dumper.make_row_from_dicom_object(span, &mut index_map, &obj);
@@ -322,36 +664,182 @@ impl DcmPluginCommand {
     }
 }
 
-fn get_record_string<'a>(
-    record: &'a Record,
-    field_name: &str,
-) -> Option<&'a str> {
-    let value = record.get(field_name)?;
-    let Value::String { val, .. } = value else {
-        return None;
-    };
-    Some(val.as_str())
+/// Output produced from a single input value.
+enum Output {
+    Value(Value),
+    /// One value per archive entry, produced as the archive is read.
+    Archive(Box<dyn Iterator<Item = Value> + Send>),
+}
+
+impl Output {
+    fn into_values(self) -> Box<dyn Iterator<Item = Value> + Send> {
+        match self {
+            Output::Value(value) => Box::new(std::iter::once(value)),
+            Output::Archive(values) => values,
+        }
+    }
+
+    fn into_pipeline_data(
+        self,
+        span: Span,
+        signals: Signals,
+    ) -> PipelineData {
+        match self {
+            Output::Value(value) => value.into_pipeline_data(),
+            Output::Archive(values) => values.into_pipeline_data(span, signals),
+        }
+    }
+}
+
+/// Error produced when processing a single input value. Apart from the error reported by nu, it keeps the details
+/// reported in the `--error` column.
+struct ProcessingError {
+    kind: ErrorKind,
+    path: Option<PathBuf>,
+    offset: Option<u64>,
+    message: String,
+    error: Box<LabeledError>,
+}
+
+impl ProcessingError {
+    fn from_reader_error(
+        reader_error: &ReaderError,
+        path: Option<PathBuf>,
+        error: LabeledError,
+    ) -> Self {
+        Self { kind: reader_error.kind(), path, offset: reader_error.offset(), message: reader_error.to_string(), error: Box::new(error) }
+    }
+
+    /// Wraps an error other than a reader error, e.g. a failure to convert a DICOM object read from `path`.
+    fn from_labeled_error(
+        error: LabeledError,
+        path: Option<PathBuf>,
+    ) -> Self {
+        // prefer the more detailed label, if any
+        let message = error
+            .labels
+            .first()
+            .map(|label| {
+                label
+                    .text
+                    .clone()
+            })
+            .unwrap_or_else(|| {
+                error
+                    .msg
+                    .clone()
+            });
+
+        Self { kind: ErrorKind::Conversion, path, offset: None, message, error: Box::new(error) }
+    }
+
+    /// Converts the error to a record used as the value of the `--error` column.
+    fn into_value(
+        self,
+        span: Span,
+    ) -> Value {
+        let path = match self.path {
+            Some(path) => Value::string(path.to_string_lossy(), span),
+            None => Value::nothing(span),
+        };
+
+        Value::record(
+            Record::from_iter([
+                ("path".to_string(), path),
+                (
+                    "kind".to_string(),
+                    Value::string(
+                        self.kind
+                            .as_str(),
+                        span,
+                    ),
+                ),
+                ("message".to_string(), Value::string(self.message, span)),
+                ("offset".to_string(), offset_to_value(self.offset, span)),
+            ]),
+            span,
+        )
+    }
+}
+
+impl From<LabeledError> for ProcessingError {
+    fn from(error: LabeledError) -> Self {
+        Self::from_labeled_error(error, None)
+    }
 }
 
-fn resolve_path(
-    filename: &str,
-    current_dir: Result<&Path, &ShellError>,
+fn offset_to_value(
+    offset: Option<u64>,
     span: Span,
-) -> Result<PathBuf, LabeledError> {
-    use std::path::Path;
+) -> Value {
+    match offset.and_then(|offset| i64::try_from(offset).ok()) {
+        Some(offset) => Value::int(offset, span),
+        None => Value::nothing(span),
+    }
+}
+
+fn partial_to_value(
+    partial: Option<PartialRead>,
+    span: Span,
+) -> Value {
+    let Some(partial) = partial else {
+        return Value::nothing(span);
+    };
 
-    let path = Path::new(filename);
+    Value::record(
+        Record::from_iter([
+            ("offset".to_string(), offset_to_value(partial.offset, span)),
+            ("reason".to_string(), Value::string(partial.reason, span)),
+            ("resynchronized".to_string(), Value::bool(partial.resynchronized, span)),
+        ]),
+        span,
+    )
+}
 
-    // If path is already absolute, return it as-is
-    if path.is_absolute() {
-        return Ok(path.to_path_buf());
+fn get_threads_flag(call: &nu_plugin::EvaluatedCall) -> Result<Option<NonZeroUsize>, LabeledError> {
+    let Some(threads) = call.get_flag_value("threads") else {
+        return Ok(None);
+    };
+
+    let span = threads.span();
+    let threads = threads.as_int()?;
+
+    usize::try_from(threads)
+        .ok()
+        .and_then(NonZeroUsize::new)
+        .map(Some)
+        .ok_or_else(|| LabeledError::new("Invalid number of threads").with_label("Expected a positive integer", span))
+}
+
+fn get_transfer_syntax_flag(call: &nu_plugin::EvaluatedCall) -> Result<Option<String>, LabeledError> {
+    let Some(transfer_syntax) = call.get_flag_value("transfer-syntax") else {
+        return Ok(None);
+    };
+
+    let span = transfer_syntax.span();
+    let uid = transfer_syntax.as_str()?;
+
+    if TransferSyntaxRegistry
+        .get(uid)
+        .is_none()
+    {
+        return Err(LabeledError::new("Unsupported transfer syntax").with_label("Expected a transfer syntax UID, e.g. 1.2.840.10008.1.2", span));
     }
 
-    // Path is relative, need to resolve against current working directory
-    let current_dir = current_dir.map_err(|e| {
-        LabeledError::new("Failed to get current working directory")
-            .with_label(format!("Cannot resolve relative path '{filename}'\n\nError: {e}"), span)
-    })?;
+    Ok(Some(uid.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversion_error_keeps_path() {
+        let error = LabeledError::new("Conversion failed").with_label("Unsupported value", Span::test_data());
+        let value = ProcessingError::from_labeled_error(error, Some(PathBuf::from("file.dcm"))).into_value(Span::test_data());
 
-    Ok(PathBuf::from(current_dir).join(filename))
+        assert_eq!(value.get_data_by_key("path"), Some(Value::test_string("file.dcm")));
+        assert_eq!(value.get_data_by_key("kind"), Some(Value::test_string("conversion")));
+        assert_eq!(value.get_data_by_key("message"), Some(Value::test_string("Unsupported value")));
+    }
 }
@@ -1,11 +1,21 @@
 use std::{
     fs::File,
-    io::{BufReader, Cursor, Read},
+    io::{self, BufReader, Cursor, Read},
     path::Path,
 };
 
-use dicom::object::{self as dicom_object, DefaultDicomObject};
-use snafu::{ResultExt, Snafu};
+use dicom::{
+    core::{DataDictionary, DataElementHeader, VR, dictionary::VirtualVr},
+    dictionary_std::{StandardDataDictionary, tags, uids},
+    encoding::{Codec, TransferSyntax, TransferSyntaxIndex, decode::DecodeFrom},
+    object::{self as dicom_object, DefaultDicomObject, FileMetaTableBuilder, InMemDicomObject},
+    transfer_syntax::TransferSyntaxRegistry,
+};
+use nu_protocol::Signals;
+use snafu::{OptionExt, ResultExt, Snafu};
+
+use crate::convert::get_string;
+use crate::dataset::read_dataset;
 
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub))]
@@ -14,46 +24,523 @@ pub enum Error {
     Io { source: std::io::Error },
 
     #[snafu(display("Could not parse Dicom object: {}", source))]
-    Dcm { source: dicom_object::ReadError },
+    Dcm {
+        #[snafu(source(from(dicom_object::ReadError, Box::new)))]
+        source: Box<dicom_object::ReadError>,
+        /// Offset of the data set (i.e. the end of file meta group) in the input, if known.
+        dataset_start: Option<u64>,
+    },
+
+    #[snafu(display("Could not parse Dicom data set: {}", source))]
+    Dataset {
+        source: dicom::parser::dataset::read::Error,
+        /// Offset of the data set in the input, if known.
+        dataset_start: Option<u64>,
+    },
+
+    #[snafu(display("Could not parse Dicom file meta group: {}", source))]
+    Meta { source: dicom_object::meta::Error },
+
+    #[snafu(display("Unsupported transfer syntax `{}`", uid))]
+    UnsupportedTransferSyntax { uid: String },
+
+    #[snafu(display("Could not determine the transfer syntax of a data set without file meta group"))]
+    UnknownTransferSyntax,
+
+    #[snafu(display("Not a Dicom data set, its first element is invalid: {}", source))]
+    InvalidFirstElement { source: Box<Error> },
+
+    #[snafu(display("Could not read archive: {}", source))]
+    Archive { source: zip::result::ZipError },
+
+    #[snafu(display("Reading Dicom object was interrupted"))]
+    Interrupted,
+}
+
+/// Number of bytes read ahead to detect the layout of the input, see `Layout::detect()`.
+pub const DETECTION_LEN: usize = 144;
+
+/// Category of an error, reported in `--error` records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The input couldn't be read, e.g. a missing file.
+    Io,
+    /// The input is not a valid DICOM object (e.g. no DICM marker, or malformed data set).
+    NotDicom,
+    /// The input ended before the DICOM object was fully read.
+    Truncated,
+    /// The file meta group is invalid or describes something that can't be read.
+    BadMeta,
+    /// The input was read but couldn't be converted to a nu value.
+    Conversion,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Io => "io",
+            ErrorKind::NotDicom => "not-dicom",
+            ErrorKind::Truncated => "truncated",
+            ErrorKind::BadMeta => "bad-meta",
+            ErrorKind::Conversion => "conversion",
+        }
+    }
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Io { source } if source.kind() == io::ErrorKind::UnexpectedEof => ErrorKind::Truncated,
+            Error::Io { .. } | Error::Archive { .. } | Error::Interrupted => ErrorKind::Io,
+            Error::Meta { source: dicom_object::meta::Error::NotDicom { .. } | dicom_object::meta::Error::ReadMagicCode { .. } } => {
+                ErrorKind::NotDicom
+            }
+            Error::Meta { source } => {
+                if is_truncated(source) {
+                    return ErrorKind::Truncated;
+                }
+                ErrorKind::BadMeta
+            }
+            Error::UnsupportedTransferSyntax { .. } => ErrorKind::BadMeta,
+            Error::UnknownTransferSyntax | Error::InvalidFirstElement { .. } => ErrorKind::NotDicom,
+            Error::Dataset { source, .. } => {
+                if is_truncated(source) {
+                    return ErrorKind::Truncated;
+                }
+                ErrorKind::NotDicom
+            }
+            Error::Dcm { source, .. } => {
+                if is_truncated(source.as_ref()) {
+                    return ErrorKind::Truncated;
+                }
+
+                match source.as_ref() {
+                    // the DICM marker couldn't be read, the input may be short but it's not DICOM either way
+                    dicom_object::ReadError::ParseMetaDataSet {
+                        source: dicom_object::meta::Error::NotDicom { .. } | dicom_object::meta::Error::ReadMagicCode { .. },
+                    } => ErrorKind::NotDicom,
+                    dicom_object::ReadError::ParseMetaDataSet { .. }
+                    | dicom_object::ReadError::ReadUnrecognizedTransferSyntax { .. }
+                    | dicom_object::ReadError::ReadUnsupportedTransferSyntax { .. }
+                    | dicom_object::ReadError::ReadUnsupportedTransferSyntaxWithSuggestion { .. } => ErrorKind::BadMeta,
+                    dicom_object::ReadError::OpenFile { .. } | dicom_object::ReadError::ReadFile { .. } => ErrorKind::Io,
+                    _ => ErrorKind::NotDicom,
+                }
+            }
+        }
+    }
+
+    /// Byte offset in the input where parsing failed, if known.
+    pub fn offset(&self) -> Option<u64> {
+        let (position, dataset_start) = match self {
+            Error::Dcm { source, dataset_start } => (parser_error_position(source.as_ref())?, dataset_start),
+            Error::Dataset { source, dataset_start } => (parser_error_position(source)?, dataset_start),
+            Error::InvalidFirstElement { source } => return source.offset(),
+            _ => return None,
+        };
+
+        // positions reported by the parser are relative to the start of the data set
+
+        Some(dataset_start.unwrap_or(0) + position)
+    }
+}
+
+/// Returns the data set position reported by the parser anywhere in the error chain.
+pub fn parser_error_position(error: &(dyn std::error::Error + 'static)) -> Option<u64> {
+    use dicom::parser::dataset::read::Error as DataSetError;
+    use dicom::parser::stateful::decode::Error as DecodeError;
+
+    error_chain(error).find_map(|e| {
+        if let Some(e) = e.downcast_ref::<DecodeError>() {
+            return match e {
+                DecodeError::NonPrimitiveType { position, .. }
+                | DecodeError::UndefinedValueLength { position, .. }
+                | DecodeError::DecodeElementHeader { position, .. }
+                | DecodeError::DecodeItemHeader { position, .. }
+                | DecodeError::DecodeText { position, .. }
+                | DecodeError::ReadValueData { position, .. }
+                | DecodeError::SeekReader { position, .. }
+                | DecodeError::DeserializeValue { position, .. }
+                | DecodeError::ReadInt { position, .. }
+                | DecodeError::ReadFloat { position, .. }
+                | DecodeError::InvalidDateValue { position, .. }
+                | DecodeError::InvalidTimeValue { position, .. }
+                | DecodeError::InvalidDateTimeValue { position, .. } => Some(*position),
+                _ => None,
+            };
+        }
+
+        match e.downcast_ref::<DataSetError>()? {
+            DataSetError::InconsistentSequenceEnd { bytes_read, .. }
+            | DataSetError::UnexpectedItemHeader { bytes_read, .. }
+            | DataSetError::InvalidElementLength { bytes_read, .. }
+            | DataSetError::InvalidItemLength { bytes_read, .. } => Some(*bytes_read),
+            _ => None,
+        }
+    })
+}
+
+/// Returns true if the error was caused by an unexpected end of input.
+pub fn is_truncated(error: &(dyn std::error::Error + 'static)) -> bool {
+    error_chain(error).any(|e| {
+        e.downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::UnexpectedEof)
+    })
+}
+
+/// Iterates over the error and all its sources.
+pub fn error_chain<'a>(error: &'a (dyn std::error::Error + 'static)) -> impl Iterator<Item = &'a (dyn std::error::Error + 'static)> {
+    std::iter::successors(Some(error), |e| e.source())
+}
+
+/// Returns the data set offset relative to `buf` which must start with the "DICM" marker, followed by the File Meta
+/// Information Group Length element (always Explicit VR Little Endian).
+fn dataset_start_after_magic(buf: &[u8]) -> Option<u64> {
+    let group_length_header = buf.get(4..12)?;
+    if group_length_header != [0x02, 0x00, 0x00, 0x00, b'U', b'L', 0x04, 0x00] {
+        return None;
+    }
+
+    let group_length = u32::from_le_bytes(
+        buf.get(12..16)?
+            .try_into()
+            .ok()?,
+    );
+
+    Some(16 + u64::from(group_length))
+}
+
+/// Reader adapter that fails as soon as nu signals an interrupt (e.g. Ctrl-C).
+pub struct InterruptibleReader<R> {
+    inner: R,
+    signals: Signals,
+}
+
+impl<R: Read> InterruptibleReader<R> {
+    pub fn new(
+        inner: R,
+        signals: Signals,
+    ) -> Self {
+        Self { inner, signals }
+    }
+}
+
+impl<R: Read> Read for InterruptibleReader<R> {
+    fn read(
+        &mut self,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        // don't use ErrorKind::Interrupted, `read_exact()` and friends would retry
+        if self
+            .signals
+            .interrupted()
+        {
+            return Err(io::Error::other("interrupted"));
+        }
+
+        self.inner
+            .read(buf)
+    }
+}
+
+/// Reads `input` using `read` (e.g. `read_dcm_stream()`) but stops reading when `signals` is interrupted.
+pub fn read_interruptible<F: Read, T>(
+    input: F,
+    signals: &Signals,
+    read: impl FnOnce(InterruptibleReader<F>) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let result = read(InterruptibleReader::new(input, signals.clone()));
+
+    // the underlying error is just a consequence of the interrupt
+    if result.is_err() && signals.interrupted() {
+        return InterruptedSnafu.fail();
+    }
+
+    result
 }
 
-pub fn read_dcm_file<P: AsRef<Path>>(path: P) -> Result<DefaultDicomObject, Error> {
+/// Reads a DICOM file, see `read_dcm_stream()`.
+pub fn read_dcm_file<P: AsRef<Path>>(
+    path: P,
+    transfer_syntax: Option<&str>,
+) -> Result<DefaultDicomObject, Error> {
     let path = path.as_ref();
     let input = BufReader::new(File::open(path).context(IoSnafu)?);
-    read_dcm_stream(input)
+    read_dcm_stream(input, transfer_syntax)
+}
+
+/// Reads a DICOM object from `input`, up to the pixel data.
+///
+/// Besides regular DICOM files, this accepts files without the preamble and/or the "DICM" marker, and raw data sets
+/// without the file meta group (e.g. dumped from DIMSE). Raw data sets are read using `transfer_syntax` (UID) if
+/// given, otherwise the transfer syntax is guessed from the first element. Their file meta group is synthesized.
+pub fn read_dcm_stream<F: Read>(
+    input: F,
+    transfer_syntax: Option<&str>,
+) -> Result<DefaultDicomObject, Error> {
+    read_dcm_stream_until(input, transfer_syntax, false)
+}
+
+/// Reads a DICOM object from `input` like `read_dcm_stream()`, but including the pixel data and anything after it.
+pub fn read_dcm_stream_with_pixel_data<F: Read>(
+    input: F,
+    transfer_syntax: Option<&str>,
+) -> Result<DefaultDicomObject, Error> {
+    read_dcm_stream_until(input, transfer_syntax, true)
 }
 
-pub fn read_dcm_stream<F: Read>(mut input: F) -> Result<DefaultDicomObject, Error> {
-    // Read the first 132 bytes into a temporary buffer to check for the preamble.
-    let mut buf = Vec::with_capacity(132);
+fn read_dcm_stream_until<F: Read>(
+    mut input: F,
+    transfer_syntax: Option<&str>,
+    with_pixel_data: bool,
+) -> Result<DefaultDicomObject, Error> {
+    // Read the first bytes into a temporary buffer to check for the preamble. The extra 12 bytes
+    // contain the meta group length which is used to report error offsets.
+    let mut buf = Vec::with_capacity(DETECTION_LEN);
     input
         .by_ref()
-        .take(132)
+        .take(DETECTION_LEN as u64)
         .read_to_end(&mut buf)
         .context(IoSnafu)?;
 
-    if buf.len() == 132 && &buf[128..132] == b"DICM" {
-        // "DICM" marker found. The data to parse starts with these 4 bytes.
-        // We create a new reader by chaining the "DICM" marker from our buffer
-        // with the rest of the original input stream.
-        let reader = Cursor::new(&buf[128..]).chain(input);
-
-        // Use the default OpenFileOptions to parse the File Meta Information.
-        dicom_object::OpenFileOptions::new()
-            .read_until(dicom::dictionary_std::tags::PIXEL_DATA)
-            .read_preamble(dicom_object::file::ReadPreamble::Never)
-            .from_reader(reader)
-            .context(DcmSnafu)
+    match Layout::detect(&buf, transfer_syntax) {
+        Layout::Preamble => {
+            let dataset_start = dataset_start_after_magic(&buf[128..]).map(|start| start + 128);
+
+            // "DICM" marker found. The data to parse starts with these 4 bytes.
+            // We create a new reader by chaining the "DICM" marker from our buffer
+            // with the rest of the original input stream.
+            let reader = Cursor::new(&buf[128..]).chain(input);
+            read_file_object(reader, dataset_start, with_pixel_data)
+        }
+        Layout::Magic => {
+            let dataset_start = dataset_start_after_magic(&buf);
+            let reader = Cursor::new(buf).chain(input);
+            read_file_object(reader, dataset_start, with_pixel_data)
+        }
+        Layout::MetaWithoutMagic => {
+            // The file meta group is there, only the "DICM" marker is missing. Add it so that the meta group can
+            // be parsed, offsets are reported relative to the original input.
+            let dataset_start = dataset_start_after_magic(&[b"DICM".as_slice(), &buf].concat()).map(|start| start - 4);
+            let reader = Cursor::new(b"DICM")
+                .chain(Cursor::new(buf))
+                .chain(input);
+            read_file_object(reader, dataset_start, with_pixel_data)
+        }
+        Layout::Raw => {
+            let ts = raw_dataset_transfer_syntax(&buf, transfer_syntax)?;
+            let first_element_end = first_element_header(&buf, ts).map(|(header, header_len)| {
+                header
+                    .len
+                    .get()
+                    .map_or(u64::MAX, |len| (header_len as u64) + u64::from(len))
+            });
+
+            let reader = Cursor::new(buf).chain(input);
+            let obj = if with_pixel_data {
+                InMemDicomObject::read_dataset_with_ts(reader, ts).context(DcmSnafu { dataset_start: Some(0) })
+            } else {
+                read_dataset(reader, ts)
+            };
+
+            // failing within the first element means that the input isn't a data set in the first place
+            let obj = obj.map_err(|error| match (error.offset(), first_element_end) {
+                (Some(offset), Some(end)) if offset < end => Error::InvalidFirstElement { source: Box::new(error) },
+                _ => error,
+            })?;
+            with_synthesized_meta(obj, ts.uid())
+        }
+    }
+}
+
+/// Parses a DICOM file starting with the "DICM" marker.
+fn read_file_object<R: Read>(
+    reader: R,
+    dataset_start: Option<u64>,
+    with_pixel_data: bool,
+) -> Result<DefaultDicomObject, Error> {
+    let options = dicom_object::OpenFileOptions::new().read_preamble(dicom_object::file::ReadPreamble::Never);
+    let options = if with_pixel_data {
+        options
     } else {
-        // No "DICM" marker. The entire buffer is part of the dataset.
-        // Create a reader from the buffer and chain it with the rest of the stream.
-        let reader = Cursor::new(buf).chain(input);
+        options.read_until(tags::PIXEL_DATA)
+    };
+
+    options
+        .from_reader(reader)
+        .context(DcmSnafu { dataset_start })
+}
+
+/// How a DICOM object is laid out at the start of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// 128 byte preamble followed by the "DICM" marker and the file meta group.
+    Preamble,
+    /// "DICM" marker followed by the file meta group, or something that isn't DICOM at all.
+    Magic,
+    /// File meta group without the "DICM" marker.
+    MetaWithoutMagic,
+    /// Data set without the file meta group.
+    Raw,
+}
+
+impl Layout {
+    /// Detects the layout from the first bytes (at least 132 if available) of the input.
+    ///
+    /// With an explicit `transfer_syntax`, anything without the file meta group is a raw data set. Its first bytes
+    /// can't always be recognized, e.g. when the data set is deflated.
+    pub fn detect(
+        buf: &[u8],
+        transfer_syntax: Option<&str>,
+    ) -> Layout {
+        if buf.get(128..132) == Some(b"DICM") {
+            return Layout::Preamble;
+        }
+
+        if buf.starts_with(b"DICM") {
+            return Layout::Magic;
+        }
+
+        // the file meta group is always Explicit VR Little Endian
+        if buf.starts_with(&[0x02, 0x00])
+            && buf
+                .get(4..6)
+                .is_some_and(|vr| VR::from_binary([vr[0], vr[1]]).is_some())
+        {
+            return Layout::MetaWithoutMagic;
+        }
+
+        if transfer_syntax.is_some() || guess_transfer_syntax(buf).is_some() {
+            return Layout::Raw;
+        }
+
+        // let the file meta parser report what's wrong
+        Layout::Magic
+    }
+
+    /// Returns true if the first bytes of the input are recognized as a DICOM object, i.e. `detect()` doesn't fall back
+    /// to the file meta parser.
+    pub fn is_recognized(
+        buf: &[u8],
+        transfer_syntax: Option<&str>,
+    ) -> bool {
+        Layout::detect(buf, transfer_syntax) != Layout::Magic || buf.starts_with(b"DICM")
+    }
+}
+
+/// Returns the transfer syntax to read a raw data set with, either `uid` or a guess based on the first element.
+pub fn raw_dataset_transfer_syntax(
+    buf: &[u8],
+    uid: Option<&str>,
+) -> Result<&'static TransferSyntax, Error> {
+    let uid = match uid {
+        Some(uid) => uid,
+        None => guess_transfer_syntax(buf).context(UnknownTransferSyntaxSnafu)?,
+    };
+
+    TransferSyntaxRegistry
+        .get(uid)
+        .context(UnsupportedTransferSyntaxSnafu { uid })
+}
+
+/// Guesses the transfer syntax of a raw data set from its first element header.
+///
+/// Explicit VR is detected by a valid VR following the tag. Endianness is chosen so that the first group number
+/// is the smaller one, which must be an even (i.e. non-private) group after the file meta group. Implicit VR Big
+/// Endian doesn't exist. The first element must then be plausible, see `is_plausible_first_element()`.
+pub fn guess_transfer_syntax(buf: &[u8]) -> Option<&'static str> {
+    let header = buf.get(0..8)?;
+
+    let group_le = u16::from_le_bytes([header[0], header[1]]);
+    let group_be = u16::from_be_bytes([header[0], header[1]]);
+    let little_endian = group_le <= group_be;
+    let group = group_le.min(group_be);
+
+    if group % 2 != 0 || !(0x0004..=0x7FE0).contains(&group) {
+        return None;
+    }
+
+    let explicit_vr = VR::from_binary([header[4], header[5]]).is_some();
+
+    let uid = match (explicit_vr, little_endian) {
+        (true, true) => uids::EXPLICIT_VR_LITTLE_ENDIAN,
+        // retired, but still found in old archives
+        #[allow(deprecated)]
+        (true, false) => uids::EXPLICIT_VR_BIG_ENDIAN,
+        (false, true) => uids::IMPLICIT_VR_LITTLE_ENDIAN,
+        (false, false) => return None,
+    };
+
+    let ts = TransferSyntaxRegistry.get(uid)?;
+    let (header, header_len) = first_element_header(buf, ts)?;
+
+    is_plausible_first_element(&header, header_len, explicit_vr, buf).then_some(uid)
+}
+
+/// Decodes the header of the first element of a raw data set, returning it with its length in bytes. None if it
+/// can't be decoded, or can't be decoded without adapting the input (i.e. deflated data sets).
+fn first_element_header(
+    buf: &[u8],
+    ts: &TransferSyntax,
+) -> Option<(DataElementHeader, usize)> {
+    if matches!(ts.codec(), Codec::Dataset(..)) {
+        return None;
+    }
+
+    ts.decoder_for::<&[u8]>()?
+        .decode_header(&mut &buf[..])
+        .ok()
+}
+
+/// Checks that the first element of a raw data set looks like a standard attribute, so that arbitrary files (e.g. text)
+/// aren't mistaken for data sets: its tag must be in the dictionary, an explicit VR must match the dictionary (or be
+/// UN), only sequences and binary values may have an undefined length, and the value must fit in `buf` if that holds
+/// the whole input.
+fn is_plausible_first_element(
+    header: &DataElementHeader,
+    header_len: usize,
+    explicit_vr: bool,
+    buf: &[u8],
+) -> bool {
+    let Some(entry) = StandardDataDictionary.by_tag(header.tag) else {
+        return false;
+    };
 
-        // Attempt to parse as a dataset without a preamble.
-        dicom_object::OpenFileOptions::new()
-            .read_until(dicom::dictionary_std::tags::PIXEL_DATA)
-            .read_preamble(dicom_object::file::ReadPreamble::Never)
-            .from_reader(reader)
-            .context(DcmSnafu)
+    if explicit_vr && header.vr != VR::UN {
+        let matches_dictionary = match entry.vr {
+            VirtualVr::Exact(vr) => header.vr == vr,
+            VirtualVr::Xs => matches!(header.vr, VR::US | VR::SS),
+            VirtualVr::Ox | VirtualVr::Px => matches!(header.vr, VR::OB | VR::OW),
+            VirtualVr::Lt => matches!(header.vr, VR::US | VR::OW),
+            _ => true,
+        };
+        if !matches_dictionary {
+            return false;
+        }
     }
+
+    match header
+        .len
+        .get()
+    {
+        None => matches!(header.vr, VR::SQ | VR::UN | VR::OB | VR::OW),
+        Some(len) => len % 2 == 0 && (buf.len() >= DETECTION_LEN || header_len + len as usize <= buf.len()),
+    }
+}
+
+/// Adds a file meta group to a raw data set, using SOP Class and Instance UIDs from the data set (if present).
+pub fn with_synthesized_meta(
+    obj: InMemDicomObject,
+    transfer_syntax: &str,
+) -> Result<DefaultDicomObject, Error> {
+    let get_uid = |tag| get_string(&obj, tag).unwrap_or_default();
+
+    let meta = FileMetaTableBuilder::new()
+        .transfer_syntax(transfer_syntax)
+        .media_storage_sop_class_uid(get_uid(tags::SOP_CLASS_UID))
+        .media_storage_sop_instance_uid(get_uid(tags::SOP_INSTANCE_UID))
+        .build()
+        .context(MetaSnafu)?;
+
+    Ok(obj.with_exact_meta(meta))
 }
@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use dicom::core::{PrimitiveValue, Tag};
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use dicom_pixeldata::PixelDecoder;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Record, Signature, Span, Value};
+use snafu::{OptionExt, Snafu, ensure};
+
+use crate::convert::{bytes, get_float, get_int, get_string};
+use crate::geometry::ImageGeometry;
+use crate::input::{DicomSource, collect_input, into_output, is_list_input};
+use crate::multiframe::number_of_frames;
+use crate::plugin::DcmPlugin;
+use crate::reader::read_dcm_stream_with_pixel_data;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Not an RT Dose, DoseUnits is missing"))]
+    NotDose,
+
+    #[snafu(display("GridFrameOffsetVector has {} values, expected one per frame ({})", offsets, frames))]
+    InvalidGridFrameOffsetVector { frames: usize, offsets: usize },
+
+    #[snafu(display("{} is missing", name))]
+    MissingAttribute { name: &'static str },
+
+    #[snafu(display("PixelData has {} bytes, expected {}", actual, expected))]
+    InvalidPixelDataLength { expected: usize, actual: usize },
+}
+
+/// Dose statistics of a frame, in DoseUnits.
+#[derive(Debug, Clone, Copy)]
+pub struct DoseStatistics {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl DoseStatistics {
+    /// Statistics of the dose values, none without values.
+    pub fn new(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let min = values
+            .iter()
+            .copied()
+            .fold(f64::INFINITY, f64::min);
+        let max = values
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let mean = values
+            .iter()
+            .sum::<f64>()
+            / values.len() as f64;
+
+        Some(Self { min, max, mean })
+    }
+}
+
+/// `dcm rtdose` command, summarises the dose grid of RT Dose objects.
+pub struct DcmRtdoseCommand;
+
+impl PluginCommand for DcmRtdoseCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm rtdose"
+    }
+
+    fn description(&self) -> &str {
+        "Summarise the dose grid geometry, scaling and per-frame dose statistics of DICOM RT Dose objects."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "rtdose".to_string(), "radiotherapy".to_string(), "dose grid".to_string()])
+            .extra_description(
+                "Returns a record per dose object, with the `SOPInstanceUID`, DoseUnits, DoseType, DoseSummationType, DoseGridScaling, \
+                 the `referenced_plan`, the grid geometry (Rows, Columns, NumberOfFrames, ImagePositionPatient, ImageOrientationPatient, \
+                 PixelSpacing and GridFrameOffsetVector), the `min`, `max` and `mean` dose of the grid and a `frames` table with the \
+                 `offset` and `position` (x, y, z in patient coordinates) of each frame and its `min`, `max` and `mean` dose. Doses are \
+                 stored values multiplied by DoseGridScaling (1 if missing), in DoseUnits. Grids may have 16 or 32 bits allocated. Objects \
+                 without pixel data (e.g. only DVHs) have no statistics and no frames.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show the maximum dose and the plan of a dose grid",
+                example: "\"rtdose.dcm\" | dcm rtdose | select DoseUnits max referenced_plan",
+                result: None,
+            },
+            Example {
+                description: "Find the frame with the highest dose",
+                example: "\"rtdose.dcm\" | dcm rtdose | get frames | sort-by max | last",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let is_list = is_list_input(&input);
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let bytes = source
+                .bytes()
+                .map_err(|e| source.error(&e, span))?;
+            let obj = read_dcm_stream_with_pixel_data(Cursor::new(&bytes[..]), None).map_err(|e| source.error(&e, span))?;
+
+            let offsets = grid_frame_offsets(&obj).map_err(|e| source.labeled_error("Invalid RT Dose", &e, span))?;
+            let scaling = get_float(&obj, tags::DOSE_GRID_SCALING).unwrap_or(1.0);
+
+            let mut frames = Vec::new();
+            let native_32bit = obj
+                .get(tags::PIXEL_DATA)
+                .and_then(|e| {
+                    e.value()
+                        .primitive()
+                })
+                .filter(|_| get_int::<u16>(&obj, tags::BITS_ALLOCATED) == Some(32));
+
+            if let Some(data) = native_32bit {
+                // the pixel data decoder only supports 8 and 16 bits allocated
+                frames = native_32bit_frames(&obj, data, offsets.len(), scaling).map_err(|e| source.labeled_error("Invalid RT Dose", &e, span))?;
+            } else if obj
+                .get(tags::PIXEL_DATA)
+                .is_some()
+            {
+                let decoded = obj
+                    .decode_pixel_data()
+                    .map_err(|e| source.labeled_error("Failed to decode pixel data", &e, span))?;
+
+                for frame in 0..offsets.len() {
+                    let doses = decoded
+                        .to_vec_frame::<f64>(frame as u32)
+                        .map_err(|e| source.labeled_error("Failed to decode pixel data", &e, span))?
+                        .into_iter()
+                        .map(|value| value * scaling)
+                        .collect::<Vec<_>>();
+                    frames.push(doses);
+                }
+            }
+
+            output.push(dose_to_value(&obj, &offsets, &frames, span));
+        }
+
+        Ok(into_output(output, is_list, call.head))
+    }
+}
+
+/// Doses of each frame of native pixel data with 32 bits allocated, unsigned or signed as per PixelRepresentation.
+fn native_32bit_frames(
+    obj: &InMemDicomObject,
+    data: &PrimitiveValue,
+    frames: usize,
+    scaling: f64,
+) -> Result<Vec<Vec<f64>>, Error> {
+    let rows: usize = get_int(obj, tags::ROWS).context(MissingAttributeSnafu { name: "Rows" })?;
+    let columns: usize = get_int(obj, tags::COLUMNS).context(MissingAttributeSnafu { name: "Columns" })?;
+    let signed = get_int::<u16>(obj, tags::PIXEL_REPRESENTATION) == Some(1);
+
+    let bytes = bytes(data);
+    let frame_len = rows * columns * 4;
+    ensure!(frame_len > 0 && bytes.len() >= frames * frame_len, InvalidPixelDataLengthSnafu { expected: frames * frame_len, actual: bytes.len() });
+
+    Ok(bytes
+        .chunks_exact(frame_len)
+        .take(frames)
+        .map(|frame| {
+            frame
+                .chunks_exact(4)
+                .map(|voxel| {
+                    let voxel = [voxel[0], voxel[1], voxel[2], voxel[3]];
+                    let value = if signed {
+                        i32::from_le_bytes(voxel) as f64
+                    } else {
+                        u32::from_le_bytes(voxel) as f64
+                    };
+                    value * scaling
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// GridFrameOffsetVector, checked against the number of frames. Single-frame grids may omit it.
+pub fn grid_frame_offsets(obj: &InMemDicomObject) -> Result<Vec<f64>, Error> {
+    obj.get(tags::DOSE_UNITS)
+        .context(NotDoseSnafu)?;
+
+    let frames = number_of_frames(obj);
+    let offsets = obj
+        .get(tags::GRID_FRAME_OFFSET_VECTOR)
+        .and_then(|e| {
+            e.to_multi_float64()
+                .ok()
+        })
+        .unwrap_or_else(|| vec![0.0]);
+    ensure!(offsets.len() == frames, InvalidGridFrameOffsetVectorSnafu { frames, offsets: offsets.len() });
+
+    Ok(offsets)
+}
+
+fn dose_to_value(
+    obj: &InMemDicomObject,
+    offsets: &[f64],
+    frames: &[Vec<f64>],
+    span: Span,
+) -> Value {
+    let string = |tag: Tag| {
+        get_string(obj, tag)
+            .map(|s| Value::string(s, span))
+            .unwrap_or_else(|| Value::nothing(span))
+    };
+    let int = |tag: Tag| {
+        obj.get(tag)
+            .and_then(|e| {
+                e.to_int::<i64>()
+                    .ok()
+            })
+            .map_or_else(|| Value::nothing(span), |i| Value::int(i, span))
+    };
+    let floats = |values: &[f64]| {
+        Value::list(
+            values
+                .iter()
+                .map(|v| Value::float(*v, span))
+                .collect(),
+            span,
+        )
+    };
+    let multi_float = |tag: Tag| {
+        obj.get(tag)
+            .and_then(|e| {
+                e.to_multi_float64()
+                    .ok()
+            })
+            .map_or_else(|| Value::nothing(span), |values| floats(&values))
+    };
+    let push_statistics = |record: &mut Record, statistics: Option<DoseStatistics>| {
+        let float = |f: Option<f64>| f.map_or_else(|| Value::nothing(span), |f| Value::float(f, span));
+        record.push("min", float(statistics.map(|s| s.min)));
+        record.push("max", float(statistics.map(|s| s.max)));
+        record.push("mean", float(statistics.map(|s| s.mean)));
+    };
+
+    // frames are stacked along the normal, GridFrameOffsetVector being relative to the first frame or absolute
+    let geometry = ImageGeometry::from_attributes(obj, &BTreeMap::new());
+    let first_offset = offsets
+        .first()
+        .copied()
+        .unwrap_or_default();
+
+    let frame_values = frames
+        .iter()
+        .zip(offsets)
+        .enumerate()
+        .map(|(index, (doses, offset))| {
+            let position = geometry
+                .as_ref()
+                .map(|geometry| {
+                    let normal = geometry.normal();
+                    let distance = offset - first_offset;
+                    [0, 1, 2].map(|axis| geometry.position[axis] + normal[axis] * distance)
+                });
+
+            let mut record = Record::new();
+            record.push("frame", Value::int(index as i64 + 1, span));
+            record.push("offset", Value::float(*offset, span));
+            record.push("position", position.map_or_else(|| Value::nothing(span), |position| floats(&position)));
+            push_statistics(&mut record, DoseStatistics::new(doses));
+            Value::record(record, span)
+        })
+        .collect();
+
+    let referenced_plan = obj
+        .get(tags::REFERENCED_RT_PLAN_SEQUENCE)
+        .and_then(|e| e.items())
+        .and_then(|items| items.first())
+        .and_then(|item| get_string(item, tags::REFERENCED_SOP_INSTANCE_UID))
+        .map_or_else(|| Value::nothing(span), |uid| Value::string(uid, span));
+
+    let mut record = Record::new();
+    record.push("SOPInstanceUID", string(tags::SOP_INSTANCE_UID));
+    record.push("DoseUnits", string(tags::DOSE_UNITS));
+    record.push("DoseType", string(tags::DOSE_TYPE));
+    record.push("DoseSummationType", string(tags::DOSE_SUMMATION_TYPE));
+    record
+        .push("DoseGridScaling", get_float(obj, tags::DOSE_GRID_SCALING).map_or_else(|| Value::nothing(span), |scaling| Value::float(scaling, span)));
+    record.push("referenced_plan", referenced_plan);
+    record.push("Rows", int(tags::ROWS));
+    record.push("Columns", int(tags::COLUMNS));
+    record.push("NumberOfFrames", int(tags::NUMBER_OF_FRAMES));
+    record.push("ImagePositionPatient", multi_float(tags::IMAGE_POSITION_PATIENT));
+    record.push("ImageOrientationPatient", multi_float(tags::IMAGE_ORIENTATION_PATIENT));
+    record.push("PixelSpacing", multi_float(tags::PIXEL_SPACING));
+    record.push("GridFrameOffsetVector", floats(offsets));
+    push_statistics(&mut record, DoseStatistics::new(&frames.concat()));
+    record.push("frames", Value::list(frame_values, span));
+
+    Value::record(record, span)
+}
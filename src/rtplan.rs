@@ -0,0 +1,296 @@
+use std::path::PathBuf;
+
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Record, Signature, Span, Value};
+use snafu::{Snafu, ensure};
+
+use crate::convert::{get_float, get_floats, get_int, get_string, items};
+use crate::input::{DicomSource, collect_input, into_output, is_list_input};
+use crate::plugin::DcmPlugin;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Not an RT Plan, BeamSequence, IonBeamSequence and FractionGroupSequence are missing"))]
+    NotPlan,
+}
+
+/// Value representation of a column.
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Int,
+    Float,
+    Floats,
+    String,
+}
+
+type Column = (&'static str, Tag, Kind);
+
+const PLAN_COLUMNS: &[Column] = &[
+    ("RTPlanLabel", tags::RT_PLAN_LABEL, Kind::String),
+    ("RTPlanName", tags::RT_PLAN_NAME, Kind::String),
+    ("RTPlanDate", tags::RT_PLAN_DATE, Kind::String),
+    ("RTPlanGeometry", tags::RT_PLAN_GEOMETRY, Kind::String),
+];
+
+const FRACTION_GROUP_COLUMNS: &[Column] = &[
+    ("FractionGroupNumber", tags::FRACTION_GROUP_NUMBER, Kind::Int),
+    ("NumberOfFractionsPlanned", tags::NUMBER_OF_FRACTIONS_PLANNED, Kind::Int),
+    ("NumberOfBeams", tags::NUMBER_OF_BEAMS, Kind::Int),
+    ("NumberOfBrachyApplicationSetups", tags::NUMBER_OF_BRACHY_APPLICATION_SETUPS, Kind::Int),
+];
+
+const REFERENCED_BEAM_COLUMNS: &[Column] = &[
+    ("ReferencedBeamNumber", tags::REFERENCED_BEAM_NUMBER, Kind::Int),
+    ("BeamMeterset", tags::BEAM_METERSET, Kind::Float),
+    ("BeamDose", tags::BEAM_DOSE, Kind::Float),
+];
+
+const BEAM_COLUMNS: &[Column] = &[
+    ("BeamNumber", tags::BEAM_NUMBER, Kind::Int),
+    ("BeamName", tags::BEAM_NAME, Kind::String),
+    ("BeamDescription", tags::BEAM_DESCRIPTION, Kind::String),
+    ("BeamType", tags::BEAM_TYPE, Kind::String),
+    ("RadiationType", tags::RADIATION_TYPE, Kind::String),
+    ("TreatmentMachineName", tags::TREATMENT_MACHINE_NAME, Kind::String),
+    ("TreatmentDeliveryType", tags::TREATMENT_DELIVERY_TYPE, Kind::String),
+    ("PrimaryDosimeterUnit", tags::PRIMARY_DOSIMETER_UNIT, Kind::String),
+    ("NumberOfControlPoints", tags::NUMBER_OF_CONTROL_POINTS, Kind::Int),
+    ("FinalCumulativeMetersetWeight", tags::FINAL_CUMULATIVE_METERSET_WEIGHT, Kind::Float),
+];
+
+/// Control point attributes that are only present in the first control point and in those that change them.
+const CONTROL_POINT_COLUMNS: &[Column] = &[
+    ("NominalBeamEnergy", tags::NOMINAL_BEAM_ENERGY, Kind::Float),
+    ("DoseRateSet", tags::DOSE_RATE_SET, Kind::Float),
+    ("GantryAngle", tags::GANTRY_ANGLE, Kind::Float),
+    ("GantryRotationDirection", tags::GANTRY_ROTATION_DIRECTION, Kind::String),
+    ("BeamLimitingDeviceAngle", tags::BEAM_LIMITING_DEVICE_ANGLE, Kind::Float),
+    ("PatientSupportAngle", tags::PATIENT_SUPPORT_ANGLE, Kind::Float),
+    ("IsocenterPosition", tags::ISOCENTER_POSITION, Kind::Floats),
+];
+
+/// `dcm rtplan` command, summarises the fraction groups, beams and control points of RT Plans.
+pub struct DcmRtplanCommand;
+
+impl PluginCommand for DcmRtplanCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm rtplan"
+    }
+
+    fn description(&self) -> &str {
+        "Summarise the fraction groups, beams and control points of DICOM RT Plans."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec![
+                "dicom".to_string(),
+                "rtplan".to_string(),
+                "radiotherapy".to_string(),
+                "beam".to_string(),
+                "control point".to_string(),
+                "meterset".to_string(),
+            ])
+            .extra_description(
+                "Returns a record per plan, with the `SOPInstanceUID`, RTPlanLabel, RTPlanName, RTPlanDate, RTPlanGeometry, the \
+                 `referenced_structure_set`, the `fraction_groups` table (with the BeamMeterset and BeamDose of their `beams`) and the \
+                 `beams` table of BeamSequence or IonBeamSequence. Each beam has its BeamMeterset from the first fraction group \
+                 referencing it and a `control_points` table with ControlPointIndex, CumulativeMetersetWeight, the `meterset` delivered \
+                 up to the control point, NominalBeamEnergy, DoseRateSet, GantryAngle, GantryRotationDirection, BeamLimitingDeviceAngle \
+                 (collimator), PatientSupportAngle (couch) and IsocenterPosition. Control point attributes that are not repeated keep \
+                 the value of the previous control point.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "List the beams of a plan",
+                example: "\"rtplan.dcm\" | dcm rtplan | get beams | select BeamName BeamType BeamMeterset NumberOfControlPoints",
+                result: None,
+            },
+            Example {
+                description: "Show the gantry angles and metersets of the first beam",
+                example: "\"rtplan.dcm\" | dcm rtplan | get beams.0.control_points | select ControlPointIndex GantryAngle meterset",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let is_list = is_list_input(&input);
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let obj = source
+                .read()
+                .map_err(|e| source.error(&e, span))?;
+
+            output.push(plan_to_value(&obj, span).map_err(|e| source.labeled_error("Invalid RT Plan", &e, span))?);
+        }
+
+        Ok(into_output(output, is_list, call.head))
+    }
+}
+
+/// Summarises the plan in `obj` as a record of plan attributes, fraction groups and beams.
+pub fn plan_to_value(
+    obj: &InMemDicomObject,
+    span: Span,
+) -> Result<Value, Error> {
+    let beams = match items(obj, tags::BEAM_SEQUENCE) {
+        [] => items(obj, tags::ION_BEAM_SEQUENCE),
+        beams => beams,
+    };
+    let fraction_groups = items(obj, tags::FRACTION_GROUP_SEQUENCE);
+    ensure!(!beams.is_empty() || !fraction_groups.is_empty(), NotPlanSnafu);
+
+    let mut record = Record::new();
+    record.push("SOPInstanceUID", get_value(obj, tags::SOP_INSTANCE_UID, Kind::String, span));
+    push_columns(&mut record, obj, PLAN_COLUMNS, span);
+    record.push(
+        "referenced_structure_set",
+        items(obj, tags::REFERENCED_STRUCTURE_SET_SEQUENCE)
+            .first()
+            .map_or_else(|| Value::nothing(span), |item| get_value(item, tags::REFERENCED_SOP_INSTANCE_UID, Kind::String, span)),
+    );
+
+    let fraction_groups = fraction_groups
+        .iter()
+        .map(|group| {
+            let referenced_beams = items(group, tags::REFERENCED_BEAM_SEQUENCE)
+                .iter()
+                .map(|beam| {
+                    let mut record = Record::new();
+                    push_columns(&mut record, beam, REFERENCED_BEAM_COLUMNS, span);
+                    Value::record(record, span)
+                })
+                .collect();
+
+            let mut record = Record::new();
+            push_columns(&mut record, group, FRACTION_GROUP_COLUMNS, span);
+            record.push("beams", Value::list(referenced_beams, span));
+            Value::record(record, span)
+        })
+        .collect();
+    record.push("fraction_groups", Value::list(fraction_groups, span));
+
+    let beams = beams
+        .iter()
+        .map(|beam| beam_to_value(obj, beam, span))
+        .collect();
+    record.push("beams", Value::list(beams, span));
+
+    Ok(Value::record(record, span))
+}
+
+fn beam_to_value(
+    obj: &InMemDicomObject,
+    beam: &InMemDicomObject,
+    span: Span,
+) -> Value {
+    let number = get_float(beam, tags::BEAM_NUMBER);
+    let beam_meterset = items(obj, tags::FRACTION_GROUP_SEQUENCE)
+        .iter()
+        .flat_map(|group| items(group, tags::REFERENCED_BEAM_SEQUENCE))
+        .find(|referenced| number.is_some() && get_float(referenced, tags::REFERENCED_BEAM_NUMBER) == number)
+        .and_then(|referenced| get_float(referenced, tags::BEAM_METERSET));
+    let final_weight = get_float(beam, tags::FINAL_CUMULATIVE_METERSET_WEIGHT).filter(|weight| *weight > 0.0);
+
+    let control_points = match items(beam, tags::CONTROL_POINT_SEQUENCE) {
+        [] => items(beam, tags::ION_CONTROL_POINT_SEQUENCE),
+        control_points => control_points,
+    };
+
+    let mut previous = Record::new();
+    let control_points = control_points
+        .iter()
+        .map(|control_point| {
+            let weight = get_float(control_point, tags::CUMULATIVE_METERSET_WEIGHT);
+            let meterset = match (weight, final_weight, beam_meterset) {
+                (Some(weight), Some(final_weight), Some(beam_meterset)) => Value::float(beam_meterset * weight / final_weight, span),
+                _ => Value::nothing(span),
+            };
+
+            let mut record = Record::new();
+            record.push("ControlPointIndex", get_value(control_point, tags::CONTROL_POINT_INDEX, Kind::Int, span));
+            record.push("CumulativeMetersetWeight", weight.map_or_else(|| Value::nothing(span), |weight| Value::float(weight, span)));
+            record.push("meterset", meterset);
+            for (name, tag, kind) in CONTROL_POINT_COLUMNS {
+                let value = match control_point.get(*tag) {
+                    Some(_) => get_value(control_point, *tag, *kind, span),
+                    None => previous
+                        .get(*name)
+                        .cloned()
+                        .unwrap_or_else(|| Value::nothing(span)),
+                };
+                record.push(*name, value);
+            }
+
+            previous = record.clone();
+            Value::record(record, span)
+        })
+        .collect();
+
+    let mut record = Record::new();
+    push_columns(&mut record, beam, BEAM_COLUMNS, span);
+    record.push("BeamMeterset", beam_meterset.map_or_else(|| Value::nothing(span), |meterset| Value::float(meterset, span)));
+    record.push("control_points", Value::list(control_points, span));
+
+    Value::record(record, span)
+}
+
+fn push_columns(
+    record: &mut Record,
+    obj: &InMemDicomObject,
+    columns: &[Column],
+    span: Span,
+) {
+    for (name, tag, kind) in columns {
+        record.push(*name, get_value(obj, *tag, *kind, span));
+    }
+}
+
+fn get_value(
+    obj: &InMemDicomObject,
+    tag: Tag,
+    kind: Kind,
+    span: Span,
+) -> Value {
+    let value = match kind {
+        Kind::Int => get_int::<i64>(obj, tag).map(|i| Value::int(i, span)),
+        Kind::Float => get_float(obj, tag).map(|f| Value::float(f, span)),
+        Kind::Floats => get_floats(obj, tag).map(|floats| {
+            Value::list(
+                floats
+                    .into_iter()
+                    .map(|f| Value::float(f, span))
+                    .collect(),
+                span,
+            )
+        }),
+        Kind::String => get_string(obj, tag).map(|s| Value::string(s, span)),
+    };
+
+    value.unwrap_or_else(|| Value::nothing(span))
+}
@@ -0,0 +1,317 @@
+use std::path::PathBuf;
+
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Value};
+use snafu::{OptionExt, Snafu, ensure};
+
+use crate::convert::{get_int, get_string, items};
+use crate::input::{DicomSource, collect_input};
+use crate::plugin::DcmPlugin;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Not an RT Structure Set, StructureSetROISequence is missing"))]
+    NotStructureSet,
+
+    #[snafu(display("Item {} of {} has no {}", item, sequence, name))]
+    MissingAttribute { sequence: &'static str, item: usize, name: &'static str },
+
+    #[snafu(display("Contour {} of ROI {} has {} ContourData values, expected a multiple of 3", contour, roi, values))]
+    InvalidContourData { roi: i64, contour: usize, values: usize },
+}
+
+/// Contour of an ROI, from ContourSequence.
+#[derive(Debug, Clone)]
+pub struct Contour {
+    pub number: Option<i64>,
+    pub geometric_type: Option<String>,
+    /// First image of ContourImageSequence, i.e. the slice the contour was drawn on.
+    pub referenced_sop_instance_uid: Option<String>,
+    /// Points in patient coordinates (mm).
+    pub points: Vec<[f64; 3]>,
+}
+
+/// ROI of a structure set, joined from StructureSetROISequence, ROIContourSequence and RTROIObservationsSequence.
+#[derive(Debug, Clone)]
+pub struct Roi {
+    pub number: i64,
+    pub name: Option<String>,
+    pub frame_of_reference_uid: Option<String>,
+    pub interpreted_type: Option<String>,
+    pub color: Option<Vec<i64>>,
+    pub contours: Vec<Contour>,
+}
+
+/// `dcm rtstruct` command, lists the ROI contours of RT Structure Sets.
+pub struct DcmRtstructCommand;
+
+impl PluginCommand for DcmRtstructCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm rtstruct"
+    }
+
+    fn description(&self) -> &str {
+        "List the ROI contours of DICOM RT Structure Sets."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec![
+                "dicom".to_string(),
+                "rtstruct".to_string(),
+                "radiotherapy".to_string(),
+                "roi".to_string(),
+                "contour".to_string(),
+                "structure set".to_string(),
+            ])
+            .extra_description(
+                "Returns a row per contour, joining StructureSetROISequence, ROIContourSequence and RTROIObservationsSequence by ROI \
+                 number: the `SOPInstanceUID` of the structure set, ROINumber, ROIName, RTROIInterpretedType, \
+                 ROIDisplayColor, ReferencedFrameOfReferenceUID, ContourNumber, ContourGeometricType, the ReferencedSOPInstanceUID of the \
+                 image the contour was drawn on, NumberOfContourPoints and the `points` as a table of x, y and z in patient coordinates \
+                 (mm). ROIs without contours have a single row with the contour columns set to nothing and an empty `points` list.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "List the ROIs of a structure set",
+                example: "\"rtstruct.dcm\" | dcm rtstruct | uniq-by ROINumber | select ROINumber ROIName RTROIInterpretedType",
+                result: None,
+            },
+            Example {
+                description: "Get the points of the PTV on each slice",
+                example: "\"rtstruct.dcm\" | dcm rtstruct | where ROIName == PTV | select ReferencedSOPInstanceUID points",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let obj = source
+                .read()
+                .map_err(|e| source.error(&e, span))?;
+
+            let sop_instance_uid = get_string(&obj, tags::SOP_INSTANCE_UID)
+                .map(|uid| Value::string(uid, span))
+                .unwrap_or_else(|| Value::nothing(span));
+
+            for roi in rois(&obj).map_err(|e| source.labeled_error("Invalid RT Structure Set", &e, span))? {
+                if roi
+                    .contours
+                    .is_empty()
+                {
+                    output.push(contour_to_value(&roi, None, &sop_instance_uid, span));
+                }
+                for contour in &roi.contours {
+                    output.push(contour_to_value(&roi, Some(contour), &sop_instance_uid, span));
+                }
+            }
+        }
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+/// Joins the ROIs of a structure set, in the order of StructureSetROISequence. ROIs that only appear in ROIContourSequence
+/// come last.
+pub fn rois(obj: &InMemDicomObject) -> Result<Vec<Roi>, Error> {
+    let structure_set_rois = obj
+        .get(tags::STRUCTURE_SET_ROI_SEQUENCE)
+        .and_then(|e| e.items())
+        .context(NotStructureSetSnafu)?;
+
+    let mut rois = Vec::new();
+    for (index, item) in structure_set_rois
+        .iter()
+        .enumerate()
+    {
+        let number = get_int(item, tags::ROI_NUMBER).context(MissingAttributeSnafu {
+            sequence: "StructureSetROISequence",
+            item: index + 1,
+            name: "ROINumber",
+        })?;
+        rois.push(Roi {
+            number,
+            name: get_string(item, tags::ROI_NAME),
+            frame_of_reference_uid: get_string(item, tags::REFERENCED_FRAME_OF_REFERENCE_UID),
+            interpreted_type: None,
+            color: None,
+            contours: Vec::new(),
+        });
+    }
+
+    for (index, item) in items(obj, tags::ROI_CONTOUR_SEQUENCE)
+        .iter()
+        .enumerate()
+    {
+        let number = get_int(item, tags::REFERENCED_ROI_NUMBER).context(MissingAttributeSnafu {
+            sequence: "ROIContourSequence",
+            item: index + 1,
+            name: "ReferencedROINumber",
+        })?;
+        let roi = find_or_insert(&mut rois, number);
+
+        roi.color = item
+            .get(tags::ROI_DISPLAY_COLOR)
+            .and_then(|e| {
+                e.to_multi_int::<i64>()
+                    .ok()
+            });
+        for (index, contour) in items(item, tags::CONTOUR_SEQUENCE)
+            .iter()
+            .enumerate()
+        {
+            let contour = decode_contour(contour, number, index + 1)?;
+            roi.contours
+                .push(contour);
+        }
+    }
+
+    for (index, item) in items(obj, tags::RTROI_OBSERVATIONS_SEQUENCE)
+        .iter()
+        .enumerate()
+    {
+        let number = get_int(item, tags::REFERENCED_ROI_NUMBER).context(MissingAttributeSnafu {
+            sequence: "RTROIObservationsSequence",
+            item: index + 1,
+            name: "ReferencedROINumber",
+        })?;
+        let roi = find_or_insert(&mut rois, number);
+
+        roi.interpreted_type = get_string(item, tags::RTROI_INTERPRETED_TYPE);
+    }
+
+    Ok(rois)
+}
+
+fn find_or_insert(
+    rois: &mut Vec<Roi>,
+    number: i64,
+) -> &mut Roi {
+    let index = match rois
+        .iter()
+        .position(|roi| roi.number == number)
+    {
+        Some(index) => index,
+        None => {
+            rois.push(Roi { number, name: None, frame_of_reference_uid: None, interpreted_type: None, color: None, contours: Vec::new() });
+            rois.len() - 1
+        }
+    };
+
+    &mut rois[index]
+}
+
+fn decode_contour(
+    item: &InMemDicomObject,
+    roi: i64,
+    contour: usize,
+) -> Result<Contour, Error> {
+    let data = item
+        .get(tags::CONTOUR_DATA)
+        .and_then(|e| {
+            e.to_multi_float64()
+                .ok()
+        })
+        .unwrap_or_default();
+    ensure!(data.len() % 3 == 0, InvalidContourDataSnafu { roi, contour, values: data.len() });
+
+    let referenced_sop_instance_uid = items(item, tags::CONTOUR_IMAGE_SEQUENCE)
+        .first()
+        .and_then(|image| get_string(image, tags::REFERENCED_SOP_INSTANCE_UID));
+
+    Ok(Contour {
+        number: get_int(item, tags::CONTOUR_NUMBER),
+        geometric_type: get_string(item, tags::CONTOUR_GEOMETRIC_TYPE),
+        referenced_sop_instance_uid,
+        points: data
+            .chunks_exact(3)
+            .map(|point| [point[0], point[1], point[2]])
+            .collect(),
+    })
+}
+
+fn contour_to_value(
+    roi: &Roi,
+    contour: Option<&Contour>,
+    sop_instance_uid: &Value,
+    span: Span,
+) -> Value {
+    let string = |s: &Option<String>| {
+        s.as_ref()
+            .map_or_else(|| Value::nothing(span), |s| Value::string(s, span))
+    };
+    let int = |i: Option<i64>| i.map_or_else(|| Value::nothing(span), |i| Value::int(i, span));
+
+    let color = roi
+        .color
+        .as_ref()
+        .map(|color| {
+            color
+                .iter()
+                .map(|c| Value::int(*c, span))
+                .collect()
+        });
+
+    let mut record = Record::new();
+    record.push("SOPInstanceUID", sop_instance_uid.clone());
+    record.push("ROINumber", Value::int(roi.number, span));
+    record.push("ROIName", string(&roi.name));
+    record.push("RTROIInterpretedType", string(&roi.interpreted_type));
+    record.push("ROIDisplayColor", color.map_or_else(|| Value::nothing(span), |color| Value::list(color, span)));
+    record.push("ReferencedFrameOfReferenceUID", string(&roi.frame_of_reference_uid));
+
+    match contour {
+        Some(contour) => {
+            let points = contour
+                .points
+                .iter()
+                .map(|[x, y, z]| {
+                    let mut record = Record::new();
+                    record.push("x", Value::float(*x, span));
+                    record.push("y", Value::float(*y, span));
+                    record.push("z", Value::float(*z, span));
+                    Value::record(record, span)
+                })
+                .collect::<Vec<_>>();
+
+            record.push("ContourNumber", int(contour.number));
+            record.push("ContourGeometricType", string(&contour.geometric_type));
+            record.push("ReferencedSOPInstanceUID", string(&contour.referenced_sop_instance_uid));
+            record.push("NumberOfContourPoints", Value::int(points.len() as i64, span));
+            record.push("points", Value::list(points, span));
+        }
+        None => {
+            for column in ["ContourNumber", "ContourGeometricType", "ReferencedSOPInstanceUID", "NumberOfContourPoints"] {
+                record.push(column, Value::nothing(span));
+            }
+            record.push("points", Value::list(Vec::new(), span));
+        }
+    }
+
+    Value::record(record, span)
+}
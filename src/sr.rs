@@ -0,0 +1,389 @@
+use std::path::PathBuf;
+
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use indexmap::IndexMap;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Value};
+use snafu::{Snafu, ensure};
+
+use crate::convert::{get_floats, get_ints, get_string, items};
+use crate::input::{DicomSource, collect_input};
+use crate::plugin::DcmPlugin;
+use crate::reader::ErrorKind;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Missing ValueType CONTAINER of the document root"))]
+    NotStructuredReport,
+}
+
+/// Coded concept, e.g. a concept name or a coded value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+    pub value: String,
+    pub scheme: String,
+    pub meaning: String,
+}
+
+/// Value of a content item, depending on its value type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentValue {
+    /// CONTAINER, or an item without value.
+    None,
+    /// TEXT, DATETIME, DATE, TIME, UIDREF and PNAME, as encoded.
+    Text(String),
+    /// NUM, without value if the measurement is missing.
+    Numeric { value: Option<f64>, units: Option<Code> },
+    /// CODE.
+    Code(Code),
+    /// IMAGE, COMPOSITE and WAVEFORM.
+    Reference { sop_class_uid: String, sop_instance_uid: String, frames: Vec<i64> },
+    /// SCOORD, SCOORD3D and TCOORD, with the graphic type or temporal range type.
+    Coordinates { graphic_type: String, data: Vec<f64> },
+    /// Path of the content item referenced by a by-reference relationship.
+    ContentReference(String),
+}
+
+/// Node of an SR content tree.
+#[derive(Debug, Clone)]
+pub struct ContentItem {
+    /// Position in the tree as a content item identifier, e.g. `1.4.2` for the second child of the fourth child of the root.
+    pub path: String,
+    /// Relationship with the parent, none for the root.
+    pub relationship: Option<String>,
+    /// None for by-reference relationships.
+    pub value_type: Option<String>,
+    pub concept: Option<Code>,
+    pub value: ContentValue,
+}
+
+/// `dcm sr` command, flattens the content tree of Structured Report documents.
+pub struct DcmSrCommand;
+
+impl PluginCommand for DcmSrCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm sr"
+    }
+
+    fn description(&self) -> &str {
+        "Return the content tree of DICOM Structured Report documents as a table with a row per content item."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .category(Category::Formats)
+            .search_terms(vec![
+                "dicom".to_string(),
+                "sr".to_string(),
+                "structured report".to_string(),
+                "content tree".to_string(),
+                "measurements".to_string(),
+            ])
+            .extra_description(
+                "Content items are listed depth-first, starting with the document root. Each row has the `SOPInstanceUID` of the document, the \
+                 `path` of the item as a content item identifier (e.g. `1.4.2`), its `relationship` with the parent, its `value_type` and \
+                 the code meaning, value and coding scheme of its concept name (`concept`, `concept_code`, `concept_scheme`). The `value` \
+                 column depends on the value type: text for TEXT, DATE, TIME, DATETIME, UIDREF and PNAME, a float for NUM (with UCUM \
+                 `units`), the code meaning for CODE (with `code` and `scheme`), the referenced SOPInstanceUID for IMAGE, COMPOSITE and \
+                 WAVEFORM (with `referenced_sop_class` and `referenced_frames`), and a list of floats for SCOORD, SCOORD3D and TCOORD (with \
+                 `graphic_type`). By-reference relationships have no value type, their value is the path of the referenced item. Objects \
+                 that aren't SR documents and non-DICOM files are skipped.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show the measurements of a report",
+                example: "\"report.dcm\" | dcm sr | where value_type == NUM | select concept value units",
+                result: None,
+            },
+            Example {
+                description: "Find the images referenced by reports",
+                example: "ls *.dcm | get name | dcm sr | where value_type == IMAGE | get value | uniq",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            // directory listings usually contain images and non-DICOM files too, only SR documents produce rows
+            let obj = match source.read() {
+                Ok(obj) => obj,
+                Err(e) if e.kind() == ErrorKind::NotDicom => continue,
+                Err(e) => return Err(source.error(&e, span)),
+            };
+            let Ok(items) = content_items(&obj) else {
+                continue;
+            };
+
+            let sop_instance_uid = get_string(&obj, tags::SOP_INSTANCE_UID)
+                .map(|uid| Value::string(uid, span))
+                .unwrap_or_else(|| Value::nothing(span));
+
+            output.extend(
+                items
+                    .iter()
+                    .map(|item| content_item_to_value(item, &sop_instance_uid, span)),
+            );
+        }
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+/// Walks the content tree of an SR document depth-first, starting with the root.
+pub fn content_items(obj: &InMemDicomObject) -> Result<Vec<ContentItem>, Error> {
+    ensure!(get_string(obj, tags::VALUE_TYPE).as_deref() == Some("CONTAINER"), NotStructuredReportSnafu);
+
+    let mut items = Vec::new();
+    walk(obj, "1".to_string(), &mut items);
+
+    Ok(items)
+}
+
+fn walk(
+    item: &InMemDicomObject,
+    path: String,
+    content: &mut Vec<ContentItem>,
+) {
+    content.push(ContentItem {
+        relationship: get_string(item, tags::RELATIONSHIP_TYPE),
+        value_type: get_string(item, tags::VALUE_TYPE),
+        concept: get_code(item, tags::CONCEPT_NAME_CODE_SEQUENCE),
+        value: get_content_value(item),
+        path: path.clone(),
+    });
+
+    for (index, child) in items(item, tags::CONTENT_SEQUENCE)
+        .iter()
+        .enumerate()
+    {
+        walk(child, format!("{path}.{}", index + 1), content);
+    }
+}
+
+fn get_content_value(item: &InMemDicomObject) -> ContentValue {
+    let Some(value_type) = get_string(item, tags::VALUE_TYPE) else {
+        return get_ints(item, tags::REFERENCED_CONTENT_ITEM_IDENTIFIER)
+            .map(|identifier| {
+                ContentValue::ContentReference(
+                    identifier
+                        .iter()
+                        .map(i64::to_string)
+                        .collect::<Vec<_>>()
+                        .join("."),
+                )
+            })
+            .unwrap_or(ContentValue::None);
+    };
+
+    let text = |tag| get_string(item, tag).map_or(ContentValue::None, ContentValue::Text);
+
+    match value_type.as_str() {
+        "TEXT" => text(tags::TEXT_VALUE),
+        "DATETIME" => text(tags::DATE_TIME),
+        "DATE" => text(tags::DATE),
+        "TIME" => text(tags::TIME),
+        "UIDREF" => text(tags::UID),
+        "PNAME" => text(tags::PERSON_NAME),
+        "NUM" => {
+            let measured_value = items(item, tags::MEASURED_VALUE_SEQUENCE).first();
+            ContentValue::Numeric {
+                value: measured_value
+                    .and_then(|measured_value| get_floats(measured_value, tags::NUMERIC_VALUE))
+                    .and_then(|values| {
+                        values
+                            .first()
+                            .copied()
+                    }),
+                units: measured_value.and_then(|measured_value| get_code(measured_value, tags::MEASUREMENT_UNITS_CODE_SEQUENCE)),
+            }
+        }
+        "CODE" => get_code(item, tags::CONCEPT_CODE_SEQUENCE).map_or(ContentValue::None, ContentValue::Code),
+        "IMAGE" | "COMPOSITE" | "WAVEFORM" => items(item, tags::REFERENCED_SOP_SEQUENCE)
+            .first()
+            .map_or(ContentValue::None, |reference| ContentValue::Reference {
+                sop_class_uid: get_string(reference, tags::REFERENCED_SOP_CLASS_UID).unwrap_or_default(),
+                sop_instance_uid: get_string(reference, tags::REFERENCED_SOP_INSTANCE_UID).unwrap_or_default(),
+                frames: get_ints(reference, tags::REFERENCED_FRAME_NUMBER).unwrap_or_default(),
+            }),
+        "SCOORD" | "SCOORD3D" => ContentValue::Coordinates {
+            graphic_type: get_string(item, tags::GRAPHIC_TYPE).unwrap_or_default(),
+            data: get_floats(item, tags::GRAPHIC_DATA).unwrap_or_default(),
+        },
+        "TCOORD" => ContentValue::Coordinates {
+            graphic_type: get_string(item, tags::TEMPORAL_RANGE_TYPE).unwrap_or_default(),
+            data: get_floats(item, tags::REFERENCED_SAMPLE_POSITIONS)
+                .or_else(|| get_floats(item, tags::REFERENCED_TIME_OFFSETS))
+                .unwrap_or_default(),
+        },
+        _ => ContentValue::None,
+    }
+}
+
+fn content_item_to_value(
+    item: &ContentItem,
+    sop_instance_uid: &Value,
+    span: Span,
+) -> Value {
+    let string = |s: Option<&str>| s.map_or_else(|| Value::nothing(span), |s| Value::string(s, span));
+    let nothing = Value::nothing(span);
+
+    let mut row = IndexMap::with_capacity(14);
+    row.insert("SOPInstanceUID".to_string(), sop_instance_uid.clone());
+    row.insert("path".to_string(), Value::string(&item.path, span));
+    row.insert(
+        "relationship".to_string(),
+        string(
+            item.relationship
+                .as_deref(),
+        ),
+    );
+    row.insert(
+        "value_type".to_string(),
+        string(
+            item.value_type
+                .as_deref(),
+        ),
+    );
+    row.insert(
+        "concept".to_string(),
+        string(
+            item.concept
+                .as_ref()
+                .map(|code| {
+                    code.meaning
+                        .as_str()
+                }),
+        ),
+    );
+    row.insert(
+        "concept_code".to_string(),
+        string(
+            item.concept
+                .as_ref()
+                .map(|code| {
+                    code.value
+                        .as_str()
+                }),
+        ),
+    );
+    row.insert(
+        "concept_scheme".to_string(),
+        string(
+            item.concept
+                .as_ref()
+                .map(|code| {
+                    code.scheme
+                        .as_str()
+                }),
+        ),
+    );
+
+    let (value, units, code, referenced_sop_class, referenced_frames, graphic_type) = match &item.value {
+        ContentValue::None => (nothing.clone(), None, None, None, nothing.clone(), None),
+        ContentValue::Text(text) | ContentValue::ContentReference(text) => (Value::string(text, span), None, None, None, nothing.clone(), None),
+        ContentValue::Numeric { value, units } => (
+            value.map_or_else(|| nothing.clone(), |value| Value::float(value, span)),
+            units
+                .as_ref()
+                .map(|units| {
+                    units
+                        .value
+                        .as_str()
+                }),
+            None,
+            None,
+            nothing.clone(),
+            None,
+        ),
+        ContentValue::Code(code) => (Value::string(&code.meaning, span), None, Some(code), None, nothing.clone(), None),
+        ContentValue::Reference { sop_class_uid, sop_instance_uid, frames } => (
+            Value::string(sop_instance_uid, span),
+            None,
+            None,
+            Some(sop_class_uid.as_str()),
+            Value::list(
+                frames
+                    .iter()
+                    .map(|frame| Value::int(*frame, span))
+                    .collect(),
+                span,
+            ),
+            None,
+        ),
+        ContentValue::Coordinates { graphic_type, data } => (
+            Value::list(
+                data.iter()
+                    .map(|value| Value::float(*value, span))
+                    .collect(),
+                span,
+            ),
+            None,
+            None,
+            None,
+            nothing.clone(),
+            Some(graphic_type.as_str()),
+        ),
+    };
+
+    row.insert("value".to_string(), value);
+    row.insert("units".to_string(), string(units));
+    row.insert(
+        "code".to_string(),
+        string(code.map(|code| {
+            code.value
+                .as_str()
+        })),
+    );
+    row.insert(
+        "scheme".to_string(),
+        string(code.map(|code| {
+            code.scheme
+                .as_str()
+        })),
+    );
+    row.insert("referenced_sop_class".to_string(), string(referenced_sop_class));
+    row.insert("referenced_frames".to_string(), referenced_frames);
+    row.insert("graphic_type".to_string(), string(graphic_type));
+
+    Value::record(Record::from_iter(row), span)
+}
+
+fn get_code(
+    obj: &InMemDicomObject,
+    tag: Tag,
+) -> Option<Code> {
+    let item = items(obj, tag).first()?;
+
+    Some(Code {
+        value: get_string(item, tags::CODE_VALUE)
+            .or_else(|| get_string(item, tags::LONG_CODE_VALUE))
+            .or_else(|| get_string(item, tags::URN_CODE_VALUE))
+            .unwrap_or_default(),
+        scheme: get_string(item, tags::CODING_SCHEME_DESIGNATOR).unwrap_or_default(),
+        meaning: get_string(item, tags::CODE_MEANING).unwrap_or_default(),
+    })
+}
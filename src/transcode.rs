@@ -0,0 +1,183 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use dicom::dictionary_std::uids;
+use dicom::encoding::adapters::EncodeOptions;
+use dicom::encoding::{Codec, TransferSyntax, TransferSyntaxIndex};
+use dicom::transfer_syntax::TransferSyntaxRegistry;
+use dicom_pixeldata::Transcode;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, LabeledError, PipelineData, Signature, SyntaxShape, Value};
+
+use crate::input::{DicomSource, collect_input, into_output, is_list_input};
+use crate::plugin::DcmPlugin;
+use crate::reader::read_dcm_stream_with_pixel_data;
+use crate::writer::write_dcm;
+
+/// Short names accepted by `--to`, besides transfer syntax UIDs.
+#[allow(deprecated)]
+const TRANSFER_SYNTAX_NAMES: &[(&str, &str)] = &[
+    ("implicit-le", uids::IMPLICIT_VR_LITTLE_ENDIAN),
+    ("explicit-le", uids::EXPLICIT_VR_LITTLE_ENDIAN),
+    ("explicit-be", uids::EXPLICIT_VR_BIG_ENDIAN),
+    ("deflated-le", uids::DEFLATED_EXPLICIT_VR_LITTLE_ENDIAN),
+    ("encapsulated-le", uids::ENCAPSULATED_UNCOMPRESSED_EXPLICIT_VR_LITTLE_ENDIAN),
+    ("jpeg-baseline", uids::JPEG_BASELINE8_BIT),
+];
+
+/// `dcm transcode` command, converts DICOM objects to another transfer syntax.
+pub struct DcmTranscodeCommand;
+
+impl PluginCommand for DcmTranscodeCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm transcode"
+    }
+
+    fn description(&self) -> &str {
+        "Convert DICOM objects to another transfer syntax."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .required_named(
+                "to",
+                SyntaxShape::String,
+                "Target transfer syntax, either a UID or one of `implicit-le`, `explicit-le`, `explicit-be`, `deflated-le`, `encapsulated-le` or \
+                 `jpeg-baseline`.",
+                None,
+            )
+            .named("quality", SyntaxShape::Int, "Quality (0-100) of lossy pixel data compression, e.g. JPEG baseline.", Some('q'))
+            .category(Category::Formats)
+            .search_terms(vec![
+                "dicom".to_string(),
+                "transcode".to_string(),
+                "convert".to_string(),
+                "transfer syntax".to_string(),
+                "compress".to_string(),
+            ])
+            .extra_description(
+                "The file meta group is updated for the new transfer syntax. Encapsulated pixel data are decoded and encoded as needed, using the \
+                 pure Rust codecs built into the plugin: RLE Lossless and JPEG (baseline, extended and lossless) can be decoded, but only JPEG \
+                 baseline can be encoded. Returns binary data for a single input and a list of binary data for a list.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Convert a file to Implicit VR Little Endian",
+                example: "\"file.dcm\" | dcm transcode --to implicit-le | save implicit.dcm",
+                result: None,
+            },
+            Example {
+                description: "Decompress files, e.g. JPEG or RLE Lossless",
+                example: "ls *.dcm | each {|f| $f.name | dcm transcode --to explicit-le | save $\"decompressed/($f.name)\" }",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let ts = get_to_flag(call)?;
+        let options = get_encode_options(call)?;
+
+        let is_list = is_list_input(&input);
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let bytes = source
+                .bytes()
+                .map_err(|e| source.error(&e, span))?;
+            let mut obj = read_dcm_stream_with_pixel_data(Cursor::new(&bytes[..]), None).map_err(|e| source.error(&e, span))?;
+
+            obj.transcode_with_options(ts, options.clone())
+                .map_err(|e| label_error("Failed to transcode", &e, &source, span))?;
+
+            let (bytes, _) = write_dcm(&obj).map_err(|e| label_error("Failed to encode", &e, &source, span))?;
+
+            output.push(Value::binary(bytes, span));
+        }
+
+        Ok(into_output(output, is_list, call.head))
+    }
+}
+
+fn label_error(
+    msg: &str,
+    error: &dyn std::error::Error,
+    source: &DicomSource,
+    span: nu_protocol::Span,
+) -> LabeledError {
+    // transcoding errors are terse, include their causes
+    let mut text = error.to_string();
+    for cause in std::iter::successors(error.source(), |e| e.source()) {
+        text = format!("{text}: {cause}");
+    }
+
+    if let Some(path) = source.path() {
+        text = format!("{text} [file {}]", path.to_string_lossy());
+    }
+
+    LabeledError::new(msg).with_label(text, span)
+}
+
+fn get_to_flag(call: &EvaluatedCall) -> Result<&'static TransferSyntax, LabeledError> {
+    let value = call
+        .get_flag_value("to")
+        .ok_or_else(|| LabeledError::new("Missing --to").with_label("Target transfer syntax is required", call.head))?;
+
+    let span = value.span();
+    let name = value.as_str()?;
+
+    let uid = TRANSFER_SYNTAX_NAMES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, uid)| uid);
+
+    let ts = TransferSyntaxRegistry
+        .get(uid)
+        .ok_or_else(|| LabeledError::new("Unsupported transfer syntax").with_label(format!("Unknown transfer syntax `{name}`"), span))?;
+
+    // the registry also knows transfer syntaxes it can only read, or not even that
+    let can_write = match ts.codec() {
+        Codec::None => true,
+        Codec::Dataset(adapter) => adapter.is_some(),
+        Codec::EncapsulatedPixelData(_, writer) => writer.is_some(),
+    };
+    if !can_write {
+        return Err(LabeledError::new("Unsupported transfer syntax").with_label(format!("Can't encode {} ({})", ts.name(), ts.uid()), span));
+    }
+
+    Ok(ts)
+}
+
+fn get_encode_options(call: &EvaluatedCall) -> Result<EncodeOptions, LabeledError> {
+    let mut options = EncodeOptions::default();
+
+    if let Some(value) = call.get_flag_value("quality") {
+        let quality = value.as_int()?;
+        options.quality = Some(
+            u8::try_from(quality)
+                .ok()
+                .filter(|quality| *quality <= 100)
+                .ok_or_else(|| LabeledError::new("Invalid quality").with_label("Expected a number between 0 and 100", value.span()))?,
+        );
+    }
+
+    Ok(options)
+}
@@ -0,0 +1,6 @@
+use uuid::Uuid;
+
+/// Generates a new globally unique UID, derived from a random UUID (PS3.5 B.2).
+pub fn generate_uid() -> String {
+    format!("2.25.{}", Uuid::new_v4().as_u128())
+}
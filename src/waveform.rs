@@ -0,0 +1,354 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use dicom::core::Tag;
+use dicom::dictionary_std::tags;
+use dicom::object::InMemDicomObject;
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand};
+use nu_protocol::{Category, Example, IntoPipelineData, LabeledError, PipelineData, Record, Signature, Span, Value};
+use snafu::{OptionExt, Snafu, ensure};
+
+use crate::convert::{bytes, get_float, get_int, get_string, words};
+use crate::input::{DicomSource, collect_input};
+use crate::plugin::DcmPlugin;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Multiplex group {} has no {}", group, name))]
+    MissingAttribute { group: usize, name: &'static str },
+
+    #[snafu(display("Multiplex group {} has unsupported {}-bit {} samples", group, bits, interpretation))]
+    UnsupportedSamples { group: usize, bits: u16, interpretation: String },
+
+    #[snafu(display("Multiplex group {} has {} sample values, expected {}", group, actual, expected))]
+    TooFewSamples { group: usize, expected: usize, actual: usize },
+}
+
+/// Channel of a multiplex group.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    /// Unique column name, the ChannelLabel or the code meaning of the channel source.
+    pub name: String,
+    pub source: Option<String>,
+    pub units: Option<String>,
+    pub sensitivity: f64,
+    pub baseline: f64,
+    pub correction: f64,
+}
+
+/// Decoded multiplex group of WaveformSequence.
+#[derive(Debug, Clone)]
+pub struct MultiplexGroup {
+    pub label: Option<String>,
+    pub sampling_frequency: f64,
+    pub channels: Vec<Channel>,
+    /// Samples of each channel, i.e. `samples[channel][sample]`.
+    pub samples: Vec<Vec<f64>>,
+}
+
+/// `dcm waveform` command, decodes the multiplex groups of waveform objects.
+pub struct DcmWaveformCommand;
+
+impl PluginCommand for DcmWaveformCommand {
+    type Plugin = DcmPlugin;
+
+    fn name(&self) -> &str {
+        "dcm waveform"
+    }
+
+    fn description(&self) -> &str {
+        "Decode the waveforms of DICOM ECG, hemodynamic and other waveform objects into tables."
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .switch("raw", "Return the stored sample values, without sensitivity, baseline and correction factor.", None)
+            .category(Category::Formats)
+            .search_terms(vec!["dicom".to_string(), "waveform".to_string(), "ecg".to_string(), "ekg".to_string(), "hemodynamic".to_string()])
+            .extra_description(
+                "Returns a record per multiplex group of WaveformSequence, with the `SOPInstanceUID` of the object, the `group` number \
+                 (starting at 1), its `MultiplexGroupLabel` and `SamplingFrequency`, its `channels` (name, source, units, sensitivity, \
+                 baseline and correction factor) and the `samples` table. The samples table has the `time` of each sample in seconds and a \
+                 column per channel, named after its ChannelLabel or the code meaning of its ChannelSourceSequence. Sample values are \
+                 scaled as value × ChannelSensitivity × ChannelSensitivityCorrectionFactor + ChannelBaseline × \
+                 ChannelSensitivityCorrectionFactor, in the units of ChannelSensitivityUnitsSequence. 8 and 16-bit signed and unsigned \
+                 samples are supported.",
+            )
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Show the samples of the first multiplex group of an ECG",
+                example: "\"ecg.dcm\" | dcm waveform | get 0.samples",
+                result: None,
+            },
+            Example {
+                description: "Find the peak of lead II",
+                example: "\"ecg.dcm\" | dcm waveform | get 0.samples | sort-by \"Lead II\" | last",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &DcmPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let current_dir = engine
+            .get_current_dir()
+            .map(PathBuf::from);
+
+        let raw = call.has_flag("raw")?;
+        let mut output = Vec::new();
+
+        for value in collect_input(input, call.head)? {
+            let span = value.span();
+            let source = DicomSource::from_value(value, current_dir.as_deref())?;
+
+            let obj = source
+                .read()
+                .map_err(|e| source.error(&e, span))?;
+
+            let sop_instance_uid = get_string(&obj, tags::SOP_INSTANCE_UID)
+                .map(|uid| Value::string(uid, span))
+                .unwrap_or_else(|| Value::nothing(span));
+
+            let groups = multiplex_groups(&obj, raw).map_err(|e| source.labeled_error("Invalid waveform", &e, span))?;
+
+            for (index, group) in groups
+                .iter()
+                .enumerate()
+            {
+                output.push(multiplex_group_to_value(group, index + 1, &sop_instance_uid, span));
+            }
+        }
+
+        Ok(Value::list(output, call.head).into_pipeline_data())
+    }
+}
+
+/// Decodes the multiplex groups of WaveformSequence. Samples are scaled unless `raw`.
+pub fn multiplex_groups(
+    obj: &InMemDicomObject,
+    raw: bool,
+) -> Result<Vec<MultiplexGroup>, Error> {
+    obj.get(tags::WAVEFORM_SEQUENCE)
+        .and_then(|e| e.items())
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(index, item)| decode_multiplex_group(item, index + 1, raw))
+        .collect()
+}
+
+fn decode_multiplex_group(
+    item: &InMemDicomObject,
+    group: usize,
+    raw: bool,
+) -> Result<MultiplexGroup, Error> {
+    let number_of_channels =
+        get_int(item, tags::NUMBER_OF_WAVEFORM_CHANNELS).context(MissingAttributeSnafu { group, name: "NumberOfWaveformChannels" })?;
+    let number_of_samples =
+        get_int(item, tags::NUMBER_OF_WAVEFORM_SAMPLES).context(MissingAttributeSnafu { group, name: "NumberOfWaveformSamples" })?;
+    let sampling_frequency = get_float(item, tags::SAMPLING_FREQUENCY).context(MissingAttributeSnafu { group, name: "SamplingFrequency" })?;
+    let bits: u16 = get_int(item, tags::WAVEFORM_BITS_ALLOCATED).context(MissingAttributeSnafu { group, name: "WaveformBitsAllocated" })?;
+    let interpretation =
+        get_string(item, tags::WAVEFORM_SAMPLE_INTERPRETATION).context(MissingAttributeSnafu { group, name: "WaveformSampleInterpretation" })?;
+    let data = item
+        .get(tags::WAVEFORM_DATA)
+        .and_then(|e| {
+            e.value()
+                .primitive()
+        })
+        .context(MissingAttributeSnafu { group, name: "WaveformData" })?;
+
+    let values: Vec<f64> = match (bits, interpretation.as_str()) {
+        (16, "SS") => words(data)
+            .into_iter()
+            .map(|word| word as i16 as f64)
+            .collect(),
+        (16, "US") => words(data)
+            .into_iter()
+            .map(f64::from)
+            .collect(),
+        (8, "SB") => bytes(data)
+            .into_iter()
+            .map(|byte| byte as i8 as f64)
+            .collect(),
+        (8, "UB") => bytes(data)
+            .into_iter()
+            .map(f64::from)
+            .collect(),
+        _ => return UnsupportedSamplesSnafu { group, bits, interpretation }.fail(),
+    };
+
+    let expected = number_of_channels * number_of_samples;
+    ensure!(values.len() >= expected, TooFewSamplesSnafu { group, expected, actual: values.len() });
+
+    let definitions = item
+        .get(tags::CHANNEL_DEFINITION_SEQUENCE)
+        .and_then(|e| e.items())
+        .unwrap_or_default();
+    let channels = unique_names(
+        (0..number_of_channels)
+            .map(|channel| definition_to_channel(definitions.get(channel), channel))
+            .collect(),
+    );
+
+    // samples are interleaved, all channels of the first sample come first
+    let samples = channels
+        .iter()
+        .enumerate()
+        .map(|(index, channel)| {
+            values
+                .iter()
+                .skip(index)
+                .step_by(number_of_channels)
+                .take(number_of_samples)
+                .map(|value| {
+                    if raw {
+                        *value
+                    } else {
+                        (value * channel.sensitivity + channel.baseline) * channel.correction
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(MultiplexGroup { label: get_string(item, tags::MULTIPLEX_GROUP_LABEL), sampling_frequency, channels, samples })
+}
+
+fn definition_to_channel(
+    definition: Option<&InMemDicomObject>,
+    index: usize,
+) -> Channel {
+    let code_meaning = |tag: Tag| {
+        let item = definition?
+            .get(tag)?
+            .items()?
+            .first()?;
+        get_string(item, tags::CODE_MEANING)
+    };
+    let code_value = |tag: Tag| {
+        let item = definition?
+            .get(tag)?
+            .items()?
+            .first()?;
+        get_string(item, tags::CODE_VALUE)
+    };
+    let float = |tag: Tag, default: f64| {
+        definition
+            .and_then(|definition| get_float(definition, tag))
+            .unwrap_or(default)
+    };
+
+    let source = code_meaning(tags::CHANNEL_SOURCE_SEQUENCE);
+    let name = definition
+        .and_then(|definition| get_string(definition, tags::CHANNEL_LABEL))
+        .or_else(|| source.clone())
+        .unwrap_or_else(|| format!("channel {}", index + 1));
+
+    Channel {
+        name,
+        source,
+        units: code_value(tags::CHANNEL_SENSITIVITY_UNITS_SEQUENCE),
+        sensitivity: float(tags::CHANNEL_SENSITIVITY, 1.0),
+        baseline: float(tags::CHANNEL_BASELINE, 0.0),
+        correction: float(tags::CHANNEL_SENSITIVITY_CORRECTION_FACTOR, 1.0),
+    }
+}
+
+/// Appends the channel number to repeated names, so that each channel has its own column.
+fn unique_names(mut channels: Vec<Channel>) -> Vec<Channel> {
+    let mut names = HashSet::new();
+
+    for (index, channel) in channels
+        .iter_mut()
+        .enumerate()
+    {
+        if channel.name == "time"
+            || !names.insert(
+                channel
+                    .name
+                    .clone(),
+            )
+        {
+            channel.name = format!("{} ({})", channel.name, index + 1);
+            names.insert(
+                channel
+                    .name
+                    .clone(),
+            );
+        }
+    }
+
+    channels
+}
+
+fn multiplex_group_to_value(
+    group: &MultiplexGroup,
+    number: usize,
+    sop_instance_uid: &Value,
+    span: Span,
+) -> Value {
+    let channels = group
+        .channels
+        .iter()
+        .map(|channel| {
+            let string = |s: &Option<String>| {
+                s.as_ref()
+                    .map_or_else(|| Value::nothing(span), |s| Value::string(s, span))
+            };
+
+            let mut record = Record::new();
+            record.push("name", Value::string(&channel.name, span));
+            record.push("source", string(&channel.source));
+            record.push("units", string(&channel.units));
+            record.push("sensitivity", Value::float(channel.sensitivity, span));
+            record.push("baseline", Value::float(channel.baseline, span));
+            record.push("correction", Value::float(channel.correction, span));
+            Value::record(record, span)
+        })
+        .collect();
+
+    let number_of_samples = group
+        .samples
+        .first()
+        .map_or(0, Vec::len);
+    let samples = (0..number_of_samples)
+        .map(|sample| {
+            let mut record = Record::new();
+            record.push("time", Value::float(sample as f64 / group.sampling_frequency, span));
+            for (channel, values) in group
+                .channels
+                .iter()
+                .zip(&group.samples)
+            {
+                record.push(&channel.name, Value::float(values[sample], span));
+            }
+            Value::record(record, span)
+        })
+        .collect();
+
+    let mut record = Record::new();
+    record.push("SOPInstanceUID", sop_instance_uid.clone());
+    record.push("group", Value::int(number as i64, span));
+    record.push(
+        "MultiplexGroupLabel",
+        group
+            .label
+            .as_ref()
+            .map_or_else(|| Value::nothing(span), |label| Value::string(label, span)),
+    );
+    record.push("SamplingFrequency", Value::float(group.sampling_frequency, span));
+    record.push("channels", Value::list(channels, span));
+    record.push("samples", Value::list(samples, span));
+
+    Value::record(record, span)
+}
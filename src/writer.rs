@@ -0,0 +1,31 @@
+use dicom::object::{self as dicom_object, DefaultDicomObject};
+use snafu::{ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Could not write Dicom object: {}", source))]
+    Write {
+        #[snafu(source(from(dicom_object::WriteError, Box::new)))]
+        source: Box<dicom_object::WriteError>,
+    },
+
+    #[snafu(display("Could not create Dicom file meta group: {}", source))]
+    BuildMeta { source: dicom_object::meta::Error },
+}
+
+/// Encodes a DICOM file, i.e. the preamble, the "DICM" marker, the file meta group and the data set. Returns the
+/// encoded bytes and the offset of the data set.
+pub fn write_dcm(obj: &DefaultDicomObject) -> Result<(Vec<u8>, usize), Error> {
+    let mut bytes = vec![0; 128];
+    bytes.extend_from_slice(b"DICM");
+
+    obj.write_meta(&mut bytes)
+        .context(WriteSnafu)?;
+    let dataset_start = bytes.len();
+
+    obj.write_dataset(&mut bytes)
+        .context(WriteSnafu)?;
+
+    Ok((bytes, dataset_start))
+}
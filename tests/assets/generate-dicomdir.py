@@ -0,0 +1,160 @@
+#!/usr/bin/env python3
+
+# Writes a small DICOMDIR (Explicit VR Little Endian) with two patients, and the referenced instances. Encoded by
+# hand so that the offsets linking the directory records are under control.
+
+import os
+import struct
+
+ASSETS_DIR = os.path.dirname(__file__)
+DICOMDIR_PATH = os.path.join(ASSETS_DIR, "dicomdir", "DICOMDIR")
+
+LONG_VRS = {"OB", "OW", "OF", "SQ", "UT", "UN"}
+
+
+def element(group, elem, vr, value):
+    if isinstance(value, str):
+        value = value.encode("ascii")
+        if len(value) % 2:
+            value += b"\0" if vr == "UI" else b" "
+
+    header = struct.pack("<HH", group, elem) + vr.encode("ascii")
+    if vr in LONG_VRS:
+        header += struct.pack("<HI", 0, len(value))
+    else:
+        header += struct.pack("<H", len(value))
+
+    return header + value
+
+
+def ul(group, elem, value):
+    return element(group, elem, "UL", struct.pack("<I", value))
+
+
+def us(group, elem, value):
+    return element(group, elem, "US", struct.pack("<H", value))
+
+
+def item(data):
+    return struct.pack("<HHI", 0xFFFE, 0xE000, len(data)) + data
+
+
+# (record type, parent index, attributes)
+RECORDS = [
+    ("PATIENT", None, [(0x0010, 0x0010, "PN", "Doe^John"), (0x0010, 0x0020, "LO", "P1")]),
+    (
+        "STUDY",
+        0,
+        [
+            (0x0008, 0x0020, "DA", "20240101"),
+            (0x0008, 0x0030, "TM", "120000"),
+            (0x0008, 0x0050, "SH", "ACC1"),
+            (0x0008, 0x1030, "LO", "Chest"),
+            (0x0020, 0x000D, "UI", "1.2.3.1"),
+            (0x0020, 0x0010, "SH", "1"),
+        ],
+    ),
+    ("SERIES", 1, [(0x0008, 0x0060, "CS", "CT"), (0x0020, 0x000E, "UI", "1.2.3.1.1"), (0x0020, 0x0011, "IS", "1")]),
+    ("IMAGE", 2, [("IMAGES\\IM1", "1.2.3.1.1.1"), (0x0020, 0x0013, "IS", "1")]),
+    ("IMAGE", 2, [("IMAGES\\IM2", "1.2.3.1.1.2"), (0x0020, 0x0013, "IS", "2")]),
+    ("PATIENT", None, [(0x0010, 0x0010, "PN", "Roe^Jane"), (0x0010, 0x0020, "LO", "P2")]),
+    (
+        "STUDY",
+        5,
+        [
+            (0x0008, 0x0020, "DA", "20240202"),
+            (0x0008, 0x0030, "TM", "080000"),
+            (0x0008, 0x0050, "SH", "ACC2"),
+            (0x0008, 0x1030, "LO", "Head"),
+            (0x0020, 0x000D, "UI", "1.2.3.2"),
+            (0x0020, 0x0010, "SH", "2"),
+        ],
+    ),
+    ("SERIES", 6, [(0x0008, 0x0060, "CS", "MR"), (0x0020, 0x000E, "UI", "1.2.3.2.1"), (0x0020, 0x0011, "IS", "1")]),
+    ("IMAGE", 7, [("IMAGES\\IM3", "1.2.3.2.1.1"), (0x0020, 0x0013, "IS", "1")]),
+]
+
+
+def encode_record(index, offsets):
+    record_type, parent, attributes = RECORDS[index]
+
+    siblings = [i for i, r in enumerate(RECORDS) if r[1] == parent]
+    next_sibling = siblings[siblings.index(index) + 1] if siblings.index(index) + 1 < len(siblings) else None
+    children = [i for i, r in enumerate(RECORDS) if r[1] == index]
+
+    data = ul(0x0004, 0x1400, offsets[next_sibling] if next_sibling is not None else 0)
+    data += us(0x0004, 0x1410, 0xFFFF)
+    data += ul(0x0004, 0x1420, offsets[children[0]] if children else 0)
+    data += element(0x0004, 0x1430, "CS", record_type)
+
+    for attribute in attributes:
+        if len(attribute) == 2:
+            file_id, instance_uid = attribute
+            data += element(0x0004, 0x1500, "CS", file_id)
+            data += element(0x0004, 0x1510, "UI", "1.2.840.10008.5.1.4.1.1.2")
+            data += element(0x0004, 0x1511, "UI", instance_uid)
+            data += element(0x0004, 0x1512, "UI", "1.2.840.10008.1.2.1")
+        else:
+            data += element(*attribute)
+
+    return item(data)
+
+
+def encode_dataset(offsets):
+    items = b"".join(encode_record(i, offsets) for i in range(len(RECORDS)))
+    roots = [i for i, r in enumerate(RECORDS) if r[1] is None]
+
+    data = element(0x0004, 0x1130, "CS", "TESTSET")
+    data += ul(0x0004, 0x1200, offsets[roots[0]])
+    data += ul(0x0004, 0x1202, offsets[roots[-1]])
+    data += us(0x0004, 0x1212, 0)
+    records_start = len(data) + 12
+    data += element(0x0004, 0x1220, "SQ", items)
+
+    return data, records_start
+
+
+def file_meta(sop_class_uid, sop_instance_uid):
+    meta_elements = element(0x0002, 0x0001, "OB", b"\0\1")
+    meta_elements += element(0x0002, 0x0002, "UI", sop_class_uid)
+    meta_elements += element(0x0002, 0x0003, "UI", sop_instance_uid)
+    meta_elements += element(0x0002, 0x0010, "UI", "1.2.840.10008.1.2.1")
+    meta_elements += element(0x0002, 0x0012, "UI", "1.2.3.4")
+    return b"\0" * 128 + b"DICM" + ul(0x0002, 0x0000, len(meta_elements)) + meta_elements
+
+
+meta = file_meta("1.2.840.10008.1.3.10", "1.2.3.4.5")
+
+# offsets are from the start of the file, items have fixed sizes so encode twice
+_, records_start = encode_dataset([0] * len(RECORDS))
+offsets = []
+position = len(meta) + records_start
+for i in range(len(RECORDS)):
+    offsets.append(position)
+    position += len(encode_record(i, [0] * len(RECORDS)))
+
+dataset, _ = encode_dataset(offsets)
+
+os.makedirs(os.path.dirname(DICOMDIR_PATH), exist_ok=True)
+with open(DICOMDIR_PATH, "wb") as f:
+    f.write(meta + dataset)
+
+
+# instances referenced by IMAGE records, with the attributes of all their parent records
+for index, (record_type, parent, attributes) in enumerate(RECORDS):
+    if record_type != "IMAGE":
+        continue
+
+    (file_id, instance_uid), *attributes = attributes
+    dataset_elements = [(0x0008, 0x0016, "UI", "1.2.840.10008.5.1.4.1.1.2"), (0x0008, 0x0018, "UI", instance_uid)] + attributes
+
+    while parent is not None:
+        _, next_parent, parent_attributes = RECORDS[parent]
+        dataset_elements += parent_attributes
+        parent = next_parent
+
+    instance_path = os.path.join(os.path.dirname(DICOMDIR_PATH), *file_id.split("\\"))
+    os.makedirs(os.path.dirname(instance_path), exist_ok=True)
+    with open(instance_path, "wb") as f:
+        f.write(file_meta("1.2.840.10008.5.1.4.1.1.2", instance_uid))
+        f.write(b"".join(element(*e) for e in sorted(dataset_elements)))
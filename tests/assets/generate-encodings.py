@@ -0,0 +1,126 @@
+#!/usr/bin/env python3
+
+# Writes the same data set in different transfer syntaxes. Encoded by hand (like generate-dicomdir.py) so that the
+# files also contain group length elements and pixel data.
+
+import os
+import struct
+
+ENCODINGS_DIR = os.path.join(os.path.dirname(__file__), "encodings")
+
+LONG_VRS = {"OB", "OW", "OF", "SQ", "UT", "UN"}
+
+# (group, element, VR, value), values are str, list of ints (for US/OW) or list of items (for SQ)
+DATASET = [
+    (0x0008, 0x0016, "UI", "1.2.840.10008.5.1.4.1.1.7"),
+    (0x0008, 0x0018, "UI", "1.2.3.100"),
+    (0x0008, 0x0060, "CS", "OT"),
+    (
+        0x0008,
+        0x1140,
+        "SQ",
+        [[(0x0008, 0x1150, "UI", "1.2.840.10008.5.1.4.1.1.7"), (0x0008, 0x1155, "UI", "1.2.3.99")]],
+    ),
+    (0x0009, 0x0010, "LO", "ACME 1.0"),
+    (0x0009, 0x1001, "LO", "PRIVATE1"),
+    (0x0010, 0x0010, "PN", "Doe^Jane"),
+    (0x0010, 0x0020, "LO", "P3"),
+    (0x0020, 0x000D, "UI", "1.2.3.3"),
+    (0x0020, 0x000E, "UI", "1.2.3.3.1"),
+    (0x0020, 0x0013, "IS", "1"),
+    (0x0028, 0x0002, "US", [1]),
+    (0x0028, 0x0004, "CS", "MONOCHROME2"),
+    (0x0028, 0x0010, "US", [2]),
+    (0x0028, 0x0011, "US", [2]),
+    (0x0028, 0x0100, "US", [16]),
+    (0x0028, 0x0101, "US", [12]),
+    (0x0028, 0x0102, "US", [11]),
+    (0x0028, 0x0103, "US", [0]),
+    (0x7FE0, 0x0010, "OW", [0, 1000, 2000, 4095]),
+]
+
+
+class TransferSyntax:
+    def __init__(self, uid, little_endian, implicit_vr):
+        self.uid = uid
+        self.order = "<" if little_endian else ">"
+        self.implicit_vr = implicit_vr
+
+
+TRANSFER_SYNTAXES = {
+    "ExplicitVRLittleEndian": TransferSyntax("1.2.840.10008.1.2.1", True, False),
+    "ImplicitVRLittleEndian": TransferSyntax("1.2.840.10008.1.2", True, True),
+    "ExplicitVRBigEndian": TransferSyntax("1.2.840.10008.1.2.2", False, False),
+}
+
+
+def value_bytes(ts, vr, value):
+    if vr == "SQ":
+        return b"".join(item(ts, dataset(ts, elements)) for elements in value)
+    if isinstance(value, list):
+        return struct.pack(f"{ts.order}{len(value)}H", *value)
+
+    value = value.encode("ascii")
+    if len(value) % 2:
+        value += b"\0" if vr == "UI" else b" "
+    return value
+
+
+def element(ts, group, elem, vr, value):
+    value = value_bytes(ts, vr, value)
+
+    header = struct.pack(f"{ts.order}HH", group, elem)
+    if ts.implicit_vr:
+        header += struct.pack(f"{ts.order}I", len(value))
+    elif vr in LONG_VRS:
+        header += vr.encode("ascii") + struct.pack(f"{ts.order}HI", 0, len(value))
+    else:
+        header += vr.encode("ascii") + struct.pack(f"{ts.order}H", len(value))
+
+    return header + value
+
+
+def item(ts, data):
+    return struct.pack(f"{ts.order}HHI", 0xFFFE, 0xE000, len(data)) + data
+
+
+def dataset(ts, elements, group_lengths=False):
+    data = b""
+    group = None
+    for e in elements:
+        if group_lengths and e[0] != group:
+            group = e[0]
+            group_data = b"".join(element(ts, *g) for g in elements if g[0] == group)
+            data += length_element(ts, group, len(group_data))
+        data += element(ts, *e)
+    return data
+
+
+def length_element(ts, group, length):
+    header = struct.pack(f"{ts.order}HH", group, 0x0000)
+    if ts.implicit_vr:
+        header += struct.pack(f"{ts.order}I", 4)
+    else:
+        header += b"UL" + struct.pack(f"{ts.order}H", 4)
+    return header + struct.pack(f"{ts.order}I", length)
+
+
+def file_meta(ts, preamble):
+    le = TRANSFER_SYNTAXES["ExplicitVRLittleEndian"]
+    meta_elements = element(le, 0x0002, 0x0001, "OB", "\0\1")
+    meta_elements += element(le, 0x0002, 0x0002, "UI", "1.2.840.10008.5.1.4.1.1.7")
+    meta_elements += element(le, 0x0002, 0x0003, "UI", "1.2.3.100")
+    meta_elements += element(le, 0x0002, 0x0010, "UI", ts.uid)
+    meta_elements += element(le, 0x0002, 0x0012, "UI", "1.2.3.4")
+    start = b"\0" * 128 + b"DICM" if preamble else b"DICM"
+    return start + length_element(le, 0x0002, len(meta_elements)) + meta_elements
+
+
+os.makedirs(ENCODINGS_DIR, exist_ok=True)
+
+for name, ts in TRANSFER_SYNTAXES.items():
+    # only Implicit VR Little Endian has group length elements and no preamble
+    group_lengths = ts.implicit_vr
+    with open(os.path.join(ENCODINGS_DIR, f"{name}.dcm"), "wb") as f:
+        f.write(file_meta(ts, preamble=not group_lengths))
+        f.write(dataset(ts, DATASET, group_lengths))
@@ -2,7 +2,11 @@
 
 from pydicom.dataset import FileDataset, FileMetaDataset
 import pydicom
+import gzip
+import io
 import os
+import tarfile
+import zipfile
 
 ASSETS_DIR = os.path.dirname(__file__)
 
@@ -35,7 +39,7 @@ for transfer_syntax in [
     ExplicitVRLittleEndian,
     ImplicitVRLittleEndian,
     ExplicitVRBigEndian,
-    # DeflatedExplicitVRLittleEndian, # TODO
+    DeflatedExplicitVRLittleEndian,
 ]:
     for preamble in [False, True]:
         suffix = "Preamble" if preamble else "NoPreamble"
@@ -58,3 +62,41 @@ for transfer_syntax in [
         ds.PatientName = name
 
         ds.save_as(os.path.join(ASSETS_DIR, filename))
+
+
+# Archives with DICOM files and a non-DICOM member
+ARCHIVES_DIR = os.path.join(ASSETS_DIR, "archives")
+ARCHIVE_MEMBERS = ["ExplicitVRLittleEndian-Preamble.dcm", "ImplicitVRLittleEndian-NoPreamble.dcm"]
+with open(os.path.join(ASSETS_DIR, "text", "README.txt"), "rb") as f:
+    README = f.read()
+
+os.makedirs(ARCHIVES_DIR, exist_ok=True)
+
+with zipfile.ZipFile(os.path.join(ARCHIVES_DIR, "studies.zip"), "w", zipfile.ZIP_DEFLATED) as archive:
+    archive.writestr(zipfile.ZipInfo("study/"), b"")
+    for member in ARCHIVE_MEMBERS:
+        archive.write(os.path.join(ASSETS_DIR, member), f"study/{member}")
+    archive.writestr("README.txt", README)
+
+
+def add_tar_member(archive, name, data):
+    info = tarfile.TarInfo(name)
+    info.size = len(data)
+    archive.addfile(info, io.BytesIO(data))
+
+
+tar_data = io.BytesIO()
+with tarfile.open(fileobj=tar_data, mode="w", format=tarfile.USTAR_FORMAT) as archive:
+    for member in ARCHIVE_MEMBERS:
+        with open(os.path.join(ASSETS_DIR, member), "rb") as f:
+            add_tar_member(archive, f"study/{member}", f.read())
+    add_tar_member(archive, "README.txt", README)
+
+with open(os.path.join(ARCHIVES_DIR, "studies.tar.gz"), "wb") as f:
+    with gzip.GzipFile(filename="", fileobj=f, mode="wb", mtime=0) as archive:
+        archive.write(tar_data.getvalue())
+
+with open(os.path.join(ASSETS_DIR, ARCHIVE_MEMBERS[0]), "rb") as src:
+    with open(os.path.join(ARCHIVES_DIR, "single.dcm.gz"), "wb") as f:
+        with gzip.GzipFile(filename=ARCHIVE_MEMBERS[0], fileobj=f, mode="wb", mtime=0) as archive:
+            archive.write(src.read())
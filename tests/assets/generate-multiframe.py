@@ -0,0 +1,151 @@
+#!/usr/bin/env python3
+
+# Writes enhanced multi-frame CT objects with functional groups. Encoded by hand (like generate-encodings.py) in
+# Explicit VR Little Endian.
+
+import os
+import struct
+
+MULTIFRAME_DIR = os.path.join(os.path.dirname(__file__), "multiframe")
+
+LONG_VRS = {"OB", "OW", "OF", "SQ", "UT", "UN"}
+
+ROWS = 2
+COLUMNS = 3
+SLICE_THICKNESS = 2.5
+
+
+def frame_item(frame, position):
+    return [
+        # CTImageFrameTypeSequence
+        (0x0018, 0x9329, "SQ", [[(0x0008, 0x9007, "CS", "ORIGINAL\\PRIMARY\\AXIAL\\NONE")]]),
+        # FrameContentSequence
+        (
+            0x0020,
+            0x9111,
+            "SQ",
+            [
+                [
+                    (0x0018, 0x9074, "DT", f"20240101120000.{frame}"),
+                    (0x0020, 0x9056, "SH", "1"),
+                    (0x0020, 0x9057, "UL", [frame + 1]),
+                    (0x0020, 0x9128, "UL", [1]),
+                    (0x0020, 0x9157, "UL", [1, frame + 1]),
+                ]
+            ],
+        ),
+        # PlanePositionSequence
+        (0x0020, 0x9113, "SQ", [[(0x0020, 0x0032, "DS", "\\".join(f"{p:g}" for p in position))]]),
+    ]
+
+
+SHARED_ITEM = [
+    # PlaneOrientationSequence
+    (0x0020, 0x9116, "SQ", [[(0x0020, 0x0037, "DS", "1\\0\\0\\0\\1\\0")]]),
+    # PixelMeasuresSequence
+    (0x0028, 0x9110, "SQ", [[(0x0018, 0x0050, "DS", f"{SLICE_THICKNESS:g}"), (0x0028, 0x0030, "DS", "0.5\\0.6")]]),
+    # FrameVOILUTSequence
+    (0x0028, 0x9132, "SQ", [[(0x0028, 0x1050, "DS", "40"), (0x0028, 0x1051, "DS", "400")]]),
+    # PixelValueTransformationSequence
+    (0x0028, 0x9145, "SQ", [[(0x0028, 0x1052, "DS", "-1024"), (0x0028, 0x1053, "DS", "1"), (0x0028, 0x1054, "LO", "HU")]]),
+]
+
+
+# (group, element, VR, value), values are str, list of ints (for US/UL/OW), list of tags (for AT) or list of items
+# (for SQ)
+def enhanced_ct(sop_instance_uid, series_instance_uid, positions):
+    frames = len(positions)
+    return [
+        (0x0008, 0x0008, "CS", "ORIGINAL\\PRIMARY\\AXIAL\\NONE"),
+        (0x0008, 0x0016, "UI", "1.2.840.10008.5.1.4.1.1.2.1"),
+        (0x0008, 0x0018, "UI", sop_instance_uid),
+        (0x0008, 0x0060, "CS", "CT"),
+        (0x0010, 0x0010, "PN", "Doe^John"),
+        (0x0010, 0x0020, "LO", "P4"),
+        (0x0020, 0x000D, "UI", "1.2.3.4"),
+        (0x0020, 0x000E, "UI", series_instance_uid),
+        (0x0020, 0x0013, "IS", "1"),
+        (0x0020, 0x0052, "UI", "1.2.3.4.0"),
+        (0x0020, 0x9221, "SQ", [[(0x0020, 0x9164, "UI", "1.2.3.4.9")]]),
+        (
+            0x0020,
+            0x9222,
+            "SQ",
+            [
+                [(0x0020, 0x9164, "UI", "1.2.3.4.9"), (0x0020, 0x9165, "AT", [(0x0020, 0x9056)]), (0x0020, 0x9167, "AT", [(0x0020, 0x9111)])],
+                [(0x0020, 0x9164, "UI", "1.2.3.4.9"), (0x0020, 0x9165, "AT", [(0x0020, 0x9057)]), (0x0020, 0x9167, "AT", [(0x0020, 0x9111)])],
+            ],
+        ),
+        (0x0028, 0x0002, "US", [1]),
+        (0x0028, 0x0004, "CS", "MONOCHROME2"),
+        (0x0028, 0x0008, "IS", str(frames)),
+        (0x0028, 0x0010, "US", [ROWS]),
+        (0x0028, 0x0011, "US", [COLUMNS]),
+        (0x0028, 0x0100, "US", [16]),
+        (0x0028, 0x0101, "US", [12]),
+        (0x0028, 0x0102, "US", [11]),
+        (0x0028, 0x0103, "US", [0]),
+        (0x5200, 0x9229, "SQ", [SHARED_ITEM]),
+        (0x5200, 0x9230, "SQ", [frame_item(frame, position) for frame, position in enumerate(positions)]),
+        # pixel values are the frame number times 100 plus the pixel index
+        (0x7FE0, 0x0010, "OW", [frame * 100 + pixel for frame in range(frames) for pixel in range(ROWS * COLUMNS)]),
+    ]
+
+
+def value_bytes(vr, value):
+    if vr == "SQ":
+        return b"".join(item(dataset(elements)) for elements in value)
+    if vr == "AT":
+        return b"".join(struct.pack("<HH", *tag) for tag in value)
+    if vr == "UL":
+        return struct.pack(f"<{len(value)}I", *value)
+    if isinstance(value, list):
+        return struct.pack(f"<{len(value)}H", *value)
+
+    value = value.encode("ascii")
+    if len(value) % 2:
+        value += b"\0" if vr == "UI" else b" "
+    return value
+
+
+def element(group, elem, vr, value):
+    value = value_bytes(vr, value)
+
+    header = struct.pack("<HH", group, elem)
+    if vr in LONG_VRS:
+        header += vr.encode("ascii") + struct.pack("<HI", 0, len(value))
+    else:
+        header += vr.encode("ascii") + struct.pack("<H", len(value))
+
+    return header + value
+
+
+def item(data):
+    return struct.pack("<HHI", 0xFFFE, 0xE000, len(data)) + data
+
+
+def dataset(elements):
+    return b"".join(element(*e) for e in elements)
+
+
+def file_meta(sop_instance_uid):
+    meta_elements = element(0x0002, 0x0001, "OB", "\0\1")
+    meta_elements += element(0x0002, 0x0002, "UI", "1.2.840.10008.5.1.4.1.1.2.1")
+    meta_elements += element(0x0002, 0x0003, "UI", sop_instance_uid)
+    meta_elements += element(0x0002, 0x0010, "UI", "1.2.840.10008.1.2.1")
+    meta_elements += element(0x0002, 0x0012, "UI", "1.2.3.4")
+    group_length = struct.pack("<HH", 0x0002, 0x0000) + b"UL" + struct.pack("<HI", 4, len(meta_elements))
+    return b"\0" * 128 + b"DICM" + group_length + meta_elements
+
+
+os.makedirs(MULTIFRAME_DIR, exist_ok=True)
+
+# a regular volume of axial slices
+with open(os.path.join(MULTIFRAME_DIR, "EnhancedCT.dcm"), "wb") as f:
+    f.write(file_meta("1.2.3.200"))
+    f.write(dataset(enhanced_ct("1.2.3.200", "1.2.3.4.1", [(-10, -20, SLICE_THICKNESS * frame) for frame in range(3)])))
+
+# a missing slice and a gantry tilt of atan(0.2), i.e. about 11.3 degrees
+with open(os.path.join(MULTIFRAME_DIR, "EnhancedCT-Irregular.dcm"), "wb") as f:
+    f.write(file_meta("1.2.3.201"))
+    f.write(dataset(enhanced_ct("1.2.3.201", "1.2.3.4.2", [(-10, -20 + 0.2 * z, z) for z in [0, 2.5, 5, 10]])))
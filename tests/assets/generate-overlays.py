@@ -0,0 +1,136 @@
+#!/usr/bin/env python3
+
+# Writes an image with overlays in group 60xx and in the high bits of pixel data. Encoded by hand (like
+# generate-multiframe.py) in Explicit VR Little Endian.
+
+import os
+import struct
+
+OVERLAYS_DIR = os.path.join(os.path.dirname(__file__), "overlays")
+
+LONG_VRS = {"OB", "OW", "OF", "SQ", "UT", "UN"}
+
+SECONDARY_CAPTURE = "1.2.840.10008.5.1.4.1.1.7"
+
+ROWS = 4
+COLUMNS = 5
+
+# overlay 6000, a diagonal
+ARROW = [[1 if row == column else 0 for column in range(COLUMNS)] for row in range(ROWS)]
+
+# overlay 6002 embedded in bit 12 of the pixel data, a frame around the image
+BORDER = [[1 if row in (0, ROWS - 1) or column in (0, COLUMNS - 1) else 0 for column in range(COLUMNS)] for row in range(ROWS)]
+
+# bit 13 set in a single pixel, without overlay attributes
+UNDECLARED = (2, 3)
+
+
+# packs bits row by row, the first pixel in the least significant bit of the first byte
+def pack_bits(bits):
+    packed = bytearray((len(bits) + 7) // 8)
+    for index, bit in enumerate(bits):
+        if bit:
+            packed[index // 8] |= 1 << (index % 8)
+    return bytes(packed)
+
+
+def overlay(group, rows, columns, overlay_type, origin, description, label, data=None, bits_allocated=1, bit_position=0, frames=None):
+    elements = [
+        (group, 0x0010, "US", [rows]),
+        (group, 0x0011, "US", [columns]),
+        (group, 0x0022, "LO", description),
+        (group, 0x0040, "CS", overlay_type),
+        (group, 0x0050, "SS", origin),
+        (group, 0x0100, "US", [bits_allocated]),
+        (group, 0x0102, "US", [bit_position]),
+        (group, 0x1500, "LO", label),
+    ]
+    if frames:
+        elements.append((group, 0x0015, "IS", str(frames)))
+    if data is not None:
+        elements.append((group, 0x3000, "OW", data))
+    return sorted(elements)
+
+
+def image():
+    pixels = []
+    for row in range(ROWS):
+        for column in range(COLUMNS):
+            value = row * 100 + column
+            value |= BORDER[row][column] << 12
+            if (row, column) == UNDECLARED:
+                value |= 1 << 13
+            pixels.append(value)
+
+    two_frames = pack_bits([1, 0, 0, 1] + [0, 1, 1, 0])
+
+    return sorted(
+        [
+            (0x0008, 0x0016, "UI", SECONDARY_CAPTURE),
+            (0x0008, 0x0018, "UI", "1.2.3.500"),
+            (0x0008, 0x0060, "CS", "OT"),
+            (0x0010, 0x0010, "PN", "Doe^John"),
+            (0x0010, 0x0020, "LO", "P4"),
+            (0x0020, 0x000D, "UI", "1.2.3.4"),
+            (0x0020, 0x000E, "UI", "1.2.3.4.7"),
+            (0x0028, 0x0002, "US", [1]),
+            (0x0028, 0x0004, "CS", "MONOCHROME2"),
+            (0x0028, 0x0010, "US", [ROWS]),
+            (0x0028, 0x0011, "US", [COLUMNS]),
+            (0x0028, 0x0100, "US", [16]),
+            (0x0028, 0x0101, "US", [12]),
+            (0x0028, 0x0102, "US", [11]),
+            (0x0028, 0x0103, "US", [0]),
+            (0x7FE0, 0x0010, "OW", struct.pack(f"<{len(pixels)}H", *pixels)),
+        ]
+        + overlay(0x6000, ROWS, COLUMNS, "G", [1, 1], "Arrow", "ANNOTATION", pack_bits([bit for row in ARROW for bit in row]))
+        + overlay(0x6002, ROWS, COLUMNS, "R", [1, 1], "Border", "ROI", bits_allocated=16, bit_position=12)
+        + overlay(0x6004, 2, 2, "G", [2, 3], "Corners", "CINE", two_frames, frames=2)
+    )
+
+
+def value_bytes(vr, value):
+    if vr == "SS":
+        return struct.pack(f"<{len(value)}h", *value)
+    if isinstance(value, bytes):
+        return value + b"\0" * (len(value) % 2)
+    if isinstance(value, list):
+        return struct.pack(f"<{len(value)}H", *value)
+
+    value = value.encode("ascii")
+    if len(value) % 2:
+        value += b"\0" if vr == "UI" else b" "
+    return value
+
+
+def element(group, elem, vr, value):
+    value = value_bytes(vr, value)
+
+    header = struct.pack("<HH", group, elem)
+    if vr in LONG_VRS:
+        header += vr.encode("ascii") + struct.pack("<HI", 0, len(value))
+    else:
+        header += vr.encode("ascii") + struct.pack("<H", len(value))
+
+    return header + value
+
+
+def dataset(elements):
+    return b"".join(element(*e) for e in elements)
+
+
+def file_meta(sop_class_uid, sop_instance_uid):
+    meta_elements = element(0x0002, 0x0001, "OB", b"\0\1")
+    meta_elements += element(0x0002, 0x0002, "UI", sop_class_uid)
+    meta_elements += element(0x0002, 0x0003, "UI", sop_instance_uid)
+    meta_elements += element(0x0002, 0x0010, "UI", "1.2.840.10008.1.2.1")
+    meta_elements += element(0x0002, 0x0012, "UI", "1.2.3.4")
+    group_length = struct.pack("<HH", 0x0002, 0x0000) + b"UL" + struct.pack("<HI", 4, len(meta_elements))
+    return b"\0" * 128 + b"DICM" + group_length + meta_elements
+
+
+os.makedirs(OVERLAYS_DIR, exist_ok=True)
+
+with open(os.path.join(OVERLAYS_DIR, "Overlays.dcm"), "wb") as f:
+    f.write(file_meta(SECONDARY_CAPTURE, "1.2.3.500"))
+    f.write(dataset(image()))
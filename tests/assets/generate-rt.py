@@ -0,0 +1,252 @@
+#!/usr/bin/env python3
+
+# Writes radiotherapy objects. Encoded by hand (like generate-multiframe.py) in Explicit VR Little Endian.
+
+import os
+import struct
+
+RT_DIR = os.path.join(os.path.dirname(__file__), "rt")
+
+LONG_VRS = {"OB", "OW", "OF", "SQ", "UT", "UN"}
+
+CT_IMAGE = "1.2.840.10008.5.1.4.1.1.2"
+RT_DOSE = "1.2.840.10008.5.1.4.1.1.481.2"
+RT_STRUCTURE_SET = "1.2.840.10008.5.1.4.1.1.481.3"
+RT_PLAN = "1.2.840.10008.5.1.4.1.1.481.5"
+
+FRAME_OF_REFERENCE = "1.2.3.4.8"
+
+
+def ds(*values):
+    return "\\".join(f"{value:g}" for value in values)
+
+
+def common(sop_class_uid, sop_instance_uid, modality, series_instance_uid):
+    return [
+        (0x0008, 0x0016, "UI", sop_class_uid),
+        (0x0008, 0x0018, "UI", sop_instance_uid),
+        (0x0008, 0x0060, "CS", modality),
+        (0x0010, 0x0010, "PN", "Doe^John"),
+        (0x0010, 0x0020, "LO", "P4"),
+        (0x0020, 0x000D, "UI", "1.2.3.4"),
+        (0x0020, 0x000E, "UI", series_instance_uid),
+    ]
+
+
+def contour(number, geometric_type, points, image=None):
+    elements = [
+        (0x3006, 0x0042, "CS", geometric_type),
+        (0x3006, 0x0046, "IS", str(len(points))),
+        (0x3006, 0x0048, "IS", str(number)),
+        (0x3006, 0x0050, "DS", ds(*[coordinate for point in points for coordinate in point])),
+    ]
+    if image:
+        elements.append((0x3006, 0x0016, "SQ", [[(0x0008, 0x1150, "UI", CT_IMAGE), (0x0008, 0x1155, "UI", image)]]))
+    return sorted(elements)
+
+
+def structure_set():
+    rois = [(1, "External"), (2, "PTV"), (3, "Marker"), (4, "Bladder")]
+    structure_set_rois = [
+        [(0x3006, 0x0022, "IS", str(number)), (0x3006, 0x0024, "UI", FRAME_OF_REFERENCE), (0x3006, 0x0026, "LO", name)] for number, name in rois
+    ]
+
+    external = [
+        contour(1, "CLOSED_PLANAR", [(-10, -10, 0), (10, -10, 0), (10, 10, 0), (-10, 10, 0)], "1.2.3.101"),
+        contour(2, "CLOSED_PLANAR", [(-8, -8, 2.5), (8, -8, 2.5), (0, 8, 2.5)], "1.2.3.102"),
+    ]
+    ptv = [contour(1, "CLOSED_PLANAR", [(-2.5, -2.5, 0), (2.5, -2.5, 0), (0, 2.5, 0)], "1.2.3.101")]
+    marker = [contour(1, "POINT", [(1.5, -3, 1.25)])]
+
+    # the bladder has no contours yet
+    roi_contours = [
+        [(0x3006, 0x002A, "IS", "0\\255\\0"), (0x3006, 0x0040, "SQ", external), (0x3006, 0x0084, "IS", "1")],
+        [(0x3006, 0x002A, "IS", "255\\0\\0"), (0x3006, 0x0040, "SQ", ptv), (0x3006, 0x0084, "IS", "2")],
+        [(0x3006, 0x002A, "IS", "0\\0\\255"), (0x3006, 0x0040, "SQ", marker), (0x3006, 0x0084, "IS", "3")],
+    ]
+
+    observations = [
+        [(0x3006, 0x0082, "IS", str(number)), (0x3006, 0x0084, "IS", str(number)), (0x3006, 0x00A4, "CS", interpreted_type)]
+        for number, interpreted_type in [(1, "EXTERNAL"), (2, "PTV"), (3, "MARKER"), (4, "ORGAN")]
+    ]
+
+    return sorted(
+        common(RT_STRUCTURE_SET, "1.2.3.600", "RTSTRUCT", "1.2.3.4.9")
+        + [
+            (0x3006, 0x0002, "SH", "PLAN"),
+            (0x3006, 0x0020, "SQ", structure_set_rois),
+            (0x3006, 0x0039, "SQ", roi_contours),
+            (0x3006, 0x0080, "SQ", observations),
+        ]
+    )
+
+
+def reference(sop_class_uid, sop_instance_uid):
+    return [(0x0008, 0x1150, "UI", sop_class_uid), (0x0008, 0x1155, "UI", sop_instance_uid)]
+
+
+# attributes are only present in the first control point and in those that change them
+def control_point(index, weight, energy=None, gantry=None, rotation=None, collimator=None, couch=None, isocenter=None):
+    elements = [(0x300A, 0x0112, "IS", str(index)), (0x300A, 0x0134, "DS", ds(weight))]
+    optional = [
+        (0x0114, "DS", energy),
+        (0x011E, "DS", gantry),
+        (0x011F, "CS", rotation),
+        (0x0120, "DS", collimator),
+        (0x0122, "DS", couch),
+        (0x012C, "DS", isocenter),
+    ]
+    elements += [(0x300A, elem, vr, value if vr == "CS" else ds(*value)) for elem, vr, value in optional if value is not None]
+    return sorted(elements)
+
+
+def beam(number, name, beam_type, final_weight, control_points):
+    return [
+        (0x300A, 0x00B2, "SH", "LINAC1"),
+        (0x300A, 0x00B3, "CS", "MU"),
+        (0x300A, 0x00C0, "IS", str(number)),
+        (0x300A, 0x00C2, "LO", name),
+        (0x300A, 0x00C4, "CS", beam_type),
+        (0x300A, 0x00C6, "CS", "PHOTON"),
+        (0x300A, 0x00CE, "CS", "TREATMENT"),
+        (0x300A, 0x010E, "DS", ds(final_weight)),
+        (0x300A, 0x0110, "IS", str(len(control_points))),
+        (0x300A, 0x0111, "SQ", control_points),
+    ]
+
+
+def plan():
+    arc = [
+        control_point(0, 0, energy=[6], gantry=[181], rotation="CW", collimator=[30], couch=[0], isocenter=[0, -2.5, 10]),
+        control_point(1, 0.5, gantry=[270]),
+        control_point(2, 1, gantry=[179], rotation="NONE"),
+    ]
+    lateral = [
+        control_point(0, 0, energy=[10], gantry=[90], rotation="NONE", collimator=[0], couch=[0], isocenter=[0, -2.5, 10]),
+        control_point(1, 100),
+    ]
+    beams = [beam(1, "ARC1", "DYNAMIC", 1, arc), beam(2, "LAT", "STATIC", 100, lateral)]
+
+    referenced_beams = [
+        [(0x300A, 0x0084, "DS", "1"), (0x300A, 0x0086, "DS", ds(meterset)), (0x300C, 0x0006, "IS", str(number))]
+        for number, meterset in [(1, 120.5), (2, 80)]
+    ]
+    fraction_group = [
+        (0x300A, 0x0071, "IS", "1"),
+        (0x300A, 0x0078, "IS", "20"),
+        (0x300A, 0x0080, "IS", "2"),
+        (0x300A, 0x00A0, "IS", "0"),
+        (0x300C, 0x0004, "SQ", referenced_beams),
+    ]
+
+    return common(RT_PLAN, "1.2.3.601", "RTPLAN", "1.2.3.4.10") + [
+        (0x300A, 0x0002, "SH", "PROSTATE"),
+        (0x300A, 0x0003, "LO", "Prostate VMAT"),
+        (0x300A, 0x0006, "DA", "20240102"),
+        (0x300A, 0x000C, "CS", "PATIENT"),
+        (0x300A, 0x0070, "SQ", [fraction_group]),
+        (0x300A, 0x00B0, "SQ", beams),
+        (0x300C, 0x0060, "SQ", [reference(RT_STRUCTURE_SET, "1.2.3.600")]),
+    ]
+
+
+# two frames of 2x3 voxels, 5 mm apart, stored in units of 0.001 Gy, or of 0.000001 Gy with 32 bits allocated
+def dose(sop_instance_uid, bits_allocated):
+    pixels = [0, 1000, 2000, 500, 1500, 2500] + [100, 200, 300, 400, 500, 600]
+    if bits_allocated == 32:
+        pixels = [pixel * 1000 for pixel in pixels]
+        pixel_data = struct.pack(f"<{len(pixels)}I", *pixels)
+        scaling = "0.000001"
+    else:
+        pixel_data = struct.pack(f"<{len(pixels)}H", *pixels)
+        scaling = "0.001"
+
+    return common(RT_DOSE, sop_instance_uid, "RTDOSE", "1.2.3.4.11") + [
+        (0x0020, 0x0032, "DS", ds(-5, -2.5, -10)),
+        (0x0020, 0x0037, "DS", ds(1, 0, 0, 0, 1, 0)),
+        (0x0020, 0x0052, "UI", FRAME_OF_REFERENCE),
+        (0x0028, 0x0002, "US", [1]),
+        (0x0028, 0x0004, "CS", "MONOCHROME2"),
+        (0x0028, 0x0008, "IS", "2"),
+        (0x0028, 0x0009, "AT", [0x3004, 0x000C]),
+        (0x0028, 0x0010, "US", [2]),
+        (0x0028, 0x0011, "US", [3]),
+        (0x0028, 0x0030, "DS", ds(2.5, 2.5)),
+        (0x0028, 0x0100, "US", [bits_allocated]),
+        (0x0028, 0x0101, "US", [bits_allocated]),
+        (0x0028, 0x0102, "US", [bits_allocated - 1]),
+        (0x0028, 0x0103, "US", [0]),
+        (0x3004, 0x0002, "CS", "GY"),
+        (0x3004, 0x0004, "CS", "PHYSICAL"),
+        (0x3004, 0x000A, "CS", "PLAN"),
+        (0x3004, 0x000C, "DS", ds(0, 5)),
+        (0x3004, 0x000E, "DS", scaling),
+        (0x300C, 0x0002, "SQ", [reference(RT_PLAN, "1.2.3.601")]),
+        (0x7FE0, 0x0010, "OW", pixel_data),
+    ]
+
+
+def value_bytes(vr, value):
+    if vr == "SQ":
+        return b"".join(item(dataset(elements)) for elements in value)
+    if isinstance(value, bytes):
+        return value + b"\0" * (len(value) % 2)
+    if isinstance(value, list):
+        return struct.pack(f"<{len(value)}H", *value)
+
+    value = value.encode("ascii")
+    if len(value) % 2:
+        value += b"\0" if vr == "UI" else b" "
+    return value
+
+
+def element(group, elem, vr, value):
+    value = value_bytes(vr, value)
+
+    header = struct.pack("<HH", group, elem)
+    if vr in LONG_VRS:
+        header += vr.encode("ascii") + struct.pack("<HI", 0, len(value))
+    else:
+        header += vr.encode("ascii") + struct.pack("<H", len(value))
+
+    return header + value
+
+
+def item(data):
+    return struct.pack("<HHI", 0xFFFE, 0xE000, len(data)) + data
+
+
+def dataset(elements):
+    return b"".join(element(*e) for e in sorted(elements))
+
+
+def file_meta(sop_class_uid, sop_instance_uid):
+    meta_elements = element(0x0002, 0x0001, "OB", b"\0\1")
+    meta_elements += element(0x0002, 0x0002, "UI", sop_class_uid)
+    meta_elements += element(0x0002, 0x0003, "UI", sop_instance_uid)
+    meta_elements += element(0x0002, 0x0010, "UI", "1.2.840.10008.1.2.1")
+    meta_elements += element(0x0002, 0x0012, "UI", "1.2.3.4")
+    group_length = struct.pack("<HH", 0x0002, 0x0000) + b"UL" + struct.pack("<HI", 4, len(meta_elements))
+    return b"\0" * 128 + b"DICM" + group_length + meta_elements
+
+
+os.makedirs(RT_DIR, exist_ok=True)
+
+# four ROIs: two contours of the external, one of the PTV, a point marker and an organ without contours
+with open(os.path.join(RT_DIR, "RTSTRUCT.dcm"), "wb") as f:
+    f.write(file_meta(RT_STRUCTURE_SET, "1.2.3.600"))
+    f.write(dataset(structure_set()))
+
+# an arc and a static beam in a single fraction group
+with open(os.path.join(RT_DIR, "RTPLAN.dcm"), "wb") as f:
+    f.write(file_meta(RT_PLAN, "1.2.3.601"))
+    f.write(dataset(plan()))
+
+with open(os.path.join(RT_DIR, "RTDOSE.dcm"), "wb") as f:
+    f.write(file_meta(RT_DOSE, "1.2.3.602"))
+    f.write(dataset(dose("1.2.3.602", 16)))
+
+# the same grid with 32 bits allocated, as commonly exported by treatment planning systems
+with open(os.path.join(RT_DIR, "RTDOSE-32.dcm"), "wb") as f:
+    f.write(file_meta(RT_DOSE, "1.2.3.603"))
+    f.write(dataset(dose("1.2.3.603", 32)))
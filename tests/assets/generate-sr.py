@@ -0,0 +1,274 @@
+#!/usr/bin/env python3
+
+# Writes Structured Report documents with a content tree covering the value types. Encoded by hand (like
+# generate-multiframe.py) in Explicit VR Little Endian.
+
+import os
+import struct
+
+SR_DIR = os.path.join(os.path.dirname(__file__), "sr")
+
+LONG_VRS = {"OB", "OW", "OF", "SQ", "UT", "UN"}
+
+COMPREHENSIVE_SR = "1.2.840.10008.5.1.4.1.1.88.33"
+XRAY_RADIATION_DOSE_SR = "1.2.840.10008.5.1.4.1.1.88.67"
+
+
+def code(value, scheme, meaning):
+    return [(0x0008, 0x0100, "SH", value), (0x0008, 0x0102, "SH", scheme), (0x0008, 0x0104, "LO", meaning)]
+
+
+# content item with a concept name, the value elements and children
+def content_item(relationship, value_type, concept, value=(), children=()):
+    elements = [(0x0040, 0xA010, "CS", relationship), (0x0040, 0xA040, "CS", value_type)]
+    elements += [(0x0040, 0xA043, "SQ", [concept])]
+    elements += list(value)
+    if children:
+        elements += [(0x0040, 0xA730, "SQ", list(children))]
+    return sorted(elements)
+
+
+def num(relationship, concept, value, units, children=()):
+    measured_value = [(0x0040, 0x08EA, "SQ", [units]), (0x0040, 0xA30A, "DS", value)]
+    return content_item(relationship, "NUM", concept, [(0x0040, 0xA300, "SQ", [measured_value])], children)
+
+
+def coded(relationship, concept, value):
+    return content_item(relationship, "CODE", concept, [(0x0040, 0xA168, "SQ", [value])])
+
+
+def image(relationship, concept, sop_class_uid, sop_instance_uid, frames):
+    reference = [(0x0008, 0x1150, "UI", sop_class_uid), (0x0008, 0x1155, "UI", sop_instance_uid)]
+    reference += [(0x0008, 0x1160, "IS", "\\".join(str(frame) for frame in frames))]
+    return content_item(relationship, "IMAGE", concept, [(0x0008, 0x1199, "SQ", [reference])])
+
+
+def by_reference(relationship, identifier):
+    return [(0x0040, 0xA010, "CS", relationship), (0x0040, 0xDB73, "UL", identifier)]
+
+
+def measurement_report():
+    lesion = [
+        num("CONTAINS", code("118565006", "SCT", "Volume"), "12.5", code("cm3", "UCUM", "cubic centimeter")),
+        num(
+            "CONTAINS",
+            code("410668003", "SCT", "Length"),
+            "4.2",
+            code("mm", "UCUM", "millimeter"),
+            [
+                content_item(
+                    "INFERRED FROM",
+                    "SCOORD",
+                    code("111030", "DCM", "Image Region"),
+                    [(0x0070, 0x0022, "FL", [10.0, 20.0, 30.5, 40.0]), (0x0070, 0x0023, "CS", "POLYLINE")],
+                    [image("SELECTED FROM", code("121191", "DCM", "Referenced Segment"), "1.2.840.10008.5.1.4.1.1.2", "1.2.3.100", [1])],
+                )
+            ],
+        ),
+        content_item(
+            "CONTAINS",
+            "TEXT",
+            code("121106", "DCM", "Comment"),
+            [(0x0040, 0xA160, "UT", "Stable since prior")],
+            [by_reference("INFERRED FROM", [1, 4, 1])],
+        ),
+        coded("CONTAINS", code("121071", "DCM", "Finding"), code("4147007", "SCT", "Mass")),
+        content_item("CONTAINS", "UIDREF", code("112002", "DCM", "Series Instance UID"), [(0x0040, 0xA124, "UI", "1.2.3.4.1")]),
+        content_item("CONTAINS", "DATE", code("111060", "DCM", "Study Date"), [(0x0040, 0xA121, "DA", "20240101")]),
+    ]
+
+    root = [
+        (0x0040, 0xA040, "CS", "CONTAINER"),
+        (0x0040, 0xA043, "SQ", [code("126000", "DCM", "Imaging Measurement Report")]),
+        (0x0040, 0xA050, "CS", "SEPARATE"),
+        (
+            0x0040,
+            0xA730,
+            "SQ",
+            [
+                coded("HAS CONCEPT MOD", code("121049", "DCM", "Language of Content Item and Descendants"), code("en", "RFC5646", "English")),
+                content_item("HAS OBS CONTEXT", "PNAME", code("121008", "DCM", "Person Observer Name"), [(0x0040, 0xA123, "PN", "Smith^Jane")]),
+                content_item("HAS OBS CONTEXT", "DATETIME", code("111526", "DCM", "DateTime Started"), [(0x0040, 0xA120, "DT", "20240101120000")]),
+                content_item("CONTAINS", "CONTAINER", code("126010", "DCM", "Imaging Measurements"), [(0x0040, 0xA050, "CS", "SEPARATE")], lesion),
+            ],
+        ),
+    ]
+
+    return document(COMPREHENSIVE_SR, "1.2.3.300", "1.2.3.4.3", root)
+
+
+def document(sop_class_uid, sop_instance_uid, series_instance_uid, root):
+    return sorted(
+        [
+            (0x0008, 0x0016, "UI", sop_class_uid),
+            (0x0008, 0x0018, "UI", sop_instance_uid),
+            (0x0008, 0x0060, "CS", "SR"),
+            (0x0010, 0x0010, "PN", "Doe^John"),
+            (0x0010, 0x0020, "LO", "P4"),
+            (0x0020, 0x000D, "UI", "1.2.3.4"),
+            (0x0020, 0x000E, "UI", series_instance_uid),
+            (0x0040, 0xA491, "CS", "COMPLETE"),
+            (0x0040, 0xA493, "CS", "VERIFIED"),
+        ]
+        + root
+    )
+
+
+def dose_report(children):
+    return [
+        (0x0040, 0xA040, "CS", "CONTAINER"),
+        (0x0040, 0xA043, "SQ", [code("113701", "DCM", "X-Ray Radiation Dose Report")]),
+        (0x0040, 0xA050, "CS", "SEPARATE"),
+        (0x0040, 0xA730, "SQ", children),
+    ]
+
+
+def dcm_num(value, meaning, number, units):
+    return num("CONTAINS", code(value, "DCM", meaning), number, code(units, "UCUM", units))
+
+
+def ct_acquisition(protocol, uid, length, kvp, tube_current, ctdi_vol, dlp):
+    source = [
+        content_item("CONTAINS", "TEXT", code("113832", "DCM", "Identification of the X-Ray Source"), [(0x0040, 0xA160, "UT", "A")]),
+        dcm_num("113733", "KVP", kvp, "kV"),
+        dcm_num("113833", "Maximum X-Ray Tube Current", tube_current, "mA"),
+        dcm_num("113734", "X-Ray Tube Current", tube_current, "mA"),
+        dcm_num("113834", "Exposure Time per Rotation", "0.5", "s"),
+    ]
+    parameters = [
+        dcm_num("113824", "Exposure Time", "5", "s"),
+        dcm_num("113825", "Scanning Length", length, "mm"),
+        dcm_num("113828", "Pitch Factor", "1", "{ratio}"),
+        content_item("CONTAINS", "CONTAINER", code("113831", "DCM", "CT X-Ray Source Parameters"), [], source),
+    ]
+    dose = [
+        dcm_num("113830", "Mean CTDIvol", ctdi_vol, "mGy"),
+        coded("CONTAINS", code("113835", "DCM", "CTDIw Phantom Type"), code("113691", "DCM", "IEC Body Dosimetry Phantom")),
+        dcm_num("113838", "DLP", dlp, "mGy.cm"),
+    ]
+    return content_item(
+        "CONTAINS",
+        "CONTAINER",
+        code("113819", "DCM", "CT Acquisition"),
+        [],
+        [
+            content_item("CONTAINS", "TEXT", code("125203", "DCM", "Acquisition Protocol"), [(0x0040, 0xA160, "UT", protocol)]),
+            coded("CONTAINS", code("123014", "DCM", "Target Region"), code("416550000", "SCT", "Chest and Abdomen")),
+            coded("CONTAINS", code("113820", "DCM", "CT Acquisition Type"), code("P5-08001", "SRT", "Spiral Acquisition")),
+            content_item("CONTAINS", "UIDREF", code("113769", "DCM", "Irradiation Event UID"), [(0x0040, 0xA124, "UI", uid)]),
+            content_item("CONTAINS", "CONTAINER", code("113822", "DCM", "CT Acquisition Parameters"), [], parameters),
+            content_item("CONTAINS", "CONTAINER", code("113829", "DCM", "CT Dose"), [], dose),
+        ],
+    )
+
+
+def ct_dose_report():
+    return dose_report(
+        [
+            coded("HAS CONCEPT MOD", code("121058", "DCM", "Procedure reported"), code("P5-08000", "SRT", "Computed Tomography X-Ray")),
+            content_item(
+                "CONTAINS",
+                "CONTAINER",
+                code("113811", "DCM", "CT Accumulated Dose Data"),
+                [],
+                [
+                    dcm_num("113812", "Total Number of Irradiation Events", "2", "{events}"),
+                    dcm_num("113813", "CT Dose Length Product Total", "650.5", "mGy.cm"),
+                ],
+            ),
+            ct_acquisition("Chest", "1.2.3.4.5.1", "300", "120", "200", "10.5", "400.25"),
+            ct_acquisition("Abdomen", "1.2.3.4.5.2", "250", "100", "150", "8", "250.25"),
+        ]
+    )
+
+
+def projection_dose_report():
+    return dose_report(
+        [
+            content_item(
+                "CONTAINS",
+                "CONTAINER",
+                code("113702", "DCM", "Accumulated X-Ray Dose Data"),
+                [],
+                [dcm_num("113722", "Dose Area Product Total", "0.00012", "Gy.m2"), dcm_num("113725", "Dose (RP) Total", "0.015", "Gy")],
+            ),
+            content_item(
+                "CONTAINS",
+                "CONTAINER",
+                code("113706", "DCM", "Irradiation Event X-Ray Data"),
+                [],
+                [
+                    content_item("CONTAINS", "UIDREF", code("113769", "DCM", "Irradiation Event UID"), [(0x0040, 0xA124, "UI", "1.2.3.4.6.1")]),
+                    coded("CONTAINS", code("113721", "DCM", "Irradiation Event Type"), code("113611", "DCM", "Stationary Acquisition")),
+                    dcm_num("122130", "Dose Area Product", "0.00012", "Gy.m2"),
+                    dcm_num("113738", "Dose (RP)", "0.015", "Gy"),
+                    dcm_num("113733", "KVP", "70", "kV"),
+                    dcm_num("113734", "X-Ray Tube Current", "400", "mA"),
+                ],
+            ),
+        ]
+    )
+
+
+def value_bytes(vr, value):
+    if vr == "SQ":
+        return b"".join(item(dataset(elements)) for elements in value)
+    if vr == "UL":
+        return struct.pack(f"<{len(value)}I", *value)
+    if vr == "FL":
+        return struct.pack(f"<{len(value)}f", *value)
+    if isinstance(value, list):
+        return struct.pack(f"<{len(value)}H", *value)
+
+    value = value.encode("ascii")
+    if len(value) % 2:
+        value += b"\0" if vr == "UI" else b" "
+    return value
+
+
+def element(group, elem, vr, value):
+    value = value_bytes(vr, value)
+
+    header = struct.pack("<HH", group, elem)
+    if vr in LONG_VRS:
+        header += vr.encode("ascii") + struct.pack("<HI", 0, len(value))
+    else:
+        header += vr.encode("ascii") + struct.pack("<H", len(value))
+
+    return header + value
+
+
+def item(data):
+    return struct.pack("<HHI", 0xFFFE, 0xE000, len(data)) + data
+
+
+def dataset(elements):
+    return b"".join(element(*e) for e in elements)
+
+
+def file_meta(sop_class_uid, sop_instance_uid):
+    meta_elements = element(0x0002, 0x0001, "OB", "\0\1")
+    meta_elements += element(0x0002, 0x0002, "UI", sop_class_uid)
+    meta_elements += element(0x0002, 0x0003, "UI", sop_instance_uid)
+    meta_elements += element(0x0002, 0x0010, "UI", "1.2.840.10008.1.2.1")
+    meta_elements += element(0x0002, 0x0012, "UI", "1.2.3.4")
+    group_length = struct.pack("<HH", 0x0002, 0x0000) + b"UL" + struct.pack("<HI", 4, len(meta_elements))
+    return b"\0" * 128 + b"DICM" + group_length + meta_elements
+
+
+os.makedirs(SR_DIR, exist_ok=True)
+
+# a measurement report with one item of each common value type
+with open(os.path.join(SR_DIR, "MeasurementReport.dcm"), "wb") as f:
+    f.write(file_meta(COMPREHENSIVE_SR, "1.2.3.300"))
+    f.write(dataset(measurement_report()))
+
+# a CT dose report (TID 10011) with two acquisitions
+with open(os.path.join(SR_DIR, "CTDoseReport.dcm"), "wb") as f:
+    f.write(file_meta(XRAY_RADIATION_DOSE_SR, "1.2.3.301"))
+    f.write(dataset(document(XRAY_RADIATION_DOSE_SR, "1.2.3.301", "1.2.3.4.4", ct_dose_report())))
+
+# a projection X-ray dose report (TID 10001) with a single irradiation event
+with open(os.path.join(SR_DIR, "ProjectionDoseReport.dcm"), "wb") as f:
+    f.write(file_meta(XRAY_RADIATION_DOSE_SR, "1.2.3.302"))
+    f.write(dataset(document(XRAY_RADIATION_DOSE_SR, "1.2.3.302", "1.2.3.4.5", projection_dose_report())))
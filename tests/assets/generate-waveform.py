@@ -0,0 +1,124 @@
+#!/usr/bin/env python3
+
+# Writes a waveform object with two multiplex groups. Encoded by hand (like generate-multiframe.py) in Explicit VR
+# Little Endian.
+
+import os
+import struct
+
+WAVEFORM_DIR = os.path.join(os.path.dirname(__file__), "waveform")
+
+LONG_VRS = {"OB", "OW", "OF", "SQ", "UT", "UN"}
+
+GENERAL_ECG = "1.2.840.10008.5.1.4.1.1.9.1.2"
+
+
+def code(value, scheme, meaning):
+    return [(0x0008, 0x0100, "SH", value), (0x0008, 0x0102, "SH", scheme), (0x0008, 0x0104, "LO", meaning)]
+
+
+def channel(label, source, sensitivity, units, baseline, correction):
+    elements = [
+        (0x003A, 0x0208, "SQ", [source]),
+        (0x003A, 0x0210, "DS", sensitivity),
+        (0x003A, 0x0211, "SQ", [units]),
+        (0x003A, 0x0212, "DS", correction),
+        (0x003A, 0x0213, "DS", baseline),
+    ]
+    if label:
+        elements.append((0x003A, 0x0203, "SH", label))
+    return sorted(elements)
+
+
+# samples are interleaved, i.e. all channels of the first sample, then all channels of the second sample, ...
+def multiplex_group(label, frequency, channels, bits, interpretation, samples):
+    fmt = {"SS": "h", "SB": "b"}[interpretation]
+    data = b"".join(struct.pack(f"<{len(sample)}{fmt}", *sample) for sample in samples)
+    return [
+        (0x003A, 0x0005, "US", [len(channels)]),
+        (0x003A, 0x0010, "UL", [len(samples)]),
+        (0x003A, 0x001A, "DS", frequency),
+        (0x003A, 0x0020, "SH", label),
+        (0x003A, 0x0200, "SQ", channels),
+        (0x5400, 0x1004, "US", [bits]),
+        (0x5400, 0x1006, "CS", interpretation),
+        (0x5400, 0x1010, "OB" if bits == 8 else "OW", data),
+    ]
+
+
+def ecg():
+    microvolt = code("uV", "UCUM", "microvolt")
+    leads = [
+        channel("", code("5.6.3-9-1", "SCPECG", "Lead I"), "2.5", microvolt, "0", "1"),
+        channel("", code("5.6.3-9-2", "SCPECG", "Lead II"), "2.5", microvolt, "0", "1"),
+        channel("V1", code("5.6.3-9-3", "SCPECG", "Lead V1"), "2.5", microvolt, "0", "1"),
+    ]
+    rhythm = multiplex_group("RHYTHM", "500", leads, 16, "SS", [[0, 10, -10], [100, 200, -300], [4, 8, 12], [-1, -2, -3]])
+
+    pressure = [channel("Pressure", code("2.2.3-1", "LN", "Arterial blood pressure"), "0.5", code("mm[Hg]", "UCUM", "mmHg"), "10", "2")]
+    pressure = multiplex_group("PRESSURE", "250", pressure, 8, "SB", [[0], [-4], [100]])
+
+    return [
+        (0x0008, 0x0016, "UI", GENERAL_ECG),
+        (0x0008, 0x0018, "UI", "1.2.3.400"),
+        (0x0008, 0x0060, "CS", "ECG"),
+        (0x0010, 0x0010, "PN", "Doe^John"),
+        (0x0010, 0x0020, "LO", "P4"),
+        (0x0020, 0x000D, "UI", "1.2.3.4"),
+        (0x0020, 0x000E, "UI", "1.2.3.4.6"),
+        (0x5400, 0x0100, "SQ", [rhythm, pressure]),
+    ]
+
+
+def value_bytes(vr, value):
+    if vr == "SQ":
+        return b"".join(item(dataset(elements)) for elements in value)
+    if vr == "UL":
+        return struct.pack(f"<{len(value)}I", *value)
+    if isinstance(value, bytes):
+        return value + b"\0" * (len(value) % 2)
+    if isinstance(value, list):
+        return struct.pack(f"<{len(value)}H", *value)
+
+    value = value.encode("ascii")
+    if len(value) % 2:
+        value += b"\0" if vr == "UI" else b" "
+    return value
+
+
+def element(group, elem, vr, value):
+    value = value_bytes(vr, value)
+
+    header = struct.pack("<HH", group, elem)
+    if vr in LONG_VRS:
+        header += vr.encode("ascii") + struct.pack("<HI", 0, len(value))
+    else:
+        header += vr.encode("ascii") + struct.pack("<H", len(value))
+
+    return header + value
+
+
+def item(data):
+    return struct.pack("<HHI", 0xFFFE, 0xE000, len(data)) + data
+
+
+def dataset(elements):
+    return b"".join(element(*e) for e in elements)
+
+
+def file_meta(sop_class_uid, sop_instance_uid):
+    meta_elements = element(0x0002, 0x0001, "OB", b"\0\1")
+    meta_elements += element(0x0002, 0x0002, "UI", sop_class_uid)
+    meta_elements += element(0x0002, 0x0003, "UI", sop_instance_uid)
+    meta_elements += element(0x0002, 0x0010, "UI", "1.2.840.10008.1.2.1")
+    meta_elements += element(0x0002, 0x0012, "UI", "1.2.3.4")
+    group_length = struct.pack("<HH", 0x0002, 0x0000) + b"UL" + struct.pack("<HI", 4, len(meta_elements))
+    return b"\0" * 128 + b"DICM" + group_length + meta_elements
+
+
+os.makedirs(WAVEFORM_DIR, exist_ok=True)
+
+# a 3-lead ECG rhythm group and an 8-bit pressure group with a baseline and a correction factor
+with open(os.path.join(WAVEFORM_DIR, "ECG.dcm"), "wb") as f:
+    f.write(file_meta(GENERAL_ECG, "1.2.3.400"))
+    f.write(dataset(ecg()))
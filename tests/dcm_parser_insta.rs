@@ -1,3 +1,5 @@
+#![allow(clippy::result_large_err)]
+
 use crate::test_utils::get_asset_base_path;
 use insta::{assert_ron_snapshot, glob};
 use nu_protocol::Span;
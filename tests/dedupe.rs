@@ -0,0 +1,57 @@
+#![allow(clippy::result_large_err)]
+
+use nu_protocol::Span;
+use test_utils::{get_int_by_cell_path, get_string_by_cell_path, get_string_list_by_cell_path, setup_plugin_for_test};
+
+mod test_utils;
+
+#[test]
+fn test_dedupe_identical() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    // non-DICOM files are skipped
+    let result = plugin_test.eval("[dicomdir/IMAGES/IM1 dicomdir/IMAGES/IM2 text/README.txt dicomdir/IMAGES/IM1] | dcm dedupe")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_list_by_cell_path(&result, "SOPInstanceUID"), ["1.2.3.1.1.1"]);
+    assert_eq!(get_string_by_cell_path(&result, "0.status"), "identical");
+    assert_eq!(get_int_by_cell_path(&result, "0.count"), 2);
+    assert!(get_string_by_cell_path(&result, "0.files.1.path").ends_with("IM1"));
+
+    Ok(())
+}
+
+#[test]
+fn test_dedupe_dataset_identical() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![Box::new(nu_command::BytesAt)])?;
+
+    // the same data set without the preamble, `bytes at` also collects the byte streams into binary values
+    let result =
+        plugin_test.eval("[(open --raw dicomdir/IMAGES/IM1 | bytes at 0..) (open --raw dicomdir/IMAGES/IM1 | bytes at 128..)] | dcm dedupe")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_by_cell_path(&result, "0.status"), "dataset-identical");
+
+    let file_hashes = get_string_list_by_cell_path(&result, "0.files.file_hash");
+    assert_ne!(file_hashes[0], file_hashes[1]);
+
+    let dataset_hashes = get_string_list_by_cell_path(&result, "0.files.dataset_hash");
+    assert_eq!(dataset_hashes[0], dataset_hashes[1]);
+
+    Ok(())
+}
+
+#[test]
+fn test_dedupe_conflicting() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    // same SOP Instance UID, different PatientName
+    let result =
+        plugin_test.eval("[ExplicitVRLittleEndian-Preamble.dcm ImplicitVRLittleEndian-Preamble.dcm dicomdir/IMAGES/IM3] | dcm dedupe --all")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_list_by_cell_path(&result, "SOPInstanceUID"), ["1.2.3", "1.2.3.2.1.1"]);
+    assert_eq!(get_string_list_by_cell_path(&result, "status"), ["conflicting", "unique"]);
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+#![allow(clippy::result_large_err)]
+
+use nu_protocol::Span;
+use test_utils::{get_asset_path, get_string_by_cell_path, setup_plugin_for_test};
+
+mod test_utils;
+
+#[test]
+fn test_dicomdir_tree() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let result = plugin_test.eval("\"dicomdir/DICOMDIR\" | dcm dicomdir")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        2
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.DirectoryRecordType"), "PATIENT");
+    assert_eq!(get_string_by_cell_path(&result, "0.PatientID"), "P1");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.DirectoryRecordType"), "STUDY");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.StudyInstanceUID"), "1.2.3.1");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.children.0.Modality"), "CT");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.children.0.children.1.ReferencedSOPInstanceUIDInFile"), "1.2.3.1.1.2");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.children.0.children.1.path"), get_asset_path("dicomdir/IMAGES/IM2").to_string_lossy());
+
+    assert_eq!(get_string_by_cell_path(&result, "1.PatientID"), "P2");
+    assert_eq!(get_string_by_cell_path(&result, "1.children.0.children.0.Modality"), "MR");
+
+    // links between records are replaced by the tree
+    let patient = result
+        .as_list()?
+        .first()
+        .unwrap()
+        .as_record()?;
+    assert!(!patient.contains("OffsetOfTheNextDirectoryRecord"));
+    assert!(!patient.contains("OffsetOfReferencedLowerLevelDirectoryEntity"));
+
+    Ok(())
+}
+
+#[test]
+fn test_dicomdir_binary() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let result = plugin_test.eval("open --raw dicomdir/DICOMDIR | dcm dicomdir")?;
+    let result = result.into_value(Span::test_data())?;
+
+    // the location of the DICOMDIR is unknown, paths are relative
+    assert_eq!(get_string_by_cell_path(&result, "1.children.0.children.0.children.0.path"), "IMAGES/IM3");
+
+    Ok(())
+}
+
+#[test]
+fn test_not_dicomdir() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let error = plugin_test
+        .eval("\"file.dcm\" | dcm dicomdir")
+        .unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Not a DICOMDIR")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_make_dicomdir_roundtrip() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let result = plugin_test.eval(
+        "[dicomdir/IMAGES/IM1 dicomdir/IMAGES/IM3 dicomdir/IMAGES/IM2] | dcm make-dicomdir --root dicomdir --file-set-id TEST | dcm dicomdir",
+    )?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        2
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.DirectoryRecordType"), "PATIENT");
+    assert_eq!(get_string_by_cell_path(&result, "0.PatientName"), "Doe^John");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.StudyInstanceUID"), "1.2.3.1");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.children.0.SeriesInstanceUID"), "1.2.3.1.1");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.children.0.children.1.DirectoryRecordType"), "IMAGE");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.children.0.children.1.ReferencedSOPInstanceUIDInFile"), "1.2.3.1.1.2");
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.children.0.children.1.path"), "IMAGES/IM2");
+
+    assert_eq!(get_string_by_cell_path(&result, "1.PatientID"), "P2");
+    assert_eq!(get_string_by_cell_path(&result, "1.children.0.children.0.Modality"), "MR");
+    assert_eq!(get_string_by_cell_path(&result, "1.children.0.children.0.children.0.path"), "IMAGES/IM3");
+
+    Ok(())
+}
+
+#[test]
+fn test_make_dicomdir_skips_non_dicom() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let result = plugin_test.eval("[dicomdir/IMAGES/IM1 text/README.txt] | dcm make-dicomdir --root dicomdir | dcm dicomdir")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_by_cell_path(&result, "0.children.0.children.0.children.0.path"), "IMAGES/IM1");
+
+    Ok(())
+}
+
+#[test]
+fn test_make_dicomdir_invalid_file_id() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let error = plugin_test
+        .eval("\"file.dcm\" | dcm make-dicomdir")
+        .unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Invalid file-set file name")
+    );
+
+    Ok(())
+}
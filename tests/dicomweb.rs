@@ -1,3 +1,5 @@
+#![allow(clippy::result_large_err)]
+
 use nu_protocol::Span;
 use test_case::test_case;
 use test_utils::{assert_nothing_by_cell_path, get_string_by_cell_path, get_string_list_by_cell_path, setup_plugin_for_test};
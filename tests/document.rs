@@ -0,0 +1,80 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{eval, get_asset_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+const WRAP: &str = "\"documents/Report.pdf\" | dcm wrap-pdf --attributes {PatientName: \"Doe^John\", PatientID: P4, StudyInstanceUID: \"1.2.3.4\"}";
+
+#[test]
+fn test_wrap_pdf_roundtrip() -> Result<(), nu_protocol::ShellError> {
+    let result = eval(&format!("{WRAP} | dcm extract-document"))?;
+
+    assert_eq!(get_string_by_cell_path(&result, "DocumentTitle"), "Report");
+    assert_eq!(get_string_by_cell_path(&result, "MIMETypeOfEncapsulatedDocument"), "application/pdf");
+    assert_eq!(get_string_by_cell_path(&result, "extension"), "pdf");
+
+    // the PDF has an odd length, the padding of EncapsulatedDocument is removed
+    let pdf = std::fs::read(get_asset_path("documents/Report.pdf")).expect("PDF asset");
+    assert_eq!(pdf.len() % 2, 1);
+    assert_eq!(
+        result
+            .get_data_by_key("document")
+            .expect("document")
+            .as_binary()?,
+        pdf
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_wrap_pdf_attributes() -> Result<(), nu_protocol::ShellError> {
+    let result = eval(&format!("{WRAP} | dcm"))?;
+
+    assert_eq!(get_string_by_cell_path(&result, "SOPClassUID"), "1.2.840.10008.5.1.4.1.1.104.1");
+    assert_eq!(get_string_by_cell_path(&result, "Modality"), "DOC");
+    assert_eq!(get_string_by_cell_path(&result, "PatientName"), "Doe^John");
+    assert_eq!(get_string_by_cell_path(&result, "PatientID"), "P4");
+    assert_eq!(get_string_by_cell_path(&result, "StudyInstanceUID"), "1.2.3.4");
+    assert!(get_string_by_cell_path(&result, "SeriesInstanceUID").starts_with("2.25."));
+    assert_eq!(get_int_by_cell_path(&result, "EncapsulatedDocumentLength"), 329);
+
+    Ok(())
+}
+
+#[test]
+fn test_wrap_pdf_title() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("open --raw documents/Report.pdf | dcm wrap-pdf --title \"Discharge letter\" | dcm extract-document")?;
+
+    assert_eq!(get_string_by_cell_path(&result, "DocumentTitle"), "Discharge letter");
+
+    Ok(())
+}
+
+#[test]
+fn test_wrap_pdf_invalid() {
+    let error = eval("\"documents/Report.pdf\" | dcm wrap-pdf --attributes {PatientNom: \"Doe^John\"}")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Unknown attribute"), "{error}");
+
+    let error = eval("\"documents/Report.pdf\" | dcm wrap-pdf --attributes {SOPInstanceUID: \"1.2.3\"}")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Unsupported attribute"), "{error}");
+
+    let error = eval("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm wrap-pdf")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Failed to wrap PDF"), "{error}");
+}
+
+#[test]
+fn test_extract_document_missing() {
+    let error = eval("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm extract-document")
+        .unwrap_err()
+        .to_string();
+
+    assert!(error.contains("Failed to extract document"), "{error}");
+}
@@ -0,0 +1,65 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{assert_nothing_by_cell_path, eval, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+#[test]
+fn test_dose_ct() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"sr/CTDoseReport.dcm\" | dcm dose")?;
+
+    assert_eq!(get_string_by_cell_path(&result, "SOPInstanceUID"), "1.2.3.301");
+    assert_eq!(get_string_by_cell_path(&result, "report"), "CT");
+    assert_eq!(get_int_by_cell_path(&result, "totals.events"), 2);
+    assert_eq!(get_float_by_cell_path(&result, "totals.dlp_total"), 650.5);
+    assert_nothing_by_cell_path(&result, "totals.dap_total");
+
+    assert_eq!(get_int_by_cell_path(&result, "events.1.event"), 2);
+    assert_eq!(get_string_by_cell_path(&result, "events.1.irradiation_event_uid"), "1.2.3.4.5.2");
+    assert_eq!(get_string_by_cell_path(&result, "events.1.protocol"), "Abdomen");
+    assert_eq!(get_string_by_cell_path(&result, "events.1.target_region"), "Chest and Abdomen");
+    assert_eq!(get_string_by_cell_path(&result, "events.1.acquisition_type"), "Spiral Acquisition");
+    assert_eq!(get_float_by_cell_path(&result, "events.1.ctdi_vol"), 8.0);
+    assert_eq!(get_float_by_cell_path(&result, "events.1.dlp"), 250.25);
+    assert_eq!(get_string_by_cell_path(&result, "events.1.phantom_type"), "IEC Body Dosimetry Phantom");
+    assert_eq!(get_float_by_cell_path(&result, "events.1.kvp"), 100.0);
+    assert_eq!(get_float_by_cell_path(&result, "events.1.tube_current"), 150.0);
+    assert_eq!(get_float_by_cell_path(&result, "events.1.exposure_time"), 5.0);
+    assert_eq!(get_float_by_cell_path(&result, "events.1.scanning_length"), 250.0);
+    assert_nothing_by_cell_path(&result, "events.1.dap");
+
+    Ok(())
+}
+
+#[test]
+fn test_dose_projection() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("[sr/ProjectionDoseReport.dcm sr/CTDoseReport.dcm] | dcm dose")?;
+
+    assert_eq!(get_string_by_cell_path(&result, "0.report"), "projection");
+    assert_eq!(get_int_by_cell_path(&result, "0.totals.events"), 1);
+    assert_eq!(get_float_by_cell_path(&result, "0.totals.dap_total"), 0.00012);
+    assert_eq!(get_float_by_cell_path(&result, "0.totals.dose_rp_total"), 0.015);
+    assert_eq!(get_string_by_cell_path(&result, "0.events.0.acquisition_type"), "Stationary Acquisition");
+    assert_eq!(get_float_by_cell_path(&result, "0.events.0.dap"), 0.00012);
+    assert_eq!(get_float_by_cell_path(&result, "0.events.0.kvp"), 70.0);
+    assert_nothing_by_cell_path(&result, "0.events.0.ctdi_vol");
+
+    assert_eq!(get_string_by_cell_path(&result, "1.report"), "CT");
+
+    Ok(())
+}
+
+#[test]
+fn test_dose_skips_other_objects() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("[sr/MeasurementReport.dcm sr/CTDoseReport.dcm encodings/ExplicitVRLittleEndian.dcm text/README.txt] | dcm dose")?;
+
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        1
+    );
+    assert_eq!(get_string_by_cell_path(&result, "0.report"), "CT");
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{eval, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+#[test]
+fn test_frames_functional_groups() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"multiframe/EnhancedCT.dcm\" | dcm frames")?;
+
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        3
+    );
+
+    for (frame, z) in [0.0, 2.5, 5.0]
+        .into_iter()
+        .enumerate()
+    {
+        assert_eq!(get_string_by_cell_path(&result, &format!("{frame}.SOPInstanceUID")), "1.2.3.200");
+        assert_eq!(get_int_by_cell_path(&result, &format!("{frame}.frame")), frame as i64 + 1);
+
+        // per-frame functional groups
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.ImagePositionPatient.2")), z);
+        assert_eq!(get_int_by_cell_path(&result, &format!("{frame}.InStackPositionNumber")), frame as i64 + 1);
+        assert_eq!(get_int_by_cell_path(&result, &format!("{frame}.TemporalPositionIndex")), 1);
+        assert_eq!(get_int_by_cell_path(&result, &format!("{frame}.DimensionIndexValues.1")), frame as i64 + 1);
+        assert_eq!(get_string_by_cell_path(&result, &format!("{frame}.FrameType")), "ORIGINAL\nPRIMARY\nAXIAL\nNONE");
+
+        // shared functional groups
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.ImageOrientationPatient.0")), 1.0);
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.PixelSpacing.0")), 0.5);
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.SliceThickness")), 2.5);
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.WindowWidth")), 400.0);
+        assert_eq!(get_string_by_cell_path(&result, &format!("{frame}.RescaleType")), "HU");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_frames_without_functional_groups() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("[multiframe/EnhancedCT.dcm encodings/ExplicitVRLittleEndian.dcm] | dcm frames")?;
+
+    let frames = result.as_list()?;
+    assert_eq!(frames.len(), 4);
+
+    // a single frame, without other columns
+    assert_eq!(get_string_by_cell_path(&result, "3.SOPInstanceUID"), "1.2.3.100");
+    assert_eq!(get_int_by_cell_path(&result, "3.frame"), 1);
+    assert_eq!(
+        frames[3]
+            .as_record()?
+            .len(),
+        2
+    );
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{
+    eval, eval_with, get_bool_by_cell_path, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path, get_string_list_by_cell_path,
+};
+
+mod test_utils;
+
+#[test]
+fn test_geometry_regular_volume() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"multiframe/EnhancedCT.dcm\" | dcm geometry")?;
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        1
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.SeriesInstanceUID"), "1.2.3.4.1");
+    assert_eq!(get_int_by_cell_path(&result, "0.count"), 3);
+    assert_eq!(get_float_by_cell_path(&result, "0.normal.2"), 1.0);
+    assert_eq!(get_float_by_cell_path(&result, "0.slice_spacing"), 2.5);
+    assert!(get_bool_by_cell_path(&result, "0.uniform"));
+    assert_eq!(get_float_by_cell_path(&result, "0.gantry_tilt"), 0.0);
+    assert!(get_string_list_by_cell_path(&result, "0.issues").is_empty());
+
+    // columns are 0.6 mm apart along x, rows 0.5 mm along y and slices 2.5 mm along z
+    let affine = [[0.6, 0.0, 0.0, -10.0], [0.0, 0.5, 0.0, -20.0], [0.0, 0.0, 2.5, 0.0], [0.0, 0.0, 0.0, 1.0]];
+    for (row, values) in affine
+        .iter()
+        .enumerate()
+    {
+        for (column, value) in values
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(get_float_by_cell_path(&result, &format!("0.affine.{row}.{column}")), *value);
+            assert_eq!(
+                get_float_by_cell_path(&result, &format!("0.images.2.affine.{row}.{column}")),
+                if column == 3 && row == 2 {
+                    5.0
+                } else {
+                    *value
+                }
+            );
+        }
+    }
+
+    for (index, distance) in [0.0, 2.5, 5.0]
+        .into_iter()
+        .enumerate()
+    {
+        assert_eq!(get_int_by_cell_path(&result, &format!("0.images.{index}.frame")), index as i64 + 1);
+        assert_eq!(get_float_by_cell_path(&result, &format!("0.images.{index}.distance")), distance);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_geometry_sorts_along_normal() -> Result<(), nu_protocol::ShellError> {
+    let result = eval_with(vec![Box::new(nu_command::Reverse)], "\"multiframe/EnhancedCT.dcm\" | dcm split-frames | reverse | dcm geometry")?;
+
+    for (index, distance) in [0.0, 2.5, 5.0]
+        .into_iter()
+        .enumerate()
+    {
+        assert_eq!(get_float_by_cell_path(&result, &format!("0.images.{index}.distance")), distance);
+    }
+    assert_eq!(get_float_by_cell_path(&result, "0.images.1.spacing"), 2.5);
+
+    Ok(())
+}
+
+#[test]
+fn test_geometry_irregular_series() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("[multiframe/EnhancedCT.dcm multiframe/EnhancedCT-Irregular.dcm] | dcm geometry")?;
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        2
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "1.SeriesInstanceUID"), "1.2.3.4.2");
+    assert_eq!(get_float_by_cell_path(&result, "1.slice_spacing"), 2.5);
+    assert!(!get_bool_by_cell_path(&result, "1.uniform"));
+    assert_eq!(get_float_by_cell_path(&result, "1.gaps.0.from"), 5.0);
+    assert_eq!(get_float_by_cell_path(&result, "1.gaps.0.to"), 10.0);
+    let tilt = 0.2f64
+        .atan()
+        .to_degrees();
+    assert!((get_float_by_cell_path(&result, "1.gantry_tilt") - tilt).abs() < 1e-9);
+
+    let issues = get_string_list_by_cell_path(&result, "1.issues");
+    assert_eq!(issues.len(), 3, "{issues:?}");
+    assert_eq!(issues[0], "non-uniform slice spacing");
+    assert_eq!(issues[1], "gap of 5.000 between positions 5.000 and 10.000");
+    assert_eq!(issues[2], "gantry tilt of 11.31 degrees");
+
+    Ok(())
+}
+
+#[test]
+fn test_geometry_duplicates() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("[multiframe/EnhancedCT.dcm multiframe/EnhancedCT.dcm] | dcm geometry")?;
+
+    assert_eq!(get_int_by_cell_path(&result, "0.count"), 6);
+    assert_eq!(get_float_by_cell_path(&result, "0.slice_spacing"), 2.5);
+    assert!(get_bool_by_cell_path(&result, "0.uniform"));
+    assert_eq!(get_float_by_cell_path(&result, "0.duplicates.1.distance"), 2.5);
+    assert_eq!(get_int_by_cell_path(&result, "0.duplicates.1.count"), 2);
+    assert_eq!(
+        get_string_list_by_cell_path(&result, "0.issues"),
+        ["2 images at position 0.000", "2 images at position 2.500", "2 images at position 5.000"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_geometry_missing() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm geometry")?;
+
+    assert_eq!(get_int_by_cell_path(&result, "0.count"), 1);
+    assert_eq!(get_string_list_by_cell_path(&result, "0.issues"), ["1 images without ImagePositionPatient, ImageOrientationPatient or PixelSpacing"]);
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+#![allow(clippy::result_large_err)]
+
+use nu_protocol::Span;
+use test_case::test_case;
+use test_utils::{eval, setup_plugin_for_test};
+
+mod test_utils;
+
+fn eval_hash(command: &str) -> Result<String, nu_protocol::ShellError> {
+    Ok(eval(command)?
+        .as_str()?
+        .to_string())
+}
+
+#[test_case(""; "all elements")]
+#[test_case("--no-pixel-data"; "no pixel data")]
+#[test_case("--no-private"; "no private")]
+fn test_hash_independent_of_encoding(flags: &str) -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    // different byte order, VR encoding, preamble and group lengths
+    let result = plugin_test.eval(&format!(
+        "[encodings/ExplicitVRLittleEndian.dcm encodings/ImplicitVRLittleEndian.dcm encodings/ExplicitVRBigEndian.dcm] | dcm hash {flags}"
+    ))?;
+    let result = result.into_value(Span::test_data())?;
+
+    let hashes: Vec<&str> = result
+        .as_list()?
+        .iter()
+        .map(|hash| hash.as_str())
+        .collect::<Result<_, _>>()?;
+    assert_eq!(hashes.len(), 3);
+    assert_eq!(hashes[0], hashes[1]);
+    assert_eq!(hashes[0], hashes[2]);
+    assert_eq!(hashes[0].len(), 64);
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_exclude() -> Result<(), nu_protocol::ShellError> {
+    let all = eval_hash("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm hash")?;
+    let no_pixel_data = eval_hash("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm hash --no-pixel-data")?;
+    let no_private = eval_hash("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm hash --no-private")?;
+    let no_pixel_data_excluded = eval_hash("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm hash --exclude [PixelData]")?;
+
+    assert_ne!(all, no_pixel_data);
+    assert_ne!(all, no_private);
+    assert_eq!(no_pixel_data, no_pixel_data_excluded);
+
+    // the tag is excluded inside ReferencedImageSequence too
+    let excluded = eval_hash("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm hash --exclude ['(0008,1155)']")?;
+    assert_ne!(all, excluded);
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_different_content() -> Result<(), nu_protocol::ShellError> {
+    let im1 = eval_hash("\"dicomdir/IMAGES/IM1\" | dcm hash")?;
+    let im2 = eval_hash("\"dicomdir/IMAGES/IM2\" | dcm hash")?;
+
+    assert_ne!(im1, im2);
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_unknown_attribute() -> Result<(), nu_protocol::ShellError> {
+    let error = eval_hash("\"dicomdir/IMAGES/IM1\" | dcm hash --exclude [NoSuchAttribute]").unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Unknown attribute")
+    );
+
+    Ok(())
+}
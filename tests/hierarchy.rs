@@ -0,0 +1,100 @@
+#![allow(clippy::result_large_err)]
+
+use nu_protocol::Span;
+use test_utils::{
+    eval, eval_with, get_filesize_by_cell_path, get_int_by_cell_path, get_string_by_cell_path, get_string_list_by_cell_path, setup_plugin_for_test,
+};
+
+mod test_utils;
+
+#[test]
+fn test_hierarchy_from_files() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    // non-DICOM files are skipped
+    let result = plugin_test.eval("[dicomdir/IMAGES/IM1 text/README.txt dicomdir/IMAGES/IM3 dicomdir/IMAGES/IM2] | dcm hierarchy")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        2
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.PatientID"), "P1");
+    assert_eq!(get_int_by_cell_path(&result, "0.instances"), 2);
+    assert_eq!(get_string_by_cell_path(&result, "0.studies.0.StudyInstanceUID"), "1.2.3.1");
+    assert_eq!(get_string_by_cell_path(&result, "0.studies.0.modalities.0"), "CT");
+    assert_eq!(get_string_by_cell_path(&result, "0.studies.0.first_date"), "20240101");
+    assert_eq!(get_string_by_cell_path(&result, "0.studies.0.series.0.SeriesInstanceUID"), "1.2.3.1.1");
+    assert_eq!(get_int_by_cell_path(&result, "0.studies.0.series.0.instances"), 2);
+    assert!(get_string_by_cell_path(&result, "0.studies.0.series.0.paths.1").ends_with("IM2"));
+    assert!(get_string_list_by_cell_path(&result, "0.studies.0.inconsistent").is_empty());
+
+    assert_eq!(get_string_by_cell_path(&result, "1.PatientID"), "P2");
+    assert_eq!(get_string_by_cell_path(&result, "1.studies.0.series.0.Modality"), "MR");
+
+    Ok(())
+}
+
+#[test]
+fn test_hierarchy_from_records() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let result = plugin_test.eval(
+        "[
+            {PatientID: P1, PatientName: 'Doe^John', StudyInstanceUID: 1.1, SeriesInstanceUID: 1.1.1, ContentDate: 20240102, size: 1kB}
+            {PatientID: P1, PatientName: 'Doe^Jon', StudyInstanceUID: 1.1, SeriesInstanceUID: 1.1.2, ContentDate: 20240101, size: 2kB}
+        ] | dcm hierarchy",
+    )?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_list_by_cell_path(&result, "0.studies.0.series.SeriesInstanceUID"), ["1.1.1", "1.1.2"]);
+    assert_eq!(get_filesize_by_cell_path(&result, "0.size"), 3000);
+    assert_eq!(get_string_by_cell_path(&result, "0.first_date"), "20240101");
+    assert_eq!(get_string_by_cell_path(&result, "0.last_date"), "20240102");
+
+    assert_eq!(get_string_by_cell_path(&result, "0.studies.0.inconsistent.0.attribute"), "PatientName");
+    assert_eq!(get_string_by_cell_path(&result, "0.studies.0.inconsistent.0.values.1"), "Doe^Jon");
+
+    Ok(())
+}
+
+#[test]
+fn test_hierarchy_from_dcm_output() -> Result<(), nu_protocol::ShellError> {
+    // `dcm` records don't have file names and sizes
+    let result = eval("[dicomdir/IMAGES/IM1 dicomdir/IMAGES/IM2] | dcm | dcm hierarchy")?;
+
+    assert_eq!(get_int_by_cell_path(&result, "0.instances"), 2);
+    assert_eq!(get_filesize_by_cell_path(&result, "0.size"), 0);
+    assert!(get_string_list_by_cell_path(&result, "0.studies.0.series.0.paths").is_empty());
+
+    // merged with file records they're kept
+    let result = eval_with(
+        vec![Box::new(nu_command::Merge)],
+        "[[name size]; [dicomdir/IMAGES/IM1 1kB] [dicomdir/IMAGES/IM2 2kB]] | merge ([dicomdir/IMAGES/IM1 dicomdir/IMAGES/IM2] | dcm) | dcm hierarchy",
+    )?;
+
+    assert_eq!(get_filesize_by_cell_path(&result, "0.size"), 3000);
+    assert_eq!(get_string_list_by_cell_path(&result, "0.studies.0.series.0.paths"), ["dicomdir/IMAGES/IM1", "dicomdir/IMAGES/IM2"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_hierarchy_missing_uid() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let error = plugin_test
+        .eval("[{PatientID: P1}] | dcm hierarchy")
+        .unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Invalid DICOM instance")
+    );
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
+#![allow(clippy::result_large_err)]
+
 use nu_plugin_dcm::plugin::DcmPluginCommand;
 use nu_protocol::Span;
 use test_case::test_case;
-use test_utils::{get_string_by_cell_path, setup_plugin_for_test};
+use test_utils::{assert_nothing_by_cell_path, get_int_by_cell_path, get_string_by_cell_path, setup_plugin_for_test};
 
 mod test_utils;
 
@@ -64,6 +66,8 @@ fn test_command_vector_open() -> Result<(), nu_protocol::ShellError> {
 #[test_case("ls *-Preamble.dcm | sort-by name | dcm"; "ls *.dcm")] // list of records
 #[test_case("ls *-Preamble.dcm | sort-by name | select name type | dcm"; "ls *.dcm | select name")] // list of records
 #[test_case("ls *-Preamble.dcm | sort-by name | get name | dcm"; "ls *.dcm | get name")] // list of names
+#[test_case("ls *-Preamble.dcm | sort-by name | dcm --threads 2"; "ls *.dcm | dcm --threads 2")] // list of records, in parallel
+#[test_case("ls *-Preamble.dcm | sort-by name | get name | dcm -t 8"; "ls *.dcm | get name | dcm -t 8")] // more threads than files
 fn test_command_ls(command: &str) -> Result<(), nu_protocol::ShellError> {
     let mut plugin_test =
         setup_plugin_for_test(vec![Box::new(nu_command::Ls), Box::new(nu_command::SortBy), Box::new(nu_command::Get), Box::new(nu_command::Select)])?;
@@ -75,17 +79,55 @@ fn test_command_ls(command: &str) -> Result<(), nu_protocol::ShellError> {
         result
             .as_list()?
             .len(),
-        3
+        4
     );
 
-    assert_eq!(get_string_by_cell_path(&result, "0.PatientName"), "ExplicitVRBigEndian-Preamble");
-    assert_eq!(get_string_by_cell_path(&result, "0.TransferSyntax"), "1.2.840.10008.1.2.2");
+    assert_eq!(get_string_by_cell_path(&result, "0.PatientName"), "DeflatedExplicitVRLittleEndian-Preamble");
+    assert_eq!(get_string_by_cell_path(&result, "0.TransferSyntax"), "1.2.840.10008.1.2.1.99");
 
-    assert_eq!(get_string_by_cell_path(&result, "1.PatientName"), "ExplicitVRLittleEndian-Preamble");
-    assert_eq!(get_string_by_cell_path(&result, "1.TransferSyntax"), "1.2.840.10008.1.2.1");
+    assert_eq!(get_string_by_cell_path(&result, "1.PatientName"), "ExplicitVRBigEndian-Preamble");
+    assert_eq!(get_string_by_cell_path(&result, "1.TransferSyntax"), "1.2.840.10008.1.2.2");
+
+    assert_eq!(get_string_by_cell_path(&result, "2.PatientName"), "ExplicitVRLittleEndian-Preamble");
+    assert_eq!(get_string_by_cell_path(&result, "2.TransferSyntax"), "1.2.840.10008.1.2.1");
+
+    assert_eq!(get_string_by_cell_path(&result, "3.PatientName"), "ImplicitVRLittleEndian-Preamble");
+    assert_eq!(get_string_by_cell_path(&result, "3.TransferSyntax"), "1.2.840.10008.1.2");
+    Ok(())
+}
+
+#[test_case("ls *.dcm | dcm --threads 0"; "zero threads")]
+#[test_case("ls *.dcm | dcm --threads -1"; "negative threads")]
+fn test_fail_on_invalid_threads(command: &str) -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![Box::new(nu_command::Ls)])?;
+
+    let error = plugin_test
+        .eval(command)
+        .unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Invalid number of threads")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fail_on_unsupported_transfer_syntax() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![Box::new(nu_command::Ls)])?;
+
+    let error = plugin_test
+        .eval("ls *.dcm | dcm --transfer-syntax 1.2.3")
+        .unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Unsupported transfer syntax")
+    );
 
-    assert_eq!(get_string_by_cell_path(&result, "2.PatientName"), "ImplicitVRLittleEndian-Preamble");
-    assert_eq!(get_string_by_cell_path(&result, "2.TransferSyntax"), "1.2.840.10008.1.2");
     Ok(())
 }
 
@@ -101,7 +143,7 @@ fn test_fail_on_extra_parameters() -> Result<(), Box<dyn std::error::Error>> {
 
         let inner_error = labeled_error
             .inner
-            .get(0)
+            .first()
             .expect("Expected an inner error");
 
         dbg!(&inner_error);
@@ -113,3 +155,164 @@ fn test_fail_on_extra_parameters() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_structured_errors() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![Box::new(nu_command::IntoBinary), Box::new(nu_command::BytesAt)])?;
+
+    let result = plugin_test.eval(
+        "[
+            (open --raw file.dcm | into binary),
+            missing.dcm,
+            0x[00 01 02 03],
+            (open --raw file.dcm | into binary | bytes at 0..200),
+            (open --raw file.dcm | into binary | bytes at 0..260)
+        ] | dcm --error error",
+    )?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_by_cell_path(&result, "0.error"), "");
+    assert_eq!(get_string_by_cell_path(&result, "0.PatientName"), "ExplicitVRLittleEndian-Preamble");
+
+    assert_eq!(get_string_by_cell_path(&result, "1.error.kind"), "io");
+    assert!(get_string_by_cell_path(&result, "1.error.path").ends_with("missing.dcm"));
+    assert_nothing_by_cell_path(&result, "1.error.offset");
+
+    assert_eq!(get_string_by_cell_path(&result, "2.error.kind"), "not-dicom");
+    assert_nothing_by_cell_path(&result, "2.error.path");
+
+    // truncated in the file meta group
+    assert_eq!(get_string_by_cell_path(&result, "3.error.kind"), "truncated");
+
+    // truncated in the data set, which starts at offset 236
+    assert_eq!(get_string_by_cell_path(&result, "4.error.kind"), "truncated");
+    assert!(get_int_by_cell_path(&result, "4.error.offset") >= 236);
+
+    Ok(())
+}
+
+#[test]
+fn test_lenient_truncated() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![Box::new(nu_command::IntoBinary), Box::new(nu_command::BytesAt)])?;
+
+    let result = plugin_test.eval("[(open --raw file.dcm | into binary | bytes at 0..260), (open --raw file.dcm | into binary)] | dcm --lenient")?;
+    let result = result.into_value(Span::test_data())?;
+
+    // the meta group is complete but PatientName is cut short
+    assert_eq!(get_string_by_cell_path(&result, "0.TransferSyntax"), "1.2.840.10008.1.2.1");
+    assert!(get_int_by_cell_path(&result, "0.partial.offset") >= 236);
+
+    assert_eq!(get_string_by_cell_path(&result, "1.PatientName"), "ExplicitVRLittleEndian-Preamble");
+    assert_nothing_by_cell_path(&result, "1.partial");
+
+    Ok(())
+}
+
+// "RE" is an even group in little endian, the text must not be mistaken for a raw data set
+#[test_case("\"text/README.txt\" | dcm --error error"; "guessed transfer syntax")]
+#[test_case("\"text/README.txt\" | dcm --error error --transfer-syntax 1.2.840.10008.1.2.1"; "explicit transfer syntax")]
+#[test_case("\"text/README.txt\" | dcm --error error --transfer-syntax 1.2.840.10008.1.2"; "implicit transfer syntax")]
+fn test_text_file_not_dicom(command: &str) -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let result = plugin_test.eval(command)?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_by_cell_path(&result, "error.kind"), "not-dicom");
+
+    Ok(())
+}
+
+#[test_case("\"archives/studies.zip\" | dcm"; "zip file")]
+#[test_case("\"archives/studies.tar.gz\" | dcm"; "tar.gz file")]
+#[test_case("open --raw archives/studies.zip | dcm"; "zip byte stream")]
+#[test_case("open --raw archives/studies.tar.gz | into binary | dcm"; "tar.gz binary")]
+fn test_archive(command: &str) -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![Box::new(nu_command::Open), Box::new(nu_command::IntoBinary)])?;
+
+    let result = plugin_test.eval(command)?;
+    let result = result.into_value(Span::test_data())?;
+
+    // README.txt is skipped
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        2
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.archive_entry"), "study/ExplicitVRLittleEndian-Preamble.dcm");
+    assert_eq!(get_string_by_cell_path(&result, "0.PatientName"), "ExplicitVRLittleEndian-Preamble");
+
+    assert_eq!(get_string_by_cell_path(&result, "1.archive_entry"), "study/ImplicitVRLittleEndian-NoPreamble.dcm");
+    assert_eq!(get_string_by_cell_path(&result, "1.PatientName"), "ImplicitVRLittleEndian-NoPreamble");
+
+    Ok(())
+}
+
+#[test]
+fn test_archive_streamed() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![Box::new(nu_command::First)])?;
+
+    // entries are read as they're pulled, the rest of the archive is never read
+    let result = plugin_test.eval("\"archives/studies.tar.gz\" | dcm | first")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_by_cell_path(&result, "archive_entry"), "study/ExplicitVRLittleEndian-Preamble.dcm");
+
+    Ok(())
+}
+
+#[test]
+fn test_archive_errors() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let result = plugin_test.eval("\"archives/studies.zip\" | dcm --error error")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_by_cell_path(&result, "0.error"), "");
+    assert_eq!(get_string_by_cell_path(&result, "2.archive_entry"), "README.txt");
+    assert_eq!(get_string_by_cell_path(&result, "2.error.kind"), "not-dicom");
+    assert!(get_string_by_cell_path(&result, "2.error.path").ends_with("studies.zip"));
+
+    Ok(())
+}
+
+#[test]
+fn test_gzipped_file() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let result = plugin_test.eval("\"archives/single.dcm.gz\" | dcm")?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_by_cell_path(&result, "0.archive_entry"), "ExplicitVRLittleEndian-Preamble.dcm");
+    assert_eq!(get_string_by_cell_path(&result, "0.PatientName"), "ExplicitVRLittleEndian-Preamble");
+
+    Ok(())
+}
+
+#[test_case(None; "sequential")]
+#[test_case(Some(2); "parallel")]
+fn test_archives_in_list_stream(threads: Option<usize>) -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![Box::new(nu_command::Ls), Box::new(nu_command::SortBy), Box::new(nu_command::Get)])?;
+
+    let threads = threads
+        .map(|threads| format!("--threads {threads}"))
+        .unwrap_or_default();
+
+    // each archive produces a record per entry
+    let result = plugin_test.eval(&format!("ls archives/studies.* | sort-by name | get name | dcm {threads}"))?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        4
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.archive_entry"), "study/ExplicitVRLittleEndian-Preamble.dcm");
+    assert_eq!(get_string_by_cell_path(&result, "3.archive_entry"), "study/ImplicitVRLittleEndian-NoPreamble.dcm");
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+#![allow(clippy::result_large_err)]
+
+use nu_protocol::Value;
+use test_utils::{eval, eval_with, get_string_by_cell_path};
+
+mod test_utils;
+
+fn get_nifti(result: &Value) -> Vec<u8> {
+    result
+        .get_data_by_key("nifti")
+        .expect("nifti")
+        .as_binary()
+        .expect("binary")
+        .to_vec()
+}
+
+fn i16_at(
+    nifti: &[u8],
+    offset: usize,
+) -> i16 {
+    i16::from_le_bytes(
+        nifti[offset..offset + 2]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+fn f32_at(
+    nifti: &[u8],
+    offset: usize,
+) -> f32 {
+    f32::from_le_bytes(
+        nifti[offset..offset + 4]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+#[test]
+fn test_to_nifti_header_and_data() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"multiframe/EnhancedCT.dcm\" | dcm to-nifti")?;
+    let nifti = get_nifti(&result);
+
+    assert_eq!(
+        i32::from_le_bytes(
+            nifti[0..4]
+                .try_into()
+                .unwrap()
+        ),
+        348
+    );
+    assert_eq!(&nifti[344..348], b"n+1\0");
+    assert_eq!(f32_at(&nifti, 108), 352.0);
+
+    // 3 columns, 2 rows and 3 slices of float32
+    let dims: Vec<i16> = (0..4)
+        .map(|index| i16_at(&nifti, 40 + index * 2))
+        .collect();
+    assert_eq!(dims, [3, 3, 2, 3]);
+    assert_eq!(i16_at(&nifti, 70), 16);
+    assert_eq!(i16_at(&nifti, 72), 32);
+
+    let pixdim: Vec<f32> = (1..4)
+        .map(|index| f32_at(&nifti, 76 + index * 4))
+        .collect();
+    assert_eq!(pixdim, [0.6, 0.5, 2.5]);
+
+    // sform and qform in RAS, i.e. with the x and y axes of DICOM's LPS flipped
+    assert_eq!(i16_at(&nifti, 252), 1);
+    assert_eq!(i16_at(&nifti, 254), 1);
+    let srow: Vec<f32> = (0..12)
+        .map(|index| f32_at(&nifti, 280 + index * 4))
+        .collect();
+    assert_eq!(srow, [-0.6, 0.0, 0.0, 10.0, 0.0, -0.5, 0.0, 20.0, 0.0, 0.0, 2.5, 0.0]);
+    let quatern: Vec<f32> = (0..6)
+        .map(|index| f32_at(&nifti, 256 + index * 4))
+        .collect();
+    assert_eq!(quatern, [0.0, 0.0, 1.0, 10.0, 20.0, 0.0]);
+
+    // rescaled pixel values, slice after slice
+    let data: Vec<f32> = (0..18)
+        .map(|index| f32_at(&nifti, 352 + index * 4))
+        .collect();
+    let expected: Vec<f32> = (0..3)
+        .flat_map(|frame| (0..6).map(move |pixel| (frame * 100 + pixel - 1024) as f32))
+        .collect();
+    assert_eq!(nifti.len(), 352 + 18 * 4);
+    assert_eq!(data, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_to_nifti_sorts_slices() -> Result<(), nu_protocol::ShellError> {
+    let multiframe = get_nifti(&eval("\"multiframe/EnhancedCT.dcm\" | dcm to-nifti")?);
+    let single_frames =
+        get_nifti(&eval_with(vec![Box::new(nu_command::Reverse)], "\"multiframe/EnhancedCT.dcm\" | dcm split-frames | reverse | dcm to-nifti")?);
+
+    assert_eq!(multiframe, single_frames);
+
+    Ok(())
+}
+
+#[test]
+fn test_to_nifti_gzip() -> Result<(), nu_protocol::ShellError> {
+    let nifti = get_nifti(&eval("\"multiframe/EnhancedCT.dcm\" | dcm to-nifti --gzip")?);
+
+    assert_eq!(nifti[0..2], [0x1f, 0x8b]);
+
+    Ok(())
+}
+
+#[test]
+fn test_to_nifti_sidecar() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"multiframe/EnhancedCT.dcm\" | dcm to-nifti")?;
+
+    assert_eq!(get_string_by_cell_path(&result, "sidecar.PatientName"), "Doe^John");
+    assert_eq!(get_string_by_cell_path(&result, "sidecar.SeriesInstanceUID"), "1.2.3.4.1");
+    assert_eq!(get_string_by_cell_path(&result, "sidecar.RescaleType"), "HU");
+    assert_eq!(get_string_by_cell_path(&result, "sidecar.ConversionSoftware"), "nu_plugin_dcm");
+    assert!(
+        result
+            .get_data_by_key("sidecar")
+            .and_then(|sidecar| sidecar.get_data_by_key("PixelData"))
+            .is_none()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_to_nifti_multiple_series() {
+    let result = eval("[multiframe/EnhancedCT.dcm multiframe/EnhancedCT-Irregular.dcm] | dcm to-nifti");
+
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Multiple series")
+    );
+}
+
+#[test]
+fn test_to_nifti_duplicates() {
+    let result = eval("[multiframe/EnhancedCT.dcm multiframe/EnhancedCT.dcm] | dcm to-nifti");
+
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Duplicate positions")
+    );
+}
@@ -0,0 +1,204 @@
+#![allow(clippy::result_large_err)]
+
+use std::path::PathBuf;
+
+use nu_protocol::Span;
+use test_utils::{eval, get_string_by_cell_path, get_string_list_by_cell_path, setup_plugin_for_test};
+
+mod test_utils;
+
+/// Creates an empty output directory unique to the test.
+fn output_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nu_plugin_dcm_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn test_organize_dry_run() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+    let out = output_dir("organize_dry_run");
+
+    let result = plugin_test.eval(&format!(
+        "[dicomdir/IMAGES/IM1 dicomdir/IMAGES/IM3 file.dcm] | dcm organize --dry-run '{}/{{PatientID}}/{{Modality}}_{{SeriesNumber}}/{{InstanceNumber}}.dcm'",
+        out.display()
+    ))?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_list_by_cell_path(&result, "action"), ["copy", "copy", "copy"]);
+    assert_eq!(
+        get_string_by_cell_path(&result, "0.destination"),
+        out.join("P1/CT_1/1.dcm")
+            .to_string_lossy()
+    );
+    assert_eq!(
+        get_string_by_cell_path(&result, "1.destination"),
+        out.join("P2/MR_1/1.dcm")
+            .to_string_lossy()
+    );
+    assert!(!out.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_organize_copy() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+    let out = output_dir("organize_copy");
+
+    // IM1 and IM2 collide, the second IM1 is a duplicate
+    let result = plugin_test.eval(&format!(
+        "[dicomdir/IMAGES/IM1 dicomdir/IMAGES/IM2 dicomdir/IMAGES/IM1] | dcm organize '{}/{{PatientID}}/{{StudyID}}.dcm'",
+        out.display()
+    ))?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_list_by_cell_path(&result, "action"), ["copy", "copy", "skip"]);
+    assert_eq!(
+        get_string_by_cell_path(&result, "1.destination"),
+        out.join("P1/1_1.dcm")
+            .to_string_lossy()
+    );
+    assert!(get_string_by_cell_path(&result, "2.reason").starts_with("Duplicate of"));
+
+    assert_eq!(std::fs::read(out.join("P1/1.dcm")).unwrap(), std::fs::read(test_utils::get_asset_path("dicomdir/IMAGES/IM1")).unwrap());
+    assert!(
+        out.join("P1/1_1.dcm")
+            .exists()
+    );
+
+    // the destination now exists
+    let result =
+        plugin_test.eval(&format!("[dicomdir/IMAGES/IM1] | dcm organize --on-collision skip '{}/{{PatientID}}/{{StudyID}}.dcm'", out.display()))?;
+    let result = result.into_value(Span::test_data())?;
+
+    assert_eq!(get_string_by_cell_path(&result, "0.action"), "skip");
+    assert_eq!(get_string_by_cell_path(&result, "0.reason"), "Destination exists");
+
+    std::fs::remove_dir_all(&out).unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_organize_unreadable_files() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+    let out = output_dir("organize_unreadable");
+    let incoming = out.join("incoming");
+    std::fs::create_dir_all(&incoming).unwrap();
+
+    let file = std::fs::read(test_utils::get_asset_path("file.dcm")).unwrap();
+    std::fs::write(incoming.join("truncated.dcm"), &file[..260]).unwrap();
+    std::fs::write(incoming.join("file.dcm"), &file).unwrap();
+    std::fs::copy(test_utils::get_asset_path("text/README.txt"), incoming.join("README.txt")).unwrap();
+
+    let result = plugin_test.eval(&format!(
+        "['{0}/truncated.dcm' '{0}/file.dcm' '{0}/README.txt'] | dcm organize --move '{1}/{{PatientName}}.dcm'",
+        incoming.display(),
+        out.display()
+    ))?;
+    let result = result.into_value(Span::test_data())?;
+
+    // the truncated file doesn't stop the other files from being moved
+    assert_eq!(get_string_list_by_cell_path(&result, "action"), ["error", "move", "skip"]);
+    assert!(
+        incoming
+            .join("truncated.dcm")
+            .exists()
+    );
+    assert!(
+        !incoming
+            .join("file.dcm")
+            .exists()
+    );
+    assert!(
+        out.join("ExplicitVRLittleEndian-Preamble.dcm")
+            .exists()
+    );
+
+    std::fs::remove_dir_all(&out).unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_organize_move_overwrite_clashing_destinations() -> Result<(), nu_protocol::ShellError> {
+    let out = output_dir("organize_move_overwrite");
+    let incoming = out.join("incoming");
+    std::fs::create_dir_all(&incoming).unwrap();
+    std::fs::create_dir_all(out.join("P1")).unwrap();
+
+    let im1 = std::fs::read(test_utils::get_asset_path("dicomdir/IMAGES/IM1")).unwrap();
+    let im2 = std::fs::read(test_utils::get_asset_path("dicomdir/IMAGES/IM2")).unwrap();
+    std::fs::write(incoming.join("IM1"), &im1).unwrap();
+    std::fs::write(incoming.join("IM2"), &im2).unwrap();
+    std::fs::write(out.join("P1/1.dcm"), "stale").unwrap();
+
+    // IM1 and IM2 render the same destination, which also existed before
+    let result = eval(&format!(
+        "['{0}/IM1' '{0}/IM2'] | dcm organize --move --on-collision overwrite '{1}/{{PatientID}}/{{StudyID}}.dcm'",
+        incoming.display(),
+        out.display()
+    ))?;
+
+    assert_eq!(get_string_list_by_cell_path(&result, "action"), ["move", "move"]);
+    assert_eq!(
+        get_string_by_cell_path(&result, "1.destination"),
+        out.join("P1/1_1.dcm")
+            .to_string_lossy()
+    );
+
+    // the stale file is overwritten, the moved IM1 is not
+    assert_eq!(std::fs::read(out.join("P1/1.dcm")).unwrap(), im1);
+    assert_eq!(std::fs::read(out.join("P1/1_1.dcm")).unwrap(), im2);
+
+    std::fs::remove_dir_all(&out).unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_organize_move_collision_error() -> Result<(), nu_protocol::ShellError> {
+    let out = output_dir("organize_move_collision_error");
+    let incoming = out.join("incoming");
+    std::fs::create_dir_all(&incoming).unwrap();
+
+    std::fs::copy(test_utils::get_asset_path("dicomdir/IMAGES/IM1"), incoming.join("IM1")).unwrap();
+    std::fs::copy(test_utils::get_asset_path("dicomdir/IMAGES/IM2"), incoming.join("IM2")).unwrap();
+
+    // the collision of IM2 is reported without losing the row of the already moved IM1
+    let result = eval(&format!(
+        "['{0}/IM1' '{0}/IM2'] | dcm organize --move --on-collision error '{1}/{{PatientID}}/{{StudyID}}.dcm'",
+        incoming.display(),
+        out.display()
+    ))?;
+
+    assert_eq!(get_string_list_by_cell_path(&result, "action"), ["move", "error"]);
+    assert_eq!(get_string_by_cell_path(&result, "1.reason"), "Destination exists");
+    assert!(
+        incoming
+            .join("IM2")
+            .exists()
+    );
+
+    std::fs::remove_dir_all(&out).unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn test_organize_invalid_template() -> Result<(), nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(vec![])?;
+
+    let error = plugin_test
+        .eval("[file.dcm] | dcm organize 'out/{NoSuchAttribute}.dcm'")
+        .unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Invalid template")
+    );
+
+    Ok(())
+}
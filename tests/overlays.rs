@@ -0,0 +1,130 @@
+#![allow(clippy::result_large_err)]
+
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use nu_protocol::Value;
+use test_utils::{assert_nothing_by_cell_path, eval, get_bool_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+fn get_bitmap(
+    result: &Value,
+    index: usize,
+) -> Vec<u8> {
+    result
+        .as_list()
+        .expect("list")[index]
+        .get_data_by_key("bitmap")
+        .expect("bitmap")
+        .as_binary()
+        .expect("binary")
+        .to_vec()
+}
+
+#[test]
+fn test_overlays_separate() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"overlays/Overlays.dcm\" | dcm overlays")?;
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        5
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.SOPInstanceUID"), "1.2.3.500");
+    assert_eq!(get_string_by_cell_path(&result, "0.group"), "6000");
+    assert_eq!(get_int_by_cell_path(&result, "0.frame"), 1);
+    assert_eq!(get_int_by_cell_path(&result, "0.OverlayRows"), 4);
+    assert_eq!(get_int_by_cell_path(&result, "0.OverlayColumns"), 5);
+    assert_eq!(get_int_by_cell_path(&result, "0.OverlayOrigin.1"), 1);
+    assert_eq!(get_string_by_cell_path(&result, "0.OverlayType"), "G");
+    assert_eq!(get_string_by_cell_path(&result, "0.OverlayDescription"), "Arrow");
+    assert_eq!(get_string_by_cell_path(&result, "0.OverlayLabel"), "ANNOTATION");
+    assert!(!get_bool_by_cell_path(&result, "0.embedded"));
+    assert_nothing_by_cell_path(&result, "0.bit_position");
+    assert_eq!(get_bitmap(&result, 0), [1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0]);
+
+    // a multi-frame overlay has a record per frame
+    assert_eq!(get_string_by_cell_path(&result, "2.group"), "6004");
+    assert_eq!(get_int_by_cell_path(&result, "2.OverlayOrigin.0"), 2);
+    assert_eq!(get_int_by_cell_path(&result, "3.frame"), 2);
+    assert_eq!(get_bitmap(&result, 2), [1, 0, 0, 1]);
+    assert_eq!(get_bitmap(&result, 3), [0, 1, 1, 0]);
+
+    Ok(())
+}
+
+#[test]
+fn test_overlays_embedded() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"overlays/Overlays.dcm\" | dcm overlays")?;
+
+    assert_eq!(get_string_by_cell_path(&result, "1.group"), "6002");
+    assert_eq!(get_string_by_cell_path(&result, "1.OverlayType"), "R");
+    assert!(get_bool_by_cell_path(&result, "1.embedded"));
+    assert_eq!(get_int_by_cell_path(&result, "1.bit_position"), 12);
+    assert_eq!(get_bitmap(&result, 1), [1, 1, 1, 1, 1, 1, 0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 1, 1, 1]);
+
+    // bit 13 is set in a single pixel without overlay attributes
+    assert_nothing_by_cell_path(&result, "4.group");
+    assert!(get_bool_by_cell_path(&result, "4.embedded"));
+    assert_eq!(get_int_by_cell_path(&result, "4.bit_position"), 13);
+    let bitmap = get_bitmap(&result, 4);
+    assert_eq!(
+        bitmap
+            .iter()
+            .position(|pixel| *pixel == 1),
+        Some(13)
+    );
+    assert_eq!(
+        bitmap
+            .iter()
+            .filter(|pixel| **pixel == 1)
+            .count(),
+        1
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_overlays_png() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"overlays/Overlays.dcm\" | dcm overlays --png")?;
+    let png = get_bitmap(&result, 0);
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..20], 5u32.to_be_bytes());
+    assert_eq!(&png[20..24], 4u32.to_be_bytes());
+    assert_eq!(&png[24..26], [1, 0]);
+
+    // scanlines of the diagonal, each with its filter type and the pixels packed from the most significant bit
+    let length = u32::from_be_bytes(
+        png[33..37]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    assert_eq!(&png[37..41], b"IDAT");
+    let mut scanlines = Vec::new();
+    ZlibDecoder::new(&png[41..41 + length])
+        .read_to_end(&mut scanlines)
+        .expect("zlib");
+    assert_eq!(scanlines, [0, 0b1000_0000, 0, 0b0100_0000, 0, 0b0010_0000, 0, 0b0001_0000]);
+
+    assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+    Ok(())
+}
+
+#[test]
+fn test_overlays_without_overlays() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm overlays")?;
+
+    assert!(
+        result
+            .as_list()?
+            .is_empty()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{eval, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+fn assert_close(
+    actual: f64,
+    expected: f64,
+) {
+    assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+}
+
+#[test]
+fn test_rtdose_grid() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"rt/RTDOSE.dcm\" | dcm rtdose")?;
+
+    assert_eq!(get_string_by_cell_path(&result, "SOPInstanceUID"), "1.2.3.602");
+    assert_eq!(get_string_by_cell_path(&result, "DoseUnits"), "GY");
+    assert_eq!(get_string_by_cell_path(&result, "DoseType"), "PHYSICAL");
+    assert_eq!(get_string_by_cell_path(&result, "DoseSummationType"), "PLAN");
+    assert_eq!(get_float_by_cell_path(&result, "DoseGridScaling"), 0.001);
+    assert_eq!(get_string_by_cell_path(&result, "referenced_plan"), "1.2.3.601");
+    assert_eq!(get_int_by_cell_path(&result, "Rows"), 2);
+    assert_eq!(get_int_by_cell_path(&result, "Columns"), 3);
+    assert_eq!(get_int_by_cell_path(&result, "NumberOfFrames"), 2);
+    assert_eq!(get_float_by_cell_path(&result, "PixelSpacing.0"), 2.5);
+    assert_eq!(get_float_by_cell_path(&result, "GridFrameOffsetVector.1"), 5.0);
+
+    assert_close(get_float_by_cell_path(&result, "min"), 0.0);
+    assert_close(get_float_by_cell_path(&result, "max"), 2.5);
+    assert_close(get_float_by_cell_path(&result, "mean"), 0.8);
+
+    Ok(())
+}
+
+#[test]
+fn test_rtdose_frames() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"rt/RTDOSE.dcm\" | dcm rtdose")?;
+    assert_eq!(
+        result
+            .get_data_by_key("frames")
+            .expect("frames")
+            .as_list()?
+            .len(),
+        2
+    );
+
+    assert_eq!(get_int_by_cell_path(&result, "frames.0.frame"), 1);
+    assert_close(get_float_by_cell_path(&result, "frames.0.max"), 2.5);
+    assert_close(get_float_by_cell_path(&result, "frames.0.mean"), 1.25);
+
+    // the second frame is 5 mm further along the normal of the grid
+    assert_eq!(get_float_by_cell_path(&result, "frames.1.offset"), 5.0);
+    assert_eq!(get_float_by_cell_path(&result, "frames.1.position.0"), -5.0);
+    assert_eq!(get_float_by_cell_path(&result, "frames.1.position.2"), -5.0);
+    assert_close(get_float_by_cell_path(&result, "frames.1.min"), 0.1);
+    assert_close(get_float_by_cell_path(&result, "frames.1.max"), 0.6);
+    assert_close(get_float_by_cell_path(&result, "frames.1.mean"), 0.35);
+
+    Ok(())
+}
+
+#[test]
+fn test_rtdose_32_bit_grid() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"rt/RTDOSE-32.dcm\" | dcm rtdose")?;
+
+    assert_eq!(get_float_by_cell_path(&result, "DoseGridScaling"), 0.000001);
+    assert_close(get_float_by_cell_path(&result, "max"), 2.5);
+    assert_close(get_float_by_cell_path(&result, "mean"), 0.8);
+    assert_close(get_float_by_cell_path(&result, "frames.0.mean"), 1.25);
+    assert_close(get_float_by_cell_path(&result, "frames.1.min"), 0.1);
+    assert_close(get_float_by_cell_path(&result, "frames.1.max"), 0.6);
+
+    Ok(())
+}
+
+#[test]
+fn test_rtdose_not_dose() {
+    let error = eval("\"rt/RTPLAN.dcm\" | dcm rtdose")
+        .unwrap_err()
+        .to_string();
+
+    assert!(error.contains("Invalid RT Dose"), "{error}");
+}
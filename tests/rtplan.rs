@@ -0,0 +1,62 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{eval, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+#[test]
+fn test_rtplan_summary() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"rt/RTPLAN.dcm\" | dcm rtplan")?;
+
+    assert_eq!(get_string_by_cell_path(&result, "SOPInstanceUID"), "1.2.3.601");
+    assert_eq!(get_string_by_cell_path(&result, "RTPlanLabel"), "PROSTATE");
+    assert_eq!(get_string_by_cell_path(&result, "RTPlanGeometry"), "PATIENT");
+    assert_eq!(get_string_by_cell_path(&result, "referenced_structure_set"), "1.2.3.600");
+
+    assert_eq!(get_int_by_cell_path(&result, "fraction_groups.0.NumberOfFractionsPlanned"), 20);
+    assert_eq!(get_int_by_cell_path(&result, "fraction_groups.0.NumberOfBeams"), 2);
+    assert_eq!(get_int_by_cell_path(&result, "fraction_groups.0.beams.1.ReferencedBeamNumber"), 2);
+    assert_eq!(get_float_by_cell_path(&result, "fraction_groups.0.beams.1.BeamMeterset"), 80.0);
+
+    assert_eq!(get_string_by_cell_path(&result, "beams.0.BeamName"), "ARC1");
+    assert_eq!(get_string_by_cell_path(&result, "beams.0.BeamType"), "DYNAMIC");
+    assert_eq!(get_string_by_cell_path(&result, "beams.0.RadiationType"), "PHOTON");
+    assert_eq!(get_int_by_cell_path(&result, "beams.0.NumberOfControlPoints"), 3);
+    assert_eq!(get_float_by_cell_path(&result, "beams.0.BeamMeterset"), 120.5);
+    assert_eq!(get_float_by_cell_path(&result, "beams.1.FinalCumulativeMetersetWeight"), 100.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_rtplan_control_points() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"rt/RTPLAN.dcm\" | dcm rtplan")?;
+
+    assert_eq!(get_int_by_cell_path(&result, "beams.0.control_points.1.ControlPointIndex"), 1);
+    assert_eq!(get_float_by_cell_path(&result, "beams.0.control_points.1.CumulativeMetersetWeight"), 0.5);
+    assert_eq!(get_float_by_cell_path(&result, "beams.0.control_points.1.meterset"), 60.25);
+    assert_eq!(get_float_by_cell_path(&result, "beams.0.control_points.1.GantryAngle"), 270.0);
+
+    // attributes that are not repeated keep the value of the previous control point
+    assert_eq!(get_float_by_cell_path(&result, "beams.0.control_points.1.NominalBeamEnergy"), 6.0);
+    assert_eq!(get_float_by_cell_path(&result, "beams.0.control_points.2.BeamLimitingDeviceAngle"), 30.0);
+    assert_eq!(get_string_by_cell_path(&result, "beams.0.control_points.1.GantryRotationDirection"), "CW");
+    assert_eq!(get_string_by_cell_path(&result, "beams.0.control_points.2.GantryRotationDirection"), "NONE");
+    assert_eq!(get_float_by_cell_path(&result, "beams.0.control_points.2.IsocenterPosition.2"), 10.0);
+
+    // metersets of a beam with a final cumulative meterset weight of 100
+    assert_eq!(get_float_by_cell_path(&result, "beams.1.control_points.0.meterset"), 0.0);
+    assert_eq!(get_float_by_cell_path(&result, "beams.1.control_points.1.meterset"), 80.0);
+    assert_eq!(get_float_by_cell_path(&result, "beams.1.control_points.1.GantryAngle"), 90.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_rtplan_not_plan() {
+    let error = eval("\"rt/RTSTRUCT.dcm\" | dcm rtplan")
+        .unwrap_err()
+        .to_string();
+
+    assert!(error.contains("Invalid RT Plan"), "{error}");
+}
@@ -0,0 +1,72 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{assert_nothing_by_cell_path, eval, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+#[test]
+fn test_rtstruct_rois() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"rt/RTSTRUCT.dcm\" | dcm rtstruct")?;
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        5
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.SOPInstanceUID"), "1.2.3.600");
+    assert_eq!(get_int_by_cell_path(&result, "0.ROINumber"), 1);
+    assert_eq!(get_string_by_cell_path(&result, "0.ROIName"), "External");
+    assert_eq!(get_string_by_cell_path(&result, "0.RTROIInterpretedType"), "EXTERNAL");
+    assert_eq!(get_int_by_cell_path(&result, "0.ROIDisplayColor.1"), 255);
+    assert_eq!(get_string_by_cell_path(&result, "0.ReferencedFrameOfReferenceUID"), "1.2.3.4.8");
+
+    assert_eq!(get_string_by_cell_path(&result, "2.ROIName"), "PTV");
+    assert_eq!(get_string_by_cell_path(&result, "2.RTROIInterpretedType"), "PTV");
+    assert_eq!(get_int_by_cell_path(&result, "2.ROIDisplayColor.0"), 255);
+
+    // an ROI without contours has a single row without points
+    assert_eq!(get_string_by_cell_path(&result, "4.ROIName"), "Bladder");
+    assert_eq!(get_string_by_cell_path(&result, "4.RTROIInterpretedType"), "ORGAN");
+    assert_nothing_by_cell_path(&result, "4.ROIDisplayColor");
+    assert_nothing_by_cell_path(&result, "4.ContourNumber");
+    assert!(
+        result.as_list()?[4]
+            .get_data_by_key("points")
+            .expect("points")
+            .as_list()?
+            .is_empty()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rtstruct_contours() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"rt/RTSTRUCT.dcm\" | dcm rtstruct")?;
+
+    assert_eq!(get_int_by_cell_path(&result, "1.ROINumber"), 1);
+    assert_eq!(get_int_by_cell_path(&result, "1.ContourNumber"), 2);
+    assert_eq!(get_string_by_cell_path(&result, "1.ContourGeometricType"), "CLOSED_PLANAR");
+    assert_eq!(get_string_by_cell_path(&result, "1.ReferencedSOPInstanceUID"), "1.2.3.102");
+    assert_eq!(get_int_by_cell_path(&result, "1.NumberOfContourPoints"), 3);
+    assert_eq!(get_float_by_cell_path(&result, "1.points.1.x"), 8.0);
+    assert_eq!(get_float_by_cell_path(&result, "1.points.1.y"), -8.0);
+    assert_eq!(get_float_by_cell_path(&result, "1.points.1.z"), 2.5);
+
+    // a point marker is not drawn on an image
+    assert_eq!(get_string_by_cell_path(&result, "3.ContourGeometricType"), "POINT");
+    assert_nothing_by_cell_path(&result, "3.ReferencedSOPInstanceUID");
+    assert_eq!(get_float_by_cell_path(&result, "3.points.0.z"), 1.25);
+
+    Ok(())
+}
+
+#[test]
+fn test_rtstruct_not_structure_set() {
+    let error = eval("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm rtstruct")
+        .unwrap_err()
+        .to_string();
+
+    assert!(error.contains("Invalid RT Structure Set"), "{error}");
+}
@@ -1,11 +1,15 @@
+#![allow(clippy::result_large_err)]
+
 use std::env;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
-use nu_plugin_dcm::plugin::{DcmPlugin, DcmPluginCommand};
-use nu_protocol::{IntoPipelineData, LabeledError};
+use nu_plugin_dcm::plugin::{DcmOptions, DcmPlugin, DcmPluginCommand};
+use nu_protocol::{ByteStream, IntoPipelineData, LabeledError, ListStream, PipelineData, Signals};
 
 use nu_protocol::{Span, Value};
 use test_case::test_case;
-use test_utils::{filepath, get_asset_path, get_string_by_cell_path};
+use test_utils::{filepath, get_asset_path, get_bool_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
 
 mod test_utils;
 
@@ -17,7 +21,7 @@ fn no_input_without_errors() {
     let cmd = DcmPluginCommand;
 
     let value = Value::nothing(Span::test_data());
-    let actual = cmd.process_pipeline_data(p, current_dir, None, &value.span(), value.into_pipeline_data());
+    let actual = cmd.process_pipeline_data(p, current_dir, DcmOptions::default(), Signals::empty(), &value.span(), value.into_pipeline_data());
 
     let expected_err =
         LabeledError::new("Unrecognized type in stream").with_label("'dcm' expects a string (filepath), binary, or column path", Span::test_data());
@@ -40,6 +44,31 @@ fn no_input_without_errors() {
     "1.2.840.10008.1.2",
     "ImplicitVRLittleEndian-Preamble";
     "read_implicit_vr_little_endian_preamble")]
+#[test_case(
+    "ExplicitVRBigEndian-NoPreamble.dcm",
+    "1.2.840.10008.1.2.2",
+    "ExplicitVRBigEndian-NoPreamble";
+    "read_explicit_vr_big_endian_no_preamble")]
+#[test_case(
+    "ExplicitVRLittleEndian-NoPreamble.dcm",
+    "1.2.840.10008.1.2.1",
+    "ExplicitVRLittleEndian-NoPreamble";
+    "read_explicit_vr_little_endian_no_preamble")]
+#[test_case(
+    "ImplicitVRLittleEndian-NoPreamble.dcm",
+    "1.2.840.10008.1.2",
+    "ImplicitVRLittleEndian-NoPreamble";
+    "read_implicit_vr_little_endian_no_preamble")]
+#[test_case(
+    "DeflatedExplicitVRLittleEndian-Preamble.dcm",
+    "1.2.840.10008.1.2.1.99",
+    "DeflatedExplicitVRLittleEndian-Preamble";
+    "read_deflated_explicit_vr_little_endian_preamble")]
+#[test_case(
+    "DeflatedExplicitVRLittleEndian-NoPreamble.dcm",
+    "1.2.840.10008.1.2.1.99",
+    "DeflatedExplicitVRLittleEndian-NoPreamble";
+    "read_deflated_explicit_vr_little_endian_no_preamble")]
 fn read_dcm_file(
     filename: &str,
     transfer_syntax: &str,
@@ -52,7 +81,7 @@ fn read_dcm_file(
     let cmd = DcmPluginCommand;
 
     let value = filepath(filename);
-    let actual = cmd.process_pipeline_data(p, current_dir, None, &value.span(), value.into_pipeline_data());
+    let actual = cmd.process_pipeline_data(p, current_dir, DcmOptions::default(), Signals::empty(), &value.span(), value.into_pipeline_data());
     let actual_value = actual
         .unwrap()
         .into_value(Span::test_data())
@@ -64,59 +93,269 @@ fn read_dcm_file(
     assert_eq!(get_string_by_cell_path(&actual_value, "PatientName"), patient_name);
 }
 
-#[test]
-#[ignore]
-fn read_explicit_vr_big_endian_no_preamble() {
-    let filename = get_asset_path("ExplicitVRBigEndian-NoPreamble.dcm");
+/// Returns the data set of a DICOM file, i.e. strips the preamble and the file meta group.
+fn raw_dataset(filename: &str) -> Vec<u8> {
+    let bytes = std::fs::read(get_asset_path(filename)).unwrap();
+    assert_eq!(&bytes[128..132], b"DICM");
+
+    let group_length = u32::from_le_bytes(
+        bytes[140..144]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+
+    bytes[144 + group_length..].to_vec()
+}
+
+#[test_case("ExplicitVRBigEndian-Preamble.dcm", None, "1.2.840.10008.1.2.2", "ExplicitVRBigEndian-Preamble"; "explicit_vr_big_endian_guessed")]
+#[test_case("ExplicitVRLittleEndian-Preamble.dcm", None, "1.2.840.10008.1.2.1", "ExplicitVRLittleEndian-Preamble"; "explicit_vr_little_endian_guessed")]
+#[test_case("ImplicitVRLittleEndian-Preamble.dcm", None, "1.2.840.10008.1.2", "ImplicitVRLittleEndian-Preamble"; "implicit_vr_little_endian_guessed")]
+#[test_case(
+    "ImplicitVRLittleEndian-Preamble.dcm",
+    Some("1.2.840.10008.1.2"),
+    "1.2.840.10008.1.2",
+    "ImplicitVRLittleEndian-Preamble";
+    "implicit_vr_little_endian_override")]
+#[test_case(
+    "DeflatedExplicitVRLittleEndian-Preamble.dcm",
+    Some("1.2.840.10008.1.2.1.99"),
+    "1.2.840.10008.1.2.1.99",
+    "DeflatedExplicitVRLittleEndian-Preamble";
+    "deflated_explicit_vr_little_endian_override")]
+fn read_raw_dataset(
+    filename: &str,
+    transfer_syntax_override: Option<&str>,
+    transfer_syntax: &str,
+    patient_name: &str,
+) {
     let current_dir = Ok(env::current_dir().unwrap());
 
     let p = DcmPlugin::default();
     let cmd = DcmPluginCommand;
 
-    let value = filepath(filename);
-    let actual = cmd.process_pipeline_data(p, current_dir, None, &value.span(), value.into_pipeline_data());
-    let _actual_value = actual
+    let options = DcmOptions { transfer_syntax: transfer_syntax_override.map(String::from), ..Default::default() };
+
+    let value = Value::test_binary(raw_dataset(filename));
+    let actual = cmd.process_pipeline_data(p, current_dir, options, Signals::empty(), &value.span(), value.into_pipeline_data());
+    let actual_value = actual
         .unwrap()
         .into_value(Span::test_data())
         .unwrap();
 
-    todo!()
+    // the file meta group is synthesized, the data sets don't contain SOP Class/Instance UIDs
+    assert_eq!(get_string_by_cell_path(&actual_value, "TransferSyntax"), transfer_syntax);
+    assert_eq!(get_string_by_cell_path(&actual_value, "MediaStorageSOPInstanceUID"), "");
+    assert_eq!(get_string_by_cell_path(&actual_value, "PatientName"), patient_name);
 }
 
 #[test]
-#[ignore]
-fn read_explicit_vr_little_endian_no_preamble() {
-    let filename = get_asset_path("ExplicitVRLittleEndian-NoPreamble.dcm");
+fn interrupted_byte_stream() {
+    let filename = get_asset_path("ExplicitVRLittleEndian-Preamble.dcm");
     let current_dir = Ok(env::current_dir().unwrap());
 
     let p = DcmPlugin::default();
     let cmd = DcmPluginCommand;
 
-    let value = filepath(filename);
-    let actual = cmd.process_pipeline_data(p, current_dir, None, &value.span(), value.into_pipeline_data());
-    let _actual_value = actual
+    let signals = Signals::new(Arc::new(AtomicBool::new(true)));
+    let bytes = std::fs::read(filename).unwrap();
+    let input = PipelineData::byte_stream(ByteStream::read_binary(bytes, Span::test_data(), Signals::empty()), None);
+
+    let actual = cmd.process_pipeline_data(p, current_dir, DcmOptions::default(), signals, &Span::test_data(), input);
+
+    assert_eq!(
+        actual
+            .unwrap_err()
+            .msg,
+        "Operation interrupted"
+    );
+}
+
+#[test]
+fn interrupted_list_stream() {
+    let filename = get_asset_path("ExplicitVRLittleEndian-Preamble.dcm");
+    let current_dir = Ok(env::current_dir().unwrap());
+
+    let p = DcmPlugin::default();
+    let cmd = DcmPluginCommand;
+
+    let signals = Signals::new(Arc::new(AtomicBool::new(true)));
+    let stream = ListStream::new(std::iter::repeat(filepath(filename)), Span::test_data(), Signals::empty());
+    let input = PipelineData::list_stream(stream, None);
+
+    let actual = cmd.process_pipeline_data(p, current_dir, DcmOptions::default(), signals, &Span::test_data(), input);
+
+    // the infinite stream must stop immediately
+    let actual_value = actual
         .unwrap()
         .into_value(Span::test_data())
         .unwrap();
 
-    todo!()
+    assert!(
+        actual_value
+            .as_list()
+            .unwrap()
+            .is_empty()
+    );
+}
+
+/// Returns a valid DICOM file followed by a sequence with a broken item and one more top level element.
+fn dcm_with_broken_sequence() -> Vec<u8> {
+    let mut bytes = std::fs::read(get_asset_path("ExplicitVRLittleEndian-Preamble.dcm")).unwrap();
+
+    // ContentSequence, undefined length
+    bytes.extend_from_slice(&[0x40, 0x00, 0x30, 0xA7, b'S', b'Q', 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    // item, undefined length
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    // ValueType
+    bytes.extend_from_slice(&[0x40, 0x00, 0x40, 0xA0, b'C', b'S', 4, 0, b'T', b'E', b'X', b'T']);
+    // TextValue with a bogus length
+    bytes.extend_from_slice(&[0x40, 0x00, 0x60, 0xA1, b'U', b'T', 0, 0, 0x00, 0x10, 0x00, 0x00]);
+    // sequence delimitation item, there's no item delimitation item
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+    // StorageMediaFileSetUID
+    bytes.extend_from_slice(&[0x88, 0x00, 0x40, 0x01, b'U', b'I', 6, 0, b'1', b'.', b'2', b'.', b'3', 0]);
+
+    bytes
 }
 
 #[test]
-#[ignore]
-fn read_implicit_vr_little_endian_no_preamble() {
-    let filename = get_asset_path("ImplicitVRLittleEndian-NoPreamble.dcm");
+fn read_broken_sequence_strict() {
     let current_dir = Ok(env::current_dir().unwrap());
 
-    let p = DcmPlugin::default();
-    let cmd = DcmPluginCommand;
+    let value = Value::test_binary(dcm_with_broken_sequence());
+    let actual = DcmPluginCommand.process_pipeline_data(
+        DcmPlugin::default(),
+        current_dir,
+        DcmOptions::default(),
+        Signals::empty(),
+        &value.span(),
+        value.into_pipeline_data(),
+    );
 
-    let value = filepath(filename);
-    let actual = cmd.process_pipeline_data(p, current_dir, None, &value.span(), value.into_pipeline_data());
-    let _actual_value = actual
+    assert!(actual.is_err());
+}
+
+#[test_case(false; "lenient")]
+#[test_case(true; "lenient with resync")]
+fn read_broken_sequence_lenient(resync: bool) {
+    let current_dir = Ok(env::current_dir().unwrap());
+    let options = DcmOptions { lenient: true, resync, ..Default::default() };
+
+    let value = Value::test_binary(dcm_with_broken_sequence());
+    let actual = DcmPluginCommand.process_pipeline_data(
+        DcmPlugin::default(),
+        current_dir,
+        options,
+        Signals::empty(),
+        &value.span(),
+        value.into_pipeline_data(),
+    );
+    let actual_value = actual
+        .unwrap()
+        .into_value(Span::test_data())
+        .unwrap();
+
+    assert_eq!(get_string_by_cell_path(&actual_value, "PatientName"), "ExplicitVRLittleEndian-Preamble");
+    assert_eq!(get_string_by_cell_path(&actual_value, "ContentSequence.0.ValueType"), "TEXT");
+    assert_eq!(get_bool_by_cell_path(&actual_value, "partial.resynchronized"), resync);
+    assert!(get_int_by_cell_path(&actual_value, "partial.offset") > 132);
+
+    let record = actual_value
+        .as_record()
+        .unwrap();
+    assert_eq!(record.contains("StorageMediaFileSetUID"), resync);
+}
+
+/// Returns a valid DICOM file followed by a sequence with an icon image with encapsulated pixel data, more elements after
+/// the icon and one more top level element.
+fn dcm_with_encapsulated_icon() -> Vec<u8> {
+    let mut bytes = std::fs::read(get_asset_path("ExplicitVRLittleEndian-Preamble.dcm")).unwrap();
+
+    // ReferencedImageSequence and its item, undefined length
+    bytes.extend_from_slice(&[0x08, 0x00, 0x40, 0x11, b'S', b'Q', 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    // IconImageSequence and its item, undefined length
+    bytes.extend_from_slice(&[0x88, 0x00, 0x00, 0x02, b'S', b'Q', 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    // Rows
+    bytes.extend_from_slice(&[0x28, 0x00, 0x10, 0x00, b'U', b'S', 2, 0, 1, 0]);
+    // PixelData, undefined length, with an empty offset table, two fragments and a sequence delimitation item
+    bytes.extend_from_slice(&[0xE0, 0x7F, 0x10, 0x00, b'O', b'B', 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0, 0, 0, 0, 0]);
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0, 4, 0, 0, 0, 0xFF, 0xD8, 0xFF, 0xD9]);
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0, 4, 0, 0, 0, 0xFF, 0xD8, 0xFF, 0xD9]);
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+    // item and sequence delimitation items of IconImageSequence
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0x0D, 0xE0, 0, 0, 0, 0]);
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+    // PresentationLUTShape in the item of ReferencedImageSequence
+    bytes.extend_from_slice(&[0x50, 0x20, 0x20, 0x00, b'C', b'S', 8, 0, b'I', b'N', b'V', b'E', b'R', b'S', b'E', b' ']);
+    // item and sequence delimitation items of ReferencedImageSequence
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0x0D, 0xE0, 0, 0, 0, 0]);
+    bytes.extend_from_slice(&[0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+    // top level PresentationLUTShape
+    bytes.extend_from_slice(&[0x50, 0x20, 0x20, 0x00, b'C', b'S', 8, 0, b'I', b'D', b'E', b'N', b'T', b'I', b'T', b'Y']);
+
+    bytes
+}
+
+#[test]
+fn read_encapsulated_icon_lenient() {
+    let current_dir = Ok(env::current_dir().unwrap());
+    let options = DcmOptions { lenient: true, ..Default::default() };
+
+    let value = Value::test_binary(dcm_with_encapsulated_icon());
+    let actual = DcmPluginCommand.process_pipeline_data(
+        DcmPlugin::default(),
+        current_dir,
+        options,
+        Signals::empty(),
+        &value.span(),
+        value.into_pipeline_data(),
+    );
+    let actual_value = actual
+        .unwrap()
+        .into_value(Span::test_data())
+        .unwrap();
+
+    // the fragments don't close the enclosing items and sequences early
+    assert_eq!(get_int_by_cell_path(&actual_value, "ReferencedImageSequence.0.IconImageSequence.0.Rows"), 1);
+    assert_eq!(get_string_by_cell_path(&actual_value, "ReferencedImageSequence.0.PresentationLUTShape"), "INVERSE");
+    assert_eq!(get_string_by_cell_path(&actual_value, "PresentationLUTShape"), "IDENTITY");
+}
+
+#[test_case(None; "file")]
+#[test_case(Some("1.2.840.10008.1.2.1.99"); "raw data set")]
+fn read_deflated_lenient(transfer_syntax: Option<&str>) {
+    let current_dir = Ok(env::current_dir().unwrap());
+    let options = DcmOptions { lenient: true, transfer_syntax: transfer_syntax.map(String::from), ..Default::default() };
+
+    let bytes = match transfer_syntax {
+        Some(_) => raw_dataset("DeflatedExplicitVRLittleEndian-Preamble.dcm"),
+        None => std::fs::read(get_asset_path("DeflatedExplicitVRLittleEndian-Preamble.dcm")).unwrap(),
+    };
+
+    let value = Value::test_binary(bytes);
+    let actual = DcmPluginCommand.process_pipeline_data(
+        DcmPlugin::default(),
+        current_dir,
+        options,
+        Signals::empty(),
+        &value.span(),
+        value.into_pipeline_data(),
+    );
+    let actual_value = actual
         .unwrap()
         .into_value(Span::test_data())
         .unwrap();
 
-    todo!()
+    assert_eq!(get_string_by_cell_path(&actual_value, "TransferSyntax"), "1.2.840.10008.1.2.1.99");
+    assert_eq!(get_string_by_cell_path(&actual_value, "PatientName"), "DeflatedExplicitVRLittleEndian-Preamble");
+
+    assert!(
+        actual_value
+            .get_data_by_key("partial")
+            .unwrap()
+            .is_nothing()
+    );
 }
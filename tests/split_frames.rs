@@ -0,0 +1,82 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{eval, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+const ENHANCED_CT: &str = "multiframe/EnhancedCT.dcm";
+
+#[test]
+fn test_split_frames_attributes() -> Result<(), nu_protocol::ShellError> {
+    let result = eval(&format!("\"{ENHANCED_CT}\" | dcm split-frames | dcm"))?;
+
+    let frames = result.as_list()?;
+    assert_eq!(frames.len(), 3);
+
+    for (frame, z) in [0.0, 2.5, 5.0]
+        .into_iter()
+        .enumerate()
+    {
+        assert_eq!(get_string_by_cell_path(&result, &format!("{frame}.SOPClassUID")), "1.2.840.10008.5.1.4.1.1.2");
+        assert_eq!(get_int_by_cell_path(&result, &format!("{frame}.InstanceNumber")), frame as i64 + 1);
+        assert_eq!(get_string_by_cell_path(&result, &format!("{frame}.PatientName")), "Doe^John");
+
+        // functional groups are moved to the top level
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.ImagePositionPatient.2")), z);
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.ImageOrientationPatient.4")), 1.0);
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.PixelSpacing.1")), 0.6);
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.SliceThickness")), 2.5);
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.WindowCenter")), 40.0);
+        assert_eq!(get_float_by_cell_path(&result, &format!("{frame}.RescaleIntercept")), -1024.0);
+        assert_eq!(get_string_by_cell_path(&result, &format!("{frame}.ImageType")), "ORIGINAL\nPRIMARY\nAXIAL\nNONE");
+
+        let record = frames[frame].as_record()?;
+        for column in ["NumberOfFrames", "SharedFunctionalGroupsSequence", "PerFrameFunctionalGroupsSequence", "FrameType"] {
+            assert!(!record.contains(column), "{column} in frame {frame}");
+        }
+
+        // every frame is a new instance
+        let sop_instance_uid = get_string_by_cell_path(&result, &format!("{frame}.SOPInstanceUID"));
+        assert!(sop_instance_uid.starts_with("2.25."));
+        assert_eq!(get_string_by_cell_path(&result, &format!("{frame}.MediaStorageSOPInstanceUID")), sop_instance_uid);
+    }
+
+    assert_ne!(get_string_by_cell_path(&result, "0.SOPInstanceUID"), get_string_by_cell_path(&result, "1.SOPInstanceUID"));
+
+    Ok(())
+}
+
+#[test]
+fn test_split_frames_pixel_data() -> Result<(), nu_protocol::ShellError> {
+    let result = eval(&format!("\"{ENHANCED_CT}\" | dcm split-frames"))?;
+
+    // pixel values are the frame number times 100 plus the pixel index, PixelData is the last element
+    for (frame, file) in result
+        .as_list()?
+        .iter()
+        .enumerate()
+    {
+        let expected: Vec<u8> = (0..6u16)
+            .flat_map(|pixel| (frame as u16 * 100 + pixel).to_le_bytes())
+            .collect();
+        assert!(
+            file.as_binary()?
+                .ends_with(&expected)
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_split_frames_without_pixel_data() -> Result<(), nu_protocol::ShellError> {
+    let error = eval("\"ExplicitVRLittleEndian-Preamble.dcm\" | dcm split-frames").unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Failed to split frames")
+    );
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+#![allow(clippy::result_large_err)]
+
+use test_utils::{assert_nothing_by_cell_path, eval, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+#[test]
+fn test_sr_content_tree() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"sr/MeasurementReport.dcm\" | dcm sr")?;
+
+    let paths: Vec<String> = result
+        .as_list()?
+        .iter()
+        .map(|row| {
+            row.get_data_by_key("path")
+                .and_then(|path| {
+                    path.coerce_string()
+                        .ok()
+                })
+                .unwrap_or_default()
+        })
+        .collect();
+    assert_eq!(paths, ["1", "1.1", "1.2", "1.3", "1.4", "1.4.1", "1.4.2", "1.4.2.1", "1.4.2.1.1", "1.4.3", "1.4.3.1", "1.4.4", "1.4.5", "1.4.6"]);
+
+    // root
+    assert_eq!(get_string_by_cell_path(&result, "0.SOPInstanceUID"), "1.2.3.300");
+    assert_nothing_by_cell_path(&result, "0.relationship");
+    assert_eq!(get_string_by_cell_path(&result, "0.value_type"), "CONTAINER");
+    assert_eq!(get_string_by_cell_path(&result, "0.concept"), "Imaging Measurement Report");
+    assert_eq!(get_string_by_cell_path(&result, "0.concept_code"), "126000");
+    assert_eq!(get_string_by_cell_path(&result, "0.concept_scheme"), "DCM");
+    assert_nothing_by_cell_path(&result, "0.value");
+
+    // observation context
+    assert_eq!(get_string_by_cell_path(&result, "2.relationship"), "HAS OBS CONTEXT");
+    assert_eq!(get_string_by_cell_path(&result, "2.value"), "Smith^Jane");
+    assert_eq!(get_string_by_cell_path(&result, "3.value"), "20240101120000");
+
+    Ok(())
+}
+
+#[test]
+fn test_sr_values() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"sr/MeasurementReport.dcm\" | dcm sr")?;
+
+    // NUM
+    assert_eq!(get_string_by_cell_path(&result, "5.concept"), "Volume");
+    assert_eq!(get_float_by_cell_path(&result, "5.value"), 12.5);
+    assert_eq!(get_string_by_cell_path(&result, "5.units"), "cm3");
+    assert_eq!(get_float_by_cell_path(&result, "6.value"), 4.2);
+    assert_eq!(get_string_by_cell_path(&result, "6.units"), "mm");
+
+    // SCOORD and the image it is selected from
+    assert_eq!(get_string_by_cell_path(&result, "7.relationship"), "INFERRED FROM");
+    assert_eq!(get_string_by_cell_path(&result, "7.graphic_type"), "POLYLINE");
+    assert_eq!(get_float_by_cell_path(&result, "7.value.2"), 30.5);
+    assert_eq!(get_string_by_cell_path(&result, "8.value_type"), "IMAGE");
+    assert_eq!(get_string_by_cell_path(&result, "8.value"), "1.2.3.100");
+    assert_eq!(get_string_by_cell_path(&result, "8.referenced_sop_class"), "1.2.840.10008.5.1.4.1.1.2");
+    assert_eq!(get_int_by_cell_path(&result, "8.referenced_frames.0"), 1);
+
+    // TEXT with a by-reference relationship
+    assert_eq!(get_string_by_cell_path(&result, "9.value"), "Stable since prior");
+    assert_nothing_by_cell_path(&result, "10.value_type");
+    assert_eq!(get_string_by_cell_path(&result, "10.value"), "1.4.1");
+
+    // CODE, UIDREF and DATE
+    assert_eq!(get_string_by_cell_path(&result, "11.value"), "Mass");
+    assert_eq!(get_string_by_cell_path(&result, "11.code"), "4147007");
+    assert_eq!(get_string_by_cell_path(&result, "11.scheme"), "SCT");
+    assert_eq!(get_string_by_cell_path(&result, "12.value"), "1.2.3.4.1");
+    assert_eq!(get_string_by_cell_path(&result, "13.value"), "20240101");
+
+    Ok(())
+}
+
+#[test]
+fn test_sr_skips_other_objects() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("[encodings/ExplicitVRLittleEndian.dcm sr/MeasurementReport.dcm text/README.txt] | dcm sr")?;
+
+    let rows = result.as_list()?;
+    assert_eq!(rows.len(), 14);
+    assert!(
+        rows.iter()
+            .all(|row| row.get_data_by_key("SOPInstanceUID") == Some(nu_protocol::Value::test_string("1.2.3.300")))
+    );
+
+    Ok(())
+}
@@ -1,3 +1,6 @@
+// nu_protocol::ShellError, returned by the test support API, exceeds the lint threshold
+#![allow(clippy::result_large_err)]
+
 use std::path::PathBuf;
 
 use nu_plugin_dcm::plugin::DcmPlugin;
@@ -46,6 +49,24 @@ pub fn setup_plugin_for_test(nu_commands: Vec<Box<dyn nu_protocol::engine::Comma
     Ok(plugin_test)
 }
 
+/// Evaluates `command` with the plugin and the given nu commands, and collects the result into a `Value`.
+#[allow(dead_code)]
+pub fn eval_with(
+    nu_commands: Vec<Box<dyn nu_protocol::engine::Command>>,
+    command: &str,
+) -> Result<Value, nu_protocol::ShellError> {
+    let mut plugin_test = setup_plugin_for_test(nu_commands)?;
+    plugin_test
+        .eval(command)?
+        .into_value(Span::test_data())
+}
+
+/// Evaluates `command` with the plugin, and collects the result into a `Value`.
+#[allow(dead_code)]
+pub fn eval(command: &str) -> Result<Value, nu_protocol::ShellError> {
+    eval_with(vec![], command)
+}
+
 /// Accesses a nested `Value` using a cell path string and panics on failure.
 ///
 /// This is a private helper function for tests to reduce boilerplate.
@@ -120,6 +141,19 @@ pub fn get_int_by_cell_path(
         .unwrap_or_else(|e| panic!("Expected int at path '{}', but found '{}'. Error: {}", path, result_value.get_type(), e))
 }
 
+/// Asserts that the value at `path` is a filesize and returns it in bytes. Panics on failure.
+#[allow(dead_code)]
+pub fn get_filesize_by_cell_path(
+    value: &Value,
+    path: &str,
+) -> i64 {
+    let result_value = get_value_by_cell_path(value, path);
+    result_value
+        .as_filesize()
+        .map(|size| size.get())
+        .unwrap_or_else(|e| panic!("Expected filesize at path '{}', but found '{}'. Error: {}", path, result_value.get_type(), e))
+}
+
 /// Asserts that the value at `path` is a bool and returns it. Panics on failure.
 #[allow(dead_code)]
 pub fn get_bool_by_cell_path(
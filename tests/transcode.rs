@@ -0,0 +1,56 @@
+#![allow(clippy::result_large_err)]
+
+use test_case::test_case;
+use test_utils::{eval, get_string_by_cell_path};
+
+mod test_utils;
+
+const ORIGINAL: &str = "encodings/ExplicitVRLittleEndian.dcm";
+
+#[test_case("implicit-le", "1.2.840.10008.1.2"; "implicit little endian")]
+#[test_case("explicit-be", "1.2.840.10008.1.2.2"; "explicit big endian")]
+#[test_case("deflated-le", "1.2.840.10008.1.2.1.99"; "deflated")]
+#[test_case("1.2.840.10008.1.2", "1.2.840.10008.1.2"; "uid")]
+fn test_transcode_native(
+    to: &str,
+    uid: &str,
+) -> Result<(), nu_protocol::ShellError> {
+    let transcoded = eval(&format!("\"{ORIGINAL}\" | dcm transcode --to {to} | dcm"))?;
+    assert_eq!(get_string_by_cell_path(&transcoded, "TransferSyntax"), uid);
+    assert_eq!(get_string_by_cell_path(&transcoded, "PatientName"), "Doe^Jane");
+
+    // the content is the same, including pixel data
+    let original_hash = eval(&format!("\"{ORIGINAL}\" | dcm hash"))?;
+    let transcoded_hash = eval(&format!("\"{ORIGINAL}\" | dcm transcode --to {to} | dcm hash"))?;
+    assert_eq!(transcoded_hash, original_hash);
+
+    Ok(())
+}
+
+#[test]
+fn test_transcode_jpeg_baseline_roundtrip() -> Result<(), nu_protocol::ShellError> {
+    let compressed = eval(&format!("\"{ORIGINAL}\" | dcm transcode --to jpeg-baseline --quality 100 | dcm"))?;
+    assert_eq!(get_string_by_cell_path(&compressed, "TransferSyntax"), "1.2.840.10008.1.2.4.50");
+
+    // compression is lossy, but the rest of the data set is unchanged
+    let decompressed = eval(&format!("\"{ORIGINAL}\" | dcm transcode --to jpeg-baseline | dcm transcode --to explicit-le | dcm"))?;
+    assert_eq!(get_string_by_cell_path(&decompressed, "TransferSyntax"), "1.2.840.10008.1.2.1");
+    assert_eq!(get_string_by_cell_path(&decompressed, "PatientName"), "Doe^Jane");
+
+    Ok(())
+}
+
+#[test_case("1.2.3"; "unknown uid")]
+#[test_case("foo"; "unknown name")]
+#[test_case("1.2.840.10008.1.2.5"; "decode only")]
+fn test_transcode_unsupported(to: &str) -> Result<(), nu_protocol::ShellError> {
+    let error = eval(&format!("\"{ORIGINAL}\" | dcm transcode --to {to}")).unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Unsupported transfer syntax")
+    );
+
+    Ok(())
+}
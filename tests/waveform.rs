@@ -0,0 +1,95 @@
+#![allow(clippy::result_large_err)]
+
+use nu_protocol::Value;
+use test_utils::{eval, get_float_by_cell_path, get_int_by_cell_path, get_string_by_cell_path};
+
+mod test_utils;
+
+fn get_column(
+    samples: &Value,
+    column: &str,
+) -> Vec<f64> {
+    samples
+        .as_list()
+        .expect("samples")
+        .iter()
+        .map(|row| {
+            row.get_data_by_key(column)
+                .expect("column")
+                .as_float()
+                .expect("float")
+        })
+        .collect()
+}
+
+#[test]
+fn test_waveform_channels() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"waveform/ECG.dcm\" | dcm waveform")?;
+    assert_eq!(
+        result
+            .as_list()?
+            .len(),
+        2
+    );
+
+    assert_eq!(get_string_by_cell_path(&result, "0.SOPInstanceUID"), "1.2.3.400");
+    assert_eq!(get_int_by_cell_path(&result, "0.group"), 1);
+    assert_eq!(get_string_by_cell_path(&result, "0.MultiplexGroupLabel"), "RHYTHM");
+    assert_eq!(get_float_by_cell_path(&result, "0.SamplingFrequency"), 500.0);
+
+    // channels are named after their label, or their source without label
+    assert_eq!(get_string_by_cell_path(&result, "0.channels.0.name"), "Lead I");
+    assert_eq!(get_string_by_cell_path(&result, "0.channels.2.name"), "V1");
+    assert_eq!(get_string_by_cell_path(&result, "0.channels.2.source"), "Lead V1");
+    assert_eq!(get_string_by_cell_path(&result, "0.channels.2.units"), "uV");
+    assert_eq!(get_float_by_cell_path(&result, "0.channels.2.sensitivity"), 2.5);
+
+    Ok(())
+}
+
+#[test]
+fn test_waveform_samples() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"waveform/ECG.dcm\" | dcm waveform")?;
+
+    let rhythm = &result.as_list()?[0]
+        .get_data_by_key("samples")
+        .expect("samples");
+    assert_eq!(get_column(rhythm, "time"), [0.0, 0.002, 0.004, 0.006]);
+    assert_eq!(get_column(rhythm, "Lead I"), [0.0, 250.0, 10.0, -2.5]);
+    assert_eq!(get_column(rhythm, "Lead II"), [25.0, 500.0, 20.0, -5.0]);
+    assert_eq!(get_column(rhythm, "V1"), [-25.0, -750.0, 30.0, -7.5]);
+
+    // 8-bit signed samples with a baseline of 10 and a correction factor of 2
+    let pressure = &result.as_list()?[1]
+        .get_data_by_key("samples")
+        .expect("samples");
+    assert_eq!(get_column(pressure, "time"), [0.0, 0.004, 0.008]);
+    assert_eq!(get_column(pressure, "Pressure"), [20.0, 16.0, 120.0]);
+
+    Ok(())
+}
+
+#[test]
+fn test_waveform_raw() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"waveform/ECG.dcm\" | dcm waveform --raw")?;
+
+    let pressure = &result.as_list()?[1]
+        .get_data_by_key("samples")
+        .expect("samples");
+    assert_eq!(get_column(pressure, "Pressure"), [0.0, -4.0, 100.0]);
+
+    Ok(())
+}
+
+#[test]
+fn test_waveform_without_waveforms() -> Result<(), nu_protocol::ShellError> {
+    let result = eval("\"encodings/ExplicitVRLittleEndian.dcm\" | dcm waveform")?;
+
+    assert!(
+        result
+            .as_list()?
+            .is_empty()
+    );
+
+    Ok(())
+}